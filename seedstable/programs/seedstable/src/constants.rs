@@ -1,24 +1,108 @@
-use anchor_lang::prelude::*;
-
 // Protocol seeds
 pub const PROTOCOL_SEED: &[u8] = b"protocol";
 pub const COLLATERAL_POOL_SEED: &[u8] = b"collateral_pool";
 pub const USER_POSITION_SEED: &[u8] = b"user_position";
 pub const ORACLE_CONFIG_SEED: &[u8] = b"oracle_config";
 pub const TREASURY_SEED: &[u8] = b"treasury";
+pub const PRICE_HISTORY_SEED: &[u8] = b"price_history";
+pub const AUCTION_SEED: &[u8] = b"auction";
+pub const STABILITY_POOL_SEED: &[u8] = b"stability_pool";
+pub const STABILITY_VAULT_SEED: &[u8] = b"stability_vault";
+pub const STABILITY_COLLATERAL_SEED: &[u8] = b"stability_collateral";
+pub const STABILITY_DEPOSIT_SEED: &[u8] = b"stability_deposit";
+pub const POSITION_INDEX_SEED: &[u8] = b"position_index";
+pub const PSM_STATE_SEED: &[u8] = b"state";
+pub const PSM_VAULT_SEED: &[u8] = b"psm_vault";
+pub const SOL_UNWRAP_SEED: &[u8] = b"sol_unwrap";
+pub const SEED_LOT_REGISTRY_SEED: &[u8] = b"seed_lot_registry";
+pub const SEED_LOT_SEED: &[u8] = b"seed_lot";
 
 // Protocol constants
 pub const MIN_COLLATERAL_RATIO: u64 = 15000; // 150% in basis points
 pub const PEG_DEVIATION_THRESHOLD: u64 = 200; // 2% in basis points
 pub const LIQUIDATION_THRESHOLD: u64 = 12000; // 120% in basis points
+pub const MAX_LIQUIDATION_THRESHOLD: u64 = 9500; // 95% LTV in basis points
+pub const MAX_LIQUIDATION_BONUS: u64 = 2000; // 20% in basis points
+pub const MIN_LIQUIDATION_BONUS: u64 = 100; // 1% in basis points
+pub const DEFAULT_CLOSE_FACTOR: u64 = 5000; // 50% of debt per liquidation
+pub const MIN_CLOSE_FACTOR: u64 = 1000; // 10% in basis points
+pub const FULL_LIQUIDATION_HEALTH_FACTOR: u64 = 9500; // Below 0.95 the whole debt can be repaid
+
+// Liquidation auction constants
+pub const DEFAULT_AUCTION_START_PREMIUM: u64 = 12000; // Start at 120% of oracle price
+pub const DEFAULT_AUCTION_FLOOR: u64 = 8000; // Decay down to 80% of oracle price
+pub const DEFAULT_AUCTION_DURATION: i64 = 3600; // 1 hour
+pub const MAX_AUCTION_START_PREMIUM: u64 = 15000; // 150% in basis points
+pub const MIN_AUCTION_FLOOR: u64 = 5000; // 50% in basis points
+pub const MIN_AUCTION_DURATION: i64 = 300; // 5 minutes
+pub const MAX_AUCTION_DURATION: i64 = 86400; // 24 hours
+
+// Redemption constants
+pub const REDEMPTION_FEE_FLOOR: u64 = 50; // 0.5% in basis points
+pub const MAX_REDEMPTION_FEE: u64 = 500; // 5% in basis points
+pub const REDEMPTION_BETA: u64 = 2; // Base rate rises by redeemed share of supply / BETA
+pub const REDEMPTION_HALF_LIFE: i64 = 12 * 60 * 60; // Base rate halves every 12 hours
+
+// Stability pool constants
+pub const PRODUCT_PRECISION: u128 = 1_000_000_000_000_000_000; // 1.0 for the product P
+pub const SCALE_FACTOR: u128 = 1_000_000_000; // P is rescaled by this when it gets too small
+pub const STABILITY_SUM_HISTORY: usize = 8; // Scales of reward sums kept per collateral
+pub const MAX_STABILITY_POOL_COLLATERALS: usize = 5; // Collateral pools a stability pool can absorb
 pub const BASIS_POINTS: u64 = 10000;
 pub const USD_DECIMALS: u8 = 6;
 
+// Stability fee constants
+pub const RATE_INDEX_PRECISION: u128 = 1_000_000_000_000; // 1.0 in rate index units
+pub const SECONDS_PER_YEAR: i64 = 365 * 24 * 60 * 60;
+pub const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+pub const DEFAULT_STABILITY_FEE: u64 = 200; // 2% per year in basis points
+pub const MAX_STABILITY_FEE: u64 = 2500; // 25% per year in basis points
+
+// Peg stability module constants
+pub const MAX_PSM_FEE: u64 = 500; // 5% in basis points
+
+// Flash mint constants
+pub const DEFAULT_FLASH_MINT_FEE: u64 = 9; // 0.09% in basis points
+pub const MAX_FLASH_MINT_FEE: u64 = 100; // 1% in basis points
+
+// Pause flags, one per operation
+pub const PAUSE_DEPOSIT: u16 = 1 << 0;
+pub const PAUSE_WITHDRAW: u16 = 1 << 1; // Also gates redemptions
+pub const PAUSE_MINT: u16 = 1 << 2; // Also gates flash mints
+pub const PAUSE_BURN: u16 = 1 << 3;
+pub const PAUSE_LIQUIDATE: u16 = 1 << 4; // Also gates auctions
+pub const PAUSE_REBALANCE: u16 = 1 << 5;
+pub const PAUSE_ORACLE_UPDATE: u16 = 1 << 6;
+pub const PAUSE_ALL: u16 = (1 << 7) - 1;
+// Emergency pauses leave deposits, repayments and the prices they need open
+// so positions can still be made safer
+pub const DEFAULT_PAUSE_FLAGS: u16 = PAUSE_WITHDRAW | PAUSE_MINT | PAUSE_LIQUIDATE | PAUSE_REBALANCE;
+
+// Authority constants
+pub const MAX_MULTISIG_OWNERS: usize = 10; // Owners of an authority multisig
+
 // Oracle constants
 pub const MAX_PRICE_STALENESS: i64 = 300; // 5 minutes
 pub const MAX_PRICE_DEVIATION: u64 = 500; // 5% in basis points
+pub const MAX_ORACLE_SOURCES: usize = 4; // Primary feeds per collateral pool
+
+// Seed lot registry constants
+pub const MAX_SEED_LOT_ATTESTORS: usize = 8; // Attestors per registry
+pub const MAX_SEED_LOT_TEXT_LEN: usize = 32; // Bytes for crop, variety and warehouse
+pub const MAX_ATTESTATION_PERIOD: i64 = 90 * 24 * 60 * 60; // 90 days
+pub const SEED_LOT_AGE_HAIRCUT: u64 = 1000; // 10% of the attested price per year since harvest
+pub const SEED_LOT_STALE_HAIRCUT: u64 = 500; // 5% per day past the attestation expiry
+
+// Price history constants
+pub const PRICE_HISTORY_CAPACITY: usize = 32; // Observations kept per pool
+pub const DEFAULT_TWAP_WINDOW: i64 = 1800; // 30 minutes
+pub const MIN_TWAP_WINDOW: i64 = 60; // 1 minute
+pub const MAX_TWAP_WINDOW: i64 = 86400; // 24 hours
 
 // Collateral limits
 pub const MAX_USDC_DEPOSIT: u64 = 1_000_000 * 1_000_000; // 1M USDC
 pub const MAX_SOL_DEPOSIT: u64 = 10_000 * 1_000_000_000; // 10K SOL
 pub const MAX_SEEDS_DEPOSIT: u64 = 100_000 * 1_000_000; // 100K Seeds
+pub const MIN_SUNSET_DURATION: i64 = 7 * 24 * 60 * 60; // 1 week
+pub const MAX_SUNSET_DURATION: i64 = SECONDS_PER_YEAR;
+pub const MAX_COLLATERAL_POSITIONS: usize = 5; // Collateral pools per user position
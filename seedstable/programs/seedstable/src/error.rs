@@ -2,6 +2,8 @@ use anchor_lang::prelude::*;
 
 #[error_code]
 pub enum ErrorCode {
+    // Must stay first: code 6000 is what the PSM's former SeedError::Paused
+    // returned, and existing PSM clients match on it
     #[msg("Protocol is currently paused")]
     ProtocolPaused,
     #[msg("Insufficient collateral ratio")]
@@ -30,4 +32,124 @@ pub enum ErrorCode {
     InvalidOracleConfig,
     #[msg("Math overflow")]
     MathOverflow,
+    #[msg("Not enough valid oracle sources to meet quorum")]
+    OracleQuorumNotMet,
+    #[msg("Collateral pool already has the maximum number of oracle sources")]
+    TooManyOracleSources,
+    #[msg("User position already holds the maximum number of collateral pools")]
+    TooManyCollateralPositions,
+    #[msg("Collateral pool accounts for the position are missing or out of order")]
+    MissingCollateralAccounts,
+    #[msg("Account is not owned by the SeedStable program")]
+    InvalidAccountOwner,
+    #[msg("Risk parameters are out of range")]
+    InvalidRiskParameters,
+    #[msg("Collateral pool debt ceiling exceeded")]
+    DebtCeilingExceeded,
+    #[msg("Stability fee exceeds the maximum")]
+    InvalidStabilityFee,
+    #[msg("Close factor is out of range")]
+    InvalidCloseFactor,
+    #[msg("Liquidation amount must be greater than zero")]
+    InvalidLiquidationAmount,
+    #[msg("Auction parameters are out of range")]
+    InvalidAuctionParameters,
+    #[msg("Collateral pool uses a different liquidation mode")]
+    WrongLiquidationMode,
+    #[msg("Auction price is above the buyer's limit")]
+    AuctionPriceExceeded,
+    #[msg("Auction is still running")]
+    AuctionStillActive,
+    #[msg("Auction has nothing left to sell")]
+    AuctionFinished,
+    #[msg("Stability pool cannot absorb this much debt")]
+    StabilityPoolDepleted,
+    #[msg("Stability pool does not accept this collateral")]
+    StabilityPoolCollateralNotSupported,
+    #[msg("Stability pool already accepts the maximum number of collaterals")]
+    TooManyStabilityPoolCollaterals,
+    #[msg("Stability pool accounts are missing")]
+    MissingStabilityPoolAccounts,
+    #[msg("Redemption fee exceeds the caller's maximum")]
+    RedemptionFeeExceeded,
+    #[msg("Positions must be passed in position index order")]
+    RedemptionOrderViolated,
+    #[msg("No debt could be redeemed from the positions passed")]
+    NothingToRedeem,
+    #[msg("Position index hint accounts are missing or do not match the list")]
+    PositionIndexMismatch,
+    #[msg("Position index hints do not bracket the position's collateral ratio")]
+    InvalidPositionIndexHint,
+    #[msg("PSM fee exceeds the maximum")]
+    InvalidPsmFee,
+    #[msg("PSM USDC exposure cap exceeded")]
+    PsmExposureCapExceeded,
+    #[msg("SEEDS mint must be controlled by the PSM and share decimals with USDC")]
+    InvalidPsmMint,
+    #[msg("Not allowed while the protocol is in recovery mode")]
+    RecoveryModeRestricted,
+    #[msg("Global settlement has been triggered")]
+    SettlementActive,
+    #[msg("Global settlement has not been triggered")]
+    SettlementNotActive,
+    #[msg("Collateral pool has no settlement price")]
+    SettlementPriceNotFrozen,
+    #[msg("Collateral pool settlement price is already frozen")]
+    SettlementPriceAlreadyFrozen,
+    #[msg("Prices or position debt are not fully settled yet")]
+    SettlementIncomplete,
+    #[msg("Stablecoin mint authority is not the protocol")]
+    InvalidMintAuthority,
+    #[msg("Flash mint exceeds the cap")]
+    FlashMintCapExceeded,
+    #[msg("Flash mint fee exceeds the maximum")]
+    InvalidFlashMintFee,
+    #[msg("No matching flash repay later in this transaction")]
+    FlashMintNotRepaid,
+    #[msg("A flash mint is already outstanding")]
+    FlashMintOutstanding,
+    #[msg("Flash repay does not match an outstanding flash mint")]
+    InvalidFlashRepay,
+    #[msg("Token program does not match the account")]
+    InvalidTokenProgram,
+    #[msg("Multisig owners or threshold are invalid")]
+    InvalidMultisig,
+    #[msg("No authority transfer is pending")]
+    NoPendingAuthority,
+    #[msg("Unknown pause flags")]
+    InvalidPauseFlags,
+    #[msg("Deposit cap or debt ceiling out of range")]
+    InvalidPoolCaps,
+    #[msg("Sunset threshold or duration out of range")]
+    InvalidSunsetParameters,
+    #[msg("Collateral pool is being sunset")]
+    CollateralPoolSunsetting,
+    #[msg("Collateral pool is not being sunset")]
+    CollateralPoolNotSunsetting,
+    #[msg("Signer is not an attestor of this registry, or the attestor set is invalid")]
+    InvalidAttestor,
+    #[msg("Seed lot description or order is invalid")]
+    InvalidSeedLot,
+    #[msg("Attested price or expiry out of range")]
+    InvalidAttestation,
+    #[msg("Seed lot mint must be empty and minted by its registry")]
+    InvalidSeedLotMint,
+    #[msg("Treasury account is not the collateral treasury")]
+    InvalidTreasuryAccount,
+    #[msg("PSM state already uses the current layout")]
+    StateAlreadyMigrated,
+    #[msg("Position is not liquidatable")]
+    PositionNotLiquidatable,
+    #[msg("Collateral pool already has a fallback oracle; replace it instead")]
+    FallbackOracleExists,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paused_keeps_the_psm_error_code() {
+        assert_eq!(u32::from(ErrorCode::ProtocolPaused), 6000);
+    }
 }
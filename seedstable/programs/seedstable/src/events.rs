@@ -0,0 +1,410 @@
+use anchor_lang::prelude::*;
+use crate::state::{CollateralType, OracleType};
+
+// Position events report the position after the change: `position_collateral_value`
+// is its book collateral value, `position_debt` includes the accrued stability
+// fee and `position_ratio` is their ratio in basis points (u64::MAX without
+// debt). `system_collateral_ratio` is the protocol-wide ratio afterwards.
+
+#[event]
+pub struct RecoveryModeChanged {
+    pub recovery_mode: bool,
+    pub collateral_ratio: u64,
+    pub min_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralDeposited {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub value_usd: u64,
+    pub price: u64,
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralWithdrawn {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub value_usd: u64, // Book value leaving the position
+    pub price: u64,
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StablecoinMinted {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey, // Pool the new debt is attributed to
+    pub amount: u64,
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub total_stablecoin_supply: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StablecoinBurned {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey, // Pool repaid first
+    pub amount: u64,
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub total_stablecoin_supply: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionLiquidated {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub liquidator: Pubkey,
+    pub stability_pool: bool, // Debt absorbed by the stability pool
+    pub debt_repaid: u64,
+    pub collateral_seized: u64,
+    pub price: u64,
+    pub liquidation_bonus: u64, // USD value seized over the repaid debt
+    pub bad_debt: u64,
+    pub health_factor: u64, // Before the liquidation
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StabilityPoolOffset {
+    pub pool: Pubkey, // Collateral pool the seized collateral came from
+    pub position: Pubkey,
+    pub debt_offset: u64, // Pooled SEEDS burned
+    pub collateral_gained: u64,
+    pub total_deposits: u64,
+    pub product: u128,
+    pub scale: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuctionStarted {
+    pub auction: Pubkey,
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub initiator: Pubkey,
+    pub collateral: u64,
+    pub debt: u64,
+    pub start_price: u64,
+    pub floor_price: u64,
+    pub duration: i64,
+    pub health_factor: u64, // Before the auction
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuctionCollateralBought {
+    pub auction: Pubkey,
+    pub pool: Pubkey,
+    pub buyer: Pubkey,
+    pub collateral: u64,
+    pub cost: u64, // SEEDS burned
+    pub price: u64,
+    pub collateral_remaining: u64,
+    pub debt_remaining: u64,
+    pub total_stablecoin_supply: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuctionClosed {
+    pub auction: Pubkey,
+    pub owner: Pubkey,
+    pub pool: Pubkey,
+    pub unsold_collateral: u64,
+    pub returned_to_owner: bool, // Otherwise sent to the pool's collateral treasury
+    pub bad_debt: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralTreasurySwept {
+    pub pool: Pubkey,
+    pub destination: Pubkey,
+    pub amount: u64,
+    pub remaining: u64, // Left in the treasury
+    pub swept_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionRedeemed {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub redeemer: Pubkey,
+    pub debt_redeemed: u64,
+    pub collateral_redeemed: u64, // Net of the redemption fee's share
+    pub price: u64,
+    pub position_collateral_value: u64,
+    pub position_debt: u64,
+    pub position_ratio: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct StablecoinRedeemed {
+    pub redeemer: Pubkey,
+    pub pool: Pubkey,
+    pub amount: u64, // SEEDS burned
+    pub collateral: u64, // Collateral paid out
+    pub fee: u64, // Basis points
+    pub price: u64,
+    pub redemption_base_rate: u64,
+    pub total_stablecoin_supply: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct Rebalanced {
+    pub stablecoin_price: u64,
+    pub deviation: u64, // Basis points off peg
+    pub expanded: bool, // Minted to the treasury rather than burned from it
+    pub amount: u64,
+    pub total_stablecoin_supply: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OraclePriceUpdated {
+    pub pool: Pubkey,
+    pub price: u64,
+    pub confidence: u64,
+    pub price_timestamp: i64,
+    pub twap: u64,
+    pub ema: u64,
+    pub pool_value_usd: u64,
+    pub system_collateral_ratio: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PauseChanged {
+    pub previous_operations: u16,
+    pub paused_operations: u16, // PAUSE_* flags
+    pub changed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SettlementPriceFrozen {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub settlement_price: u64,
+    pub pools_frozen: u64,
+    pub pool_count: u64,
+    pub settlement_debt: u64, // Debt outstanding when settlement was triggered
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionPoolSettled {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub pool: Pubkey,
+    pub settlement_price: u64,
+    pub collateral: u64, // Sub-position collateral before settlement
+    pub collateral_settled: u64, // Kept for SEEDS holders
+    pub excess: u64, // Returned to the owner
+    pub settlement_collateral: u64, // Pool total left to SEEDS holders
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct PositionSettled {
+    pub owner: Pubkey,
+    pub position: Pubkey,
+    pub caller: Pubkey,
+    pub debt: u64,
+    pub covered: u64, // Debt covered by the position's collateral
+    pub shortfall: u64, // Debt left to SEEDS holders
+    pub settled_debt: u64,
+    pub settlement_shortfall: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct GuardianChanged {
+    pub old_guardian: Pubkey,
+    pub new_guardian: Pubkey, // Pubkey::default() when removed
+    pub changed_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityProposed {
+    pub authority: Pubkey,
+    pub pending_authority: Pubkey, // Pubkey::default() when a proposal is cancelled
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct AuthorityTransferred {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+    pub timestamp: i64,
+}
+
+/// Parameters reported by `ParameterChanged`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Parameter {
+    StabilityFee,
+    CloseFactor,
+    FlashMintCap,
+    FlashMintFee,
+    MaxLtv,
+    LiquidationThreshold,
+    LiquidationBonus,
+    DebtCeiling,
+    OracleQuorum,
+    TwapWindow,
+    LiquidationMode,
+    AuctionStartPremium,
+    AuctionFloor,
+    AuctionDuration,
+    MaxDeposit,
+    PoolFrozen,
+    SunsetThreshold,
+    SunsetDuration,
+}
+
+#[event]
+pub struct ParameterChanged {
+    pub parameter: Parameter,
+    pub pool: Option<Pubkey>, // Set for collateral pool parameters
+    pub old_value: u64,
+    pub new_value: u64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct CollateralPoolCreated {
+    pub pool: Pubkey,
+    pub mint: Pubkey,
+    pub collateral_type: CollateralType,
+    pub oracle_type: OracleType,
+    pub price_feed: Pubkey,
+    pub max_ltv: u64,
+    pub liquidation_threshold: u64,
+    pub liquidation_bonus: u64,
+    pub debt_ceiling: u64,
+    pub max_deposit: u64,
+    pub twap_window: i64,
+    pub created_by: Pubkey,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleSourceAdded {
+    pub pool: Pubkey,
+    pub price_feed: Pubkey,
+    pub oracle_type: OracleType,
+    pub max_staleness: i64,
+    pub max_deviation: u64,
+    pub is_fallback: bool,
+    pub num_oracle_sources: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct OracleSourceReplaced {
+    pub pool: Pubkey,
+    pub old_price_feed: Pubkey,
+    pub new_price_feed: Pubkey,
+    pub oracle_type: OracleType,
+    pub is_fallback: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeedLotAttestorChanged {
+    pub registry: Pubkey,
+    pub attestor: Pubkey,
+    pub allowed: bool,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeedLotRegistered {
+    pub registry: Pubkey,
+    pub lot: Pubkey,
+    pub lot_id: u64,
+    pub attestor: Pubkey,
+    pub recipient: Pubkey, // Token account the lot's tokens were minted to
+    pub quantity: u64,
+    pub attested_price: u64,
+    pub attestation_expiry: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeedLotAttested {
+    pub registry: Pubkey,
+    pub lot: Pubkey,
+    pub attestor: Pubkey,
+    pub old_price: u64,
+    pub new_price: u64,
+    pub attestation_expiry: i64,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct SeedLotRegistryPriced {
+    pub registry: Pubkey,
+    pub price: u64, // Quantity-weighted price after haircuts
+    pub total_quantity: u64,
+    pub lot_count: u64,
+    pub timestamp: i64,
+}
+
+/// Emits a `ParameterChanged` for each `(parameter, old_value, new_value)`.
+pub fn emit_parameter_changes(pool: Option<Pubkey>, changes: &[(Parameter, u64, u64)]) -> Result<()> {
+    let timestamp = Clock::get()?.unix_timestamp;
+    for &(parameter, old_value, new_value) in changes {
+        emit!(ParameterChanged {
+            parameter,
+            pool,
+            old_value,
+            new_value,
+            timestamp,
+        });
+    }
+    Ok(())
+}
@@ -0,0 +1,543 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, TransferChecked, Burn};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+use super::position_index::*;
+
+#[derive(Accounts)]
+pub struct StartAuction<'info> {
+    #[account(mut)]
+    pub initiator: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_LIQUIDATE) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [USER_POSITION_SEED, position_owner.key().as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// CHECK: Owner of the position being liquidated
+    pub position_owner: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        constraint = collateral_pool.liquidation_mode == LiquidationMode::DutchAuction @ ErrorCode::WrongLiquidationMode
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        init,
+        payer = initiator,
+        space = LiquidationAuction::LEN,
+        seeds = [AUCTION_SEED, user_position.key().as_ref(), collateral_pool.key().as_ref()],
+        bump
+    )]
+    pub auction: Account<'info, LiquidationAuction>,
+
+    pub index: PositionIndexHints<'info>,
+
+    pub system_program: Program<'info, System>,
+    // remaining_accounts: [collateral_pool, price_history] per sub-position,
+    // pools writable so auctioned debt can be released from their ceilings
+}
+
+#[derive(Accounts)]
+pub struct BuyAuctionCollateral<'info> {
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_LIQUIDATE) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [AUCTION_SEED, auction.user_position.as_ref(), collateral_pool.key().as_ref()],
+        bump = auction.bump,
+        has_one = collateral_pool
+    )]
+    pub auction: Account<'info, LiquidationAuction>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        mut,
+        address = protocol_state.stablecoin_mint
+    )]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = collateral_pool.vault
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = collateral_pool.mint,
+        token::authority = buyer
+    )]
+    pub buyer_collateral_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint,
+        token::authority = buyer
+    )]
+    pub buyer_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the stablecoin mint; `token_program` is the collateral's
+    pub stablecoin_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseAuction<'info> {
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [AUCTION_SEED, auction.user_position.as_ref(), collateral_pool.key().as_ref()],
+        bump = auction.bump,
+        has_one = collateral_pool,
+        has_one = initiator,
+        close = initiator
+    )]
+    pub auction: Account<'info, LiquidationAuction>,
+
+    /// CHECK: Receives the auction account rent, checked against the auction
+    #[account(mut)]
+    pub initiator: AccountInfo<'info>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        mut,
+        address = collateral_pool.vault
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    /// Receives unsold collateral once the debt is covered
+    #[account(
+        mut,
+        token::mint = collateral_pool.mint,
+        token::authority = auction.position_owner
+    )]
+    pub owner_collateral_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Receives unsold collateral when the auction expires short of the debt
+    #[account(
+        mut,
+        seeds = [TREASURY_SEED, collateral_pool.mint.as_ref()],
+        bump,
+        constraint = treasury_collateral_account.key() != collateral_pool.vault @ ErrorCode::InvalidTreasuryAccount
+    )]
+    pub treasury_collateral_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SweepCollateralTreasury<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [TREASURY_SEED, collateral_pool.mint.as_ref()],
+        bump
+    )]
+    pub treasury_collateral_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = collateral_pool.mint
+    )]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn start_auction_handler(ctx: Context<StartAuction>) -> Result<()> {
+    let user_position = &mut ctx.accounts.user_position;
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let now = Clock::get()?.unix_timestamp;
+
+    // Value all of the position's collateral at spot prices
+    let valuation = user_position
+        .collateral_value(ctx.remaining_accounts, Valuation::Spot)?;
+
+    protocol_state.accrue_stability_fee(now)?;
+    let debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    require!(
+        protocol_state.is_liquidatable(&valuation, debt),
+        ErrorCode::PositionNotLiquidatable
+    );
+
+    // The whole sub-position goes up for sale
+    let pool_key = collateral_pool.key();
+    let pool_position = user_position
+        .find_position(&pool_key)
+        .copied()
+        .ok_or(ErrorCode::InsufficientFunds)?;
+    require!(pool_position.amount > 0, ErrorCode::InsufficientFunds);
+
+    // Move at most the collateral's spot value of debt into the auction
+    let price = collateral_pool.current_price()?;
+    let spot_value = calculate_usd_value(pool_position.amount, price, collateral_pool.decimals)?;
+    let auction_debt = debt.min(spot_value);
+    let normalized_amount = protocol_state.normalize_repayment(user_position.normalized_debt, auction_debt)?;
+
+    let (start_price, floor_price) = LiquidationAuction::price_bounds(
+        price,
+        collateral_pool.auction_start_premium,
+        collateral_pool.auction_floor,
+    )?;
+
+    let auction = &mut ctx.accounts.auction;
+    auction.position_owner = ctx.accounts.position_owner.key();
+    auction.user_position = user_position.key();
+    auction.collateral_pool = pool_key;
+    auction.initiator = ctx.accounts.initiator.key();
+    auction.collateral_remaining = pool_position.amount;
+    auction.debt_remaining = auction_debt;
+    auction.start_price = start_price;
+    auction.floor_price = floor_price;
+    auction.start_timestamp = now;
+    auction.duration = collateral_pool.auction_duration;
+    auction.bump = ctx.bumps.auction;
+
+    // Update user position
+    let position = user_position
+        .find_position_mut(&pool_key)
+        .ok_or(ErrorCode::InsufficientFunds)?;
+    position.amount = 0;
+    position.value_usd = 0;
+    user_position.collateral_deposited = user_position.collateral_deposited
+        .saturating_sub(pool_position.amount);
+
+    let repaid = user_position.repay_debt(&pool_key, normalized_amount)?;
+    release_pool_debt(collateral_pool, &repaid, ctx.remaining_accounts)?;
+    user_position.remove_empty_positions();
+    user_position.last_update_timestamp = now;
+
+    // Auctioned collateral stays in `total_deposited`, and in the pool's
+    // value, until it is sold
+    protocol_state.total_normalized_debt = protocol_state.total_normalized_debt
+        .checked_sub(normalized_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.total_auction_debt = protocol_state.total_auction_debt
+        .checked_add(auction_debt)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.refresh_collateral_ratio(now);
+
+    // Keep the position index sorted
+    let position_key = user_position.key();
+    ctx.accounts.index.reposition(position_key, user_position)?;
+
+    let position_debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    emit!(AuctionStarted {
+        auction: auction.key(),
+        owner: auction.position_owner,
+        position: position_key,
+        pool: pool_key,
+        initiator: auction.initiator,
+        collateral: pool_position.amount,
+        debt: auction_debt,
+        start_price,
+        floor_price,
+        duration: auction.duration,
+        health_factor: valuation.health_factor(debt),
+        position_collateral_value: user_position.total_collateral_value(),
+        position_debt,
+        position_ratio: user_position.book_ratio(position_debt),
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: now,
+    });
+
+    msg!(
+        "Started auction of {} collateral for {} stablecoins, price {} decaying to {}",
+        pool_position.amount,
+        auction_debt,
+        start_price,
+        floor_price
+    );
+    Ok(())
+}
+
+pub fn buy_auction_collateral_handler(
+    ctx: Context<BuyAuctionCollateral>,
+    max_collateral: u64,
+    max_price: u64,
+) -> Result<()> {
+    let auction = &mut ctx.accounts.auction;
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let protocol_state = &mut ctx.accounts.protocol_state;
+
+    require!(!auction.is_settled(), ErrorCode::AuctionFinished);
+    require!(max_collateral > 0, ErrorCode::InvalidLiquidationAmount);
+
+    let price = auction.current_price(Clock::get()?.unix_timestamp);
+    require!(price <= max_price, ErrorCode::AuctionPriceExceeded);
+
+    // Collateral needed to cover the remaining debt, rounded up
+    let scale = 10_u128.pow(collateral_pool.decimals as u32);
+    let collateral_for_debt = u64::try_from(
+        (auction.debt_remaining as u128 * scale).div_ceil(price as u128)
+    ).map_err(|_| ErrorCode::MathOverflow)?;
+
+    let collateral_amount = max_collateral
+        .min(auction.collateral_remaining)
+        .min(collateral_for_debt);
+    let cost = if collateral_amount == collateral_for_debt {
+        auction.debt_remaining
+    } else {
+        // Partial fills round in the protocol's favour
+        let cost = (collateral_amount as u128 * price as u128).div_ceil(scale);
+        u64::try_from(cost)
+            .map_err(|_| ErrorCode::MathOverflow)?
+            .min(auction.debt_remaining)
+    };
+
+    require!(
+        ctx.accounts.buyer_stablecoin_account.amount >= cost,
+        ErrorCode::InsufficientFunds
+    );
+
+    // Burn stablecoins from buyer
+    let cpi_accounts = Burn {
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        from: ctx.accounts.buyer_stablecoin_account.to_account_info(),
+        authority: ctx.accounts.buyer.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.stablecoin_token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    token_interface::burn(cpi_ctx, cost)?;
+
+    // Transfer collateral to buyer
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.collateral_vault.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+        to: ctx.accounts.buyer_collateral_account.to_account_info(),
+        authority: protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+    token_interface::transfer_checked(cpi_ctx, collateral_amount, ctx.accounts.collateral_mint.decimals)?;
+
+    // Update auction
+    auction.collateral_remaining -= collateral_amount;
+    auction.debt_remaining -= cost;
+
+    // Update collateral pool
+    collateral_pool.total_deposited = collateral_pool.total_deposited
+        .checked_sub(collateral_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    collateral_pool.sync_value(protocol_state)?;
+
+    // Update protocol state
+    protocol_state.total_auction_debt = protocol_state.total_auction_debt
+        .checked_sub(cost)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
+        .saturating_sub(cost);
+    let now = Clock::get()?.unix_timestamp;
+    protocol_state.refresh_collateral_ratio(now);
+
+    emit!(AuctionCollateralBought {
+        auction: auction.key(),
+        pool: collateral_pool.key(),
+        buyer: ctx.accounts.buyer.key(),
+        collateral: collateral_amount,
+        cost,
+        price,
+        collateral_remaining: auction.collateral_remaining,
+        debt_remaining: auction.debt_remaining,
+        total_stablecoin_supply: protocol_state.total_stablecoin_supply,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: now,
+    });
+
+    msg!("Bought {} auctioned collateral at {} for {} stablecoins", collateral_amount, price, cost);
+    Ok(())
+}
+
+pub fn close_auction_handler(ctx: Context<CloseAuction>) -> Result<()> {
+    let auction = &ctx.accounts.auction;
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let now = Clock::get()?.unix_timestamp;
+
+    require!(
+        auction.is_settled() || auction.is_expired(now),
+        ErrorCode::AuctionStillActive
+    );
+
+    // Unsold collateral goes back to the owner once the debt is covered,
+    // otherwise to the treasury alongside the uncovered debt
+    let debt_covered = auction.debt_remaining == 0;
+    let unsold = auction.collateral_remaining;
+    if unsold > 0 {
+        let seeds = &[
+            PROTOCOL_SEED,
+            &[protocol_state.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let destination = if debt_covered {
+            ctx.accounts.owner_collateral_account.to_account_info()
+        } else {
+            ctx.accounts.treasury_collateral_account.to_account_info()
+        };
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.collateral_vault.to_account_info(),
+            mint: ctx.accounts.collateral_mint.to_account_info(),
+            to: destination,
+            authority: protocol_state.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token_interface::transfer_checked(cpi_ctx, unsold, ctx.accounts.collateral_mint.decimals)?;
+
+        collateral_pool.total_deposited = collateral_pool.total_deposited
+            .checked_sub(unsold)
+            .ok_or(ErrorCode::MathOverflow)?;
+        collateral_pool.sync_value(protocol_state)?;
+    }
+
+    // Debt the auction failed to raise is written off
+    let bad_debt = auction.debt_remaining;
+    protocol_state.total_auction_debt = protocol_state.total_auction_debt
+        .checked_sub(bad_debt)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.total_bad_debt = protocol_state.total_bad_debt
+        .checked_add(bad_debt)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.refresh_collateral_ratio(now);
+
+    emit!(AuctionClosed {
+        auction: auction.key(),
+        owner: auction.position_owner,
+        pool: collateral_pool.key(),
+        unsold_collateral: unsold,
+        returned_to_owner: debt_covered,
+        bad_debt,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: now,
+    });
+
+    msg!(
+        "Closed auction with {} unsold collateral returned to the {}",
+        unsold,
+        if debt_covered { "owner" } else { "treasury" }
+    );
+    if bad_debt > 0 {
+        msg!("Wrote off {} stablecoins of bad debt", bad_debt);
+    }
+    Ok(())
+}
+
+/// Moves collateral left in a pool's treasury by expired auctions out to
+/// `destination`, e.g. to be sold against the written-off bad debt.
+pub fn sweep_collateral_treasury_handler(ctx: Context<SweepCollateralTreasury>, amount: u64) -> Result<()> {
+    let treasury = &ctx.accounts.treasury_collateral_account;
+    require!(amount > 0 && amount <= treasury.amount, ErrorCode::InsufficientFunds);
+
+    let protocol_state = &ctx.accounts.protocol_state;
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: treasury.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+        to: ctx.accounts.destination.to_account_info(),
+        authority: protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.collateral_mint.decimals)?;
+
+    emit!(CollateralTreasurySwept {
+        pool: ctx.accounts.collateral_pool.key(),
+        destination: ctx.accounts.destination.key(),
+        amount,
+        remaining: treasury.amount - amount,
+        swept_by: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Swept {} collateral from the pool treasury", amount);
+    Ok(())
+}
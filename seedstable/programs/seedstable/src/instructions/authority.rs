@@ -0,0 +1,131 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions as instructions_sysvar;
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+
+/// Accounts proving an authority signed. A single-key authority signs as the
+/// parent's `authority` and leaves these out. A multisig authority passes its
+/// `Multisig` and the instructions sysvar, and `threshold` of its owners sign
+/// the instruction, the parent's `authority` among them. The other owners'
+/// signatures are read from the instruction's account metas in the sysvar,
+/// so they only need to appear there as signers. The instruction must be
+/// called directly, not through CPI, and be the only one in the transaction
+/// that uses the multisig.
+#[derive(Accounts)]
+pub struct AuthoritySigners<'info> {
+    pub multisig: Option<Account<'info, Multisig>>,
+
+    /// CHECK: address checked; read for the instruction's signers
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: Option<UncheckedAccount<'info>>,
+}
+
+impl<'info> AuthoritySigners<'info> {
+    /// Whether `signer` acts for `expected`: it is `expected` itself, or it
+    /// owns the multisig at `expected` and enough other owners signed too.
+    pub fn approve(&self, expected: &Pubkey, signer: &Signer) -> bool {
+        if signer.key() == *expected {
+            return true;
+        }
+        match (&self.multisig, &self.instructions) {
+            (Some(multisig), Some(instructions)) => {
+                multisig.key() == *expected
+                    && multisig.active_owners().contains(signer.key)
+                    && multisig.approves(&multisig.key(), instructions).unwrap_or(false)
+            }
+            _ => false,
+        }
+    }
+}
+
+#[derive(Accounts)]
+pub struct CreateMultisig<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Multisig::LEN
+    )]
+    pub multisig: Account<'info, Multisig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAuthority<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.pending_authority, &new_authority) @ ErrorCode::Unauthorized
+    )]
+    pub new_authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = protocol_state.pending_authority != Pubkey::default() @ ErrorCode::NoPendingAuthority
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+/// Creates an m-of-n multisig that can then be proposed as an authority.
+pub fn create_multisig_handler(ctx: Context<CreateMultisig>, owners: Vec<Pubkey>, threshold: u8) -> Result<()> {
+    ctx.accounts.multisig.set_owners(&owners, threshold)?;
+
+    msg!("Created a {}-of-{} multisig", threshold, owners.len());
+    Ok(())
+}
+
+/// Starts an authority transfer; `Pubkey::default()` cancels a pending one.
+pub fn propose_authority_handler(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    protocol_state.pending_authority = new_authority;
+
+    emit!(AuthorityProposed {
+        authority: protocol_state.authority,
+        pending_authority: new_authority,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Proposed {} as protocol authority", new_authority);
+    Ok(())
+}
+
+/// Completes an authority transfer, signed by the proposed authority.
+pub fn accept_authority_handler(ctx: Context<AcceptAuthority>) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let old_authority = protocol_state.authority;
+    protocol_state.authority = protocol_state.pending_authority;
+    protocol_state.pending_authority = Pubkey::default();
+
+    emit!(AuthorityTransferred {
+        old_authority,
+        new_authority: protocol_state.authority,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Protocol authority transferred to {}", protocol_state.authority);
+    Ok(())
+}
@@ -1,8 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Burn};
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, Burn};
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::position_index::*;
 
 #[derive(Accounts)]
 pub struct BurnStable<'info> {
@@ -13,7 +15,7 @@ pub struct BurnStable<'info> {
         mut,
         seeds = [PROTOCOL_SEED],
         bump = protocol_state.bump,
-        constraint = !protocol_state.is_paused @ ErrorCode::ProtocolPaused
+        constraint = !protocol_state.is_paused(PAUSE_BURN) @ ErrorCode::ProtocolPaused
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -24,31 +26,48 @@ pub struct BurnStable<'info> {
     )]
     pub user_position: Account<'info, UserPosition>,
 
+    /// Pool whose attributed debt is repaid first
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
     #[account(
         mut,
         address = protocol_state.stablecoin_mint
     )]
-    pub stablecoin_mint: Account<'info, Mint>,
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint = stablecoin_mint,
         token::authority = user
     )]
-    pub user_stablecoin_account: Account<'info, TokenAccount>,
+    pub user_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub index: PositionIndexHints<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: writable [collateral_pool, price_history] pairs for
+    // any other pool the repayment spills into
 }
 
 pub fn handler(ctx: Context<BurnStable>, amount: u64) -> Result<()> {
     let protocol_state = &mut ctx.accounts.protocol_state;
     let user_position = &mut ctx.accounts.user_position;
 
-    // Check if user has enough stablecoins to burn
+    // Debt is principal plus the stability fee accrued on it
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+    let debt = protocol_state.debt_for(user_position.normalized_debt)?;
+
+    // Check if the burn stays within what the user owes
     require!(
-        user_position.stablecoin_minted >= amount,
+        debt >= amount,
         ErrorCode::InsufficientFunds
     );
+    let normalized_amount = protocol_state.normalize_repayment(user_position.normalized_debt, amount)?;
 
     require!(
         ctx.accounts.user_stablecoin_account.amount >= amount,
@@ -64,20 +83,44 @@ pub fn handler(ctx: Context<BurnStable>, amount: u64) -> Result<()> {
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
     
-    token::burn(cpi_ctx, amount)?;
+    token_interface::burn(cpi_ctx, amount)?;
 
-    // Update user position
-    user_position.stablecoin_minted = user_position.stablecoin_minted
-        .checked_sub(amount)
-        .ok_or(ErrorCode::MathOverflow)?;
+    // Update user position and release the repaid pool debt
+    let repaid = user_position.repay_debt(&ctx.accounts.collateral_pool.key(), normalized_amount)?;
+    release_pool_debt(&mut ctx.accounts.collateral_pool, &repaid, ctx.remaining_accounts)?;
+    user_position.remove_empty_positions();
     user_position.last_update_timestamp = Clock::get()?.unix_timestamp;
 
     // Update protocol state
-    protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
-        .checked_sub(amount)
+    protocol_state.total_normalized_debt = protocol_state.total_normalized_debt
+        .checked_sub(normalized_amount)
         .ok_or(ErrorCode::MathOverflow)?;
+    // Position debt rounds up, so the last repayments may exceed the tracked
+    // supply by a few base units
+    protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
+        .saturating_sub(amount);
     
-    protocol_state.collateral_ratio = protocol_state.calculate_collateral_ratio();
+    protocol_state.refresh_collateral_ratio(Clock::get()?.unix_timestamp);
+
+    // Keep the position index sorted
+    let position_key = ctx.accounts.user_position.key();
+    ctx.accounts.index.reposition(position_key, &mut ctx.accounts.user_position)?;
+
+    let user_position = &ctx.accounts.user_position;
+    let protocol_state = &ctx.accounts.protocol_state;
+    let position_debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    emit!(StablecoinBurned {
+        owner: user_position.owner,
+        position: position_key,
+        pool: ctx.accounts.collateral_pool.key(),
+        amount,
+        position_collateral_value: user_position.total_collateral_value(),
+        position_debt,
+        position_ratio: user_position.book_ratio(position_debt),
+        total_stablecoin_supply: protocol_state.total_stablecoin_supply,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: user_position.last_update_timestamp,
+    });
 
     msg!("Burned {} stablecoins from user", amount);
     Ok(())
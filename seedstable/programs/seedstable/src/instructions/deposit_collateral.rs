@@ -1,8 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, TransferChecked};
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::position_index::*;
 
 #[derive(Accounts)]
 #[instruction(collateral_type: CollateralType)]
@@ -14,7 +16,7 @@ pub struct DepositCollateral<'info> {
         mut,
         seeds = [PROTOCOL_SEED],
         bump = protocol_state.bump,
-        constraint = !protocol_state.is_paused @ ErrorCode::ProtocolPaused
+        constraint = !protocol_state.is_paused(PAUSE_DEPOSIT) @ ErrorCode::ProtocolPaused
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -36,104 +38,103 @@ pub struct DepositCollateral<'info> {
     )]
     pub collateral_pool: Account<'info, CollateralPool>,
 
-    pub collateral_mint: Account<'info, Mint>,
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         address = collateral_pool.vault
     )]
-    pub collateral_vault: Account<'info, TokenAccount>,
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
 
     #[account(
         mut,
         token::mint = collateral_mint,
         token::authority = user
     )]
-    pub user_collateral_account: Account<'info, TokenAccount>,
+    pub user_collateral_account: InterfaceAccount<'info, TokenAccount>,
 
-    /// CHECK: Oracle account validation happens in instruction
-    pub oracle_account: AccountInfo<'info>,
+    pub index: PositionIndexHints<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn handler(ctx: Context<DepositCollateral>, collateral_type: CollateralType, amount: u64) -> Result<()> {
-    let collateral_pool = &mut ctx.accounts.collateral_pool;
-    let user_position = &mut ctx.accounts.user_position;
-    let protocol_state = &mut ctx.accounts.protocol_state;
-
-    // Check deposit limits
-    require!(
-        collateral_pool.can_accept_deposit(amount),
-        ErrorCode::DepositExceedsMaximum
-    );
-
+pub fn handler(ctx: Context<DepositCollateral>, _collateral_type: CollateralType, amount: u64) -> Result<()> {
     require!(
         ctx.accounts.user_collateral_account.amount >= amount,
         ErrorCode::InsufficientFunds
     );
 
-    // Get price from oracle (simplified - would need actual oracle integration)
-    let price = get_oracle_price(&ctx.accounts.oracle_account, collateral_type)?;
-    
+    let usd_value = book_deposit(
+        &ctx.accounts.user,
+        &mut ctx.accounts.protocol_state,
+        &mut ctx.accounts.user_position,
+        ctx.bumps.user_position,
+        &mut ctx.accounts.collateral_pool,
+        &mut ctx.accounts.index,
+        amount,
+    )?;
+
     // Transfer collateral from user to vault
-    let cpi_accounts = Transfer {
+    let cpi_accounts = TransferChecked {
         from: ctx.accounts.user_collateral_account.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
         to: ctx.accounts.collateral_vault.to_account_info(),
         authority: ctx.accounts.user.to_account_info(),
     };
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
     
-    token::transfer(cpi_ctx, amount)?;
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.collateral_mint.decimals)?;
+
+    msg!("Deposited {} collateral tokens worth ${} USD", amount, usd_value);
+    Ok(())
+}
+
+/// Credits a deposit of `amount` to the user's position, the pool and the
+/// protocol totals, re-sorts the position and emits `CollateralDeposited`.
+/// Returns the deposit's USD value; the caller moves the tokens.
+pub(crate) fn book_deposit<'info>(
+    user: &Signer<'info>,
+    protocol_state: &mut Account<'info, ProtocolState>,
+    user_position: &mut Account<'info, UserPosition>,
+    user_position_bump: u8,
+    collateral_pool: &mut Account<'info, CollateralPool>,
+    index: &mut PositionIndexHints<'info>,
+    amount: u64,
+) -> Result<u64> {
+    // Check deposit limits
+    require!(
+        collateral_pool.can_accept_deposit(amount),
+        ErrorCode::DepositExceedsMaximum
+    );
+
+    // Use the pool's last aggregated oracle price
+    let price = collateral_pool.current_price()?;
 
     // Calculate USD value
-    let decimals = ctx.accounts.collateral_mint.decimals;
-    let usd_value = calculate_usd_value(amount, price, decimals)?;
+    let usd_value = calculate_usd_value(amount, price, collateral_pool.decimals)?;
 
     // Update collateral pool
     collateral_pool.total_deposited = collateral_pool.total_deposited
         .checked_add(amount)
         .ok_or(ErrorCode::MathOverflow)?;
-    collateral_pool.total_value_usd = collateral_pool.total_value_usd
-        .checked_add(usd_value)
-        .ok_or(ErrorCode::MathOverflow)?;
+    collateral_pool.sync_value(protocol_state)?;
 
     // Initialize user position if needed
     if user_position.owner == Pubkey::default() {
-        user_position.owner = ctx.accounts.user.key();
+        user_position.owner = user.key();
         user_position.protocol_state = protocol_state.key();
         user_position.collateral_deposited = 0;
-        user_position.stablecoin_minted = 0;
-        user_position.collateral_pool = None;
-        user_position.bump = ctx.bumps.user_position;
+        user_position.normalized_debt = 0;
+        user_position.collateral_positions = Vec::new();
+        user_position.in_index = false;
+        user_position.bump = user_position_bump;
     }
 
-    // Update user position
-    let pool_position = CollateralPoolPosition {
-        pool: collateral_pool.key(),
-        amount,
-        value_usd: usd_value,
-    };
-
-    // Update or set collateral pool position
-    if let Some(existing_pos) = &mut user_position.collateral_pool {
-        if existing_pos.pool == collateral_pool.key() {
-            existing_pos.amount = existing_pos.amount
-                .checked_add(amount)
-                .ok_or(ErrorCode::MathOverflow)?;
-            existing_pos.value_usd = existing_pos.value_usd
-                .checked_add(usd_value)
-                .ok_or(ErrorCode::MathOverflow)?;
-        } else {
-            // Replace with new pool position
-            user_position.collateral_pool = Some(pool_position);
-        }
-    } else {
-        user_position.collateral_pool = Some(pool_position);
-    }
+    // Add to the sub-position for this pool, opening one if needed
+    user_position.add_collateral(collateral_pool.key(), amount, usd_value)?;
 
     user_position.collateral_deposited = user_position.collateral_deposited
         .checked_add(amount)
@@ -141,30 +142,26 @@ pub fn handler(ctx: Context<DepositCollateral>, collateral_type: CollateralType,
     user_position.last_update_timestamp = Clock::get()?.unix_timestamp;
 
     // Update protocol state
-    protocol_state.total_collateral_value = protocol_state.total_collateral_value
-        .checked_add(usd_value)
-        .ok_or(ErrorCode::MathOverflow)?;
-    protocol_state.collateral_ratio = protocol_state.calculate_collateral_ratio();
-
-    msg!("Deposited {} collateral tokens worth ${} USD", amount, usd_value);
-    Ok(())
-}
+    protocol_state.refresh_collateral_ratio(Clock::get()?.unix_timestamp);
 
-fn get_oracle_price(_oracle_account: &AccountInfo, collateral_type: CollateralType) -> Result<u64> {
-    // Simplified oracle price - in production would integrate with Pyth/Switchboard
-    match collateral_type {
-        CollateralType::USDC => Ok(1_000_000), // $1.00 with 6 decimals
-        CollateralType::SOL => Ok(100_000_000), // $100.00 with 6 decimals
-        CollateralType::TokenizedSeeds => Ok(50_000_000), // $50.00 with 6 decimals
-    }
-}
+    // Keep the position index sorted
+    let position_key = user_position.key();
+    index.reposition(position_key, user_position)?;
 
-fn calculate_usd_value(amount: u64, price: u64, decimals: u8) -> Result<u64> {
-    let amount_scaled = amount
-        .checked_div(10_u64.pow(decimals as u32))
-        .ok_or(ErrorCode::MathOverflow)?;
-    
-    Ok(amount_scaled
-        .checked_mul(price)
-        .ok_or(ErrorCode::MathOverflow)?)
+    let position_debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    emit!(CollateralDeposited {
+        owner: user_position.owner,
+        position: position_key,
+        pool: collateral_pool.key(),
+        amount,
+        value_usd: usd_value,
+        price,
+        position_collateral_value: user_position.total_collateral_value(),
+        position_debt,
+        position_ratio: user_position.book_ratio(position_debt),
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: user_position.last_update_timestamp,
+    });
+
+    Ok(usd_value)
 }
@@ -2,14 +2,51 @@ use anchor_lang::prelude::*;
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
 
 #[derive(Accounts)]
 pub struct EmergencyPause<'info> {
     #[account(
-        constraint = authority.key() == protocol_state.authority @ ErrorCode::Unauthorized
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
     )]
     pub authority: Signer<'info>,
 
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct GuardianPause<'info> {
+    #[account(
+        constraint = guardian.key() == protocol_state.guardian @ ErrorCode::Unauthorized
+    )]
+    pub guardian: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.global_settlement @ ErrorCode::SettlementActive
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
     #[account(
         mut,
         seeds = [PROTOCOL_SEED],
@@ -18,10 +55,23 @@ pub struct EmergencyPause<'info> {
     pub protocol_state: Account<'info, ProtocolState>,
 }
 
+/// Pauses the risk-increasing operations in `DEFAULT_PAUSE_FLAGS`, or lifts
+/// every pause.
 pub fn handler(ctx: Context<EmergencyPause>, pause: bool) -> Result<()> {
     let protocol_state = &mut ctx.accounts.protocol_state;
-    
-    protocol_state.is_paused = pause;
+
+    // Settlement is one-way; the protocol stays paused
+    require!(!protocol_state.global_settlement, ErrorCode::SettlementActive);
+
+    let previous_operations = protocol_state.paused_operations;
+    protocol_state.paused_operations = if pause { DEFAULT_PAUSE_FLAGS } else { 0 };
+
+    emit!(PauseChanged {
+        previous_operations,
+        paused_operations: protocol_state.paused_operations,
+        changed_by: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
     
     if pause {
         msg!("Protocol emergency pause activated");
@@ -31,3 +81,63 @@ pub fn handler(ctx: Context<EmergencyPause>, pause: bool) -> Result<()> {
     
     Ok(())
 }
+
+/// Replaces the paused operations with exactly the `PAUSE_*` flags given.
+pub fn set_paused_operations_handler(ctx: Context<EmergencyPause>, operations: u16) -> Result<()> {
+    require!(operations & !PAUSE_ALL == 0, ErrorCode::InvalidPauseFlags);
+
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    require!(!protocol_state.global_settlement, ErrorCode::SettlementActive);
+
+    let previous_operations = protocol_state.paused_operations;
+    protocol_state.paused_operations = operations;
+
+    emit!(PauseChanged {
+        previous_operations,
+        paused_operations: operations,
+        changed_by: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Paused operations set to {:#09b}", operations);
+    Ok(())
+}
+
+/// Adds pause flags; the guardian cannot clear any.
+pub fn guardian_pause_handler(ctx: Context<GuardianPause>, operations: u16) -> Result<()> {
+    require!(
+        operations != 0 && operations & !PAUSE_ALL == 0,
+        ErrorCode::InvalidPauseFlags
+    );
+
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let previous_operations = protocol_state.paused_operations;
+    protocol_state.paused_operations |= operations;
+
+    emit!(PauseChanged {
+        previous_operations,
+        paused_operations: protocol_state.paused_operations,
+        changed_by: ctx.accounts.guardian.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Guardian paused operations {:#09b}", operations);
+    Ok(())
+}
+
+/// Sets the guardian; `Pubkey::default()` removes it.
+pub fn set_guardian_handler(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let old_guardian = protocol_state.guardian;
+    protocol_state.guardian = guardian;
+
+    emit!(GuardianChanged {
+        old_guardian,
+        new_guardian: guardian,
+        changed_by: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Guardian set to {}", guardian);
+    Ok(())
+}
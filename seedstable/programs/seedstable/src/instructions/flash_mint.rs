@@ -0,0 +1,232 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::solana_program::sysvar::instructions::{
+    self as instructions_sysvar, load_current_index_checked, load_instruction_at_checked,
+};
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, Burn, Mint, MintTo, TokenInterface, TokenAccount, TransferChecked};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+use crate::stablecoin::transfer_checked_with_hook;
+
+#[derive(Accounts)]
+pub struct FlashMint<'info> {
+    pub borrower: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_MINT) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        address = protocol_state.stablecoin_mint,
+        constraint = stablecoin_mint.mint_authority == COption::Some(protocol_state.key()) @ ErrorCode::InvalidMintAuthority
+    )]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint
+    )]
+    pub borrower_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: the instructions sysvar, checked by address
+    #[account(address = instructions_sysvar::ID)]
+    pub instructions: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct FlashRepay<'info> {
+    pub borrower: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        address = protocol_state.stablecoin_mint,
+        constraint = stablecoin_mint.mint_authority == COption::Some(protocol_state.key()) @ ErrorCode::InvalidMintAuthority
+    )]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint,
+        token::authority = borrower
+    )]
+    pub borrower_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        address = protocol_state.treasury
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetFlashMintParams<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+/// Mints `amount` SEEDS that must be burned again, plus the fee, by a
+/// `flash_repay` for the same amount later in this transaction.
+pub fn flash_mint_handler(ctx: Context<FlashMint>, amount: u64) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+
+    require!(amount > 0, ErrorCode::InsufficientFunds);
+    require!(amount <= protocol_state.flash_mint_cap, ErrorCode::FlashMintCapExceeded);
+    require!(protocol_state.flash_mint_outstanding == 0, ErrorCode::FlashMintOutstanding);
+
+    // Only a top-level call can be followed by its repayment
+    let instructions = ctx.accounts.instructions.to_account_info();
+    let current_index = load_current_index_checked(&instructions)? as usize;
+    let current = load_instruction_at_checked(current_index, &instructions)?;
+    require_keys_eq!(current.program_id, crate::ID, ErrorCode::FlashMintNotRepaid);
+
+    let protocol_key = protocol_state.key();
+    let mut repaid = false;
+    let mut index = current_index + 1;
+    while let Ok(instruction) = load_instruction_at_checked(index, &instructions) {
+        if is_flash_repay(&instruction, protocol_key, amount) {
+            repaid = true;
+            break;
+        }
+        index += 1;
+    }
+    require!(repaid, ErrorCode::FlashMintNotRepaid);
+
+    // Mint stablecoins to borrower
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = MintTo {
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        to: ctx.accounts.borrower_stablecoin_account.to_account_info(),
+        authority: protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+    token_interface::mint_to(cpi_ctx, amount)?;
+
+    protocol_state.flash_mint_outstanding = amount;
+
+    msg!("Flash minted {} stablecoins", amount);
+    Ok(())
+}
+
+/// Burns the flash-minted principal and sends the fee to the treasury.
+pub fn flash_repay_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, FlashRepay<'info>>,
+    amount: u64,
+) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+
+    require!(
+        amount > 0 && protocol_state.flash_mint_outstanding == amount,
+        ErrorCode::InvalidFlashRepay
+    );
+    let fee = flash_mint_fee(amount, protocol_state.flash_mint_fee);
+
+    // Burn the principal
+    let cpi_accounts = Burn {
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        from: ctx.accounts.borrower_stablecoin_account.to_account_info(),
+        authority: ctx.accounts.borrower.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    token_interface::burn(cpi_ctx, amount)?;
+
+    // Pay the fee to the treasury; transfer hook accounts come in through remaining_accounts
+    if fee > 0 {
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.borrower_stablecoin_account.to_account_info(),
+            mint: ctx.accounts.stablecoin_mint.to_account_info(),
+            to: ctx.accounts.treasury.to_account_info(),
+            authority: ctx.accounts.borrower.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+
+        transfer_checked_with_hook(cpi_ctx, fee, ctx.accounts.stablecoin_mint.decimals)?;
+    }
+
+    protocol_state.flash_mint_outstanding = 0;
+
+    msg!("Flash repaid {} stablecoins with a {} fee", amount, fee);
+    Ok(())
+}
+
+pub fn set_flash_mint_params_handler(ctx: Context<SetFlashMintParams>, cap: u64, fee: u64) -> Result<()> {
+    require!(fee <= MAX_FLASH_MINT_FEE, ErrorCode::InvalidFlashMintFee);
+
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    emit_parameter_changes(
+        None,
+        &[
+            (Parameter::FlashMintCap, protocol_state.flash_mint_cap, cap),
+            (Parameter::FlashMintFee, protocol_state.flash_mint_fee, fee),
+        ],
+    )?;
+    protocol_state.flash_mint_cap = cap;
+    protocol_state.flash_mint_fee = fee;
+
+    msg!("Flash mint cap set to {} with a {} bps fee", cap, fee);
+    Ok(())
+}
+
+/// Fee on a flash mint, rounded up.
+fn flash_mint_fee(amount: u64, fee_bps: u64) -> u64 {
+    (amount as u128 * fee_bps as u128).div_ceil(BASIS_POINTS as u128) as u64
+}
+
+/// Whether `instruction` is a `flash_repay` of `amount` against this protocol.
+fn is_flash_repay(
+    instruction: &anchor_lang::solana_program::instruction::Instruction,
+    protocol_state: Pubkey,
+    amount: u64,
+) -> bool {
+    if instruction.program_id != crate::ID || instruction.data.len() < 16 {
+        return false;
+    }
+    if instruction.data[..8] != crate::instruction::FlashRepay::DISCRIMINATOR {
+        return false;
+    }
+    let repay_amount = u64::from_le_bytes(instruction.data[8..16].try_into().unwrap());
+    // FlashRepay lists protocol_state second
+    repay_amount == amount
+        && instruction.accounts.get(1).map(|meta| meta.pubkey) == Some(protocol_state)
+}
@@ -1,9 +1,28 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{Mint, Token, TokenAccount};
+use anchor_lang::solana_program::program::{invoke, invoke_signed};
+use anchor_lang::system_program::{self, CreateAccount};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    extension::{metadata_pointer, transfer_hook, ExtensionType},
+    state::{Account as SplAccount, Mint as SplMint},
+};
+use anchor_spl::token_interface::TokenInterface;
+use spl_token_metadata_interface::state::TokenMetadata;
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
 
+/// Token-2022 metadata for the stablecoin mint. When given, the mint is
+/// created under Token-2022 with the metadata stored on the mint itself.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct StablecoinMetadata {
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+    /// Program run on every SEEDS transfer, e.g. for compliance checks
+    pub transfer_hook_program: Option<Pubkey>,
+}
+
 #[derive(Accounts)]
 #[instruction(bump: u8)]
 pub struct Initialize<'info> {
@@ -19,30 +38,33 @@ pub struct Initialize<'info> {
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
-    #[account(
-        init,
-        payer = authority,
-        mint::decimals = USD_DECIMALS,
-        mint::authority = protocol_state,
-    )]
-    pub stablecoin_mint: Account<'info, Mint>,
+    /// Created and initialized by the handler so Token-2022 extensions can
+    /// be set up before the mint itself
+    #[account(mut)]
+    pub stablecoin_mint: Signer<'info>,
 
+    /// CHECK: created by the handler as a token account for `stablecoin_mint`
     #[account(
-        init,
-        payer = authority,
-        token::mint = stablecoin_mint,
-        token::authority = protocol_state,
+        mut,
         seeds = [TREASURY_SEED],
         bump
     )]
-    pub treasury: Account<'info, TokenAccount>,
+    pub treasury: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
-pub fn handler(ctx: Context<Initialize>, bump: u8) -> Result<()> {
+pub fn handler(ctx: Context<Initialize>, _bump: u8, metadata: Option<StablecoinMetadata>) -> Result<()> {
+    let token_program_id = ctx.accounts.token_program.key();
+    if metadata.is_some() {
+        require_keys_eq!(token_program_id, spl_token_2022::ID, ErrorCode::InvalidTokenProgram);
+    }
+
+    create_stablecoin_mint(&ctx, metadata.as_ref())?;
+    create_treasury(&ctx, metadata.as_ref())?;
+
     let protocol_state = &mut ctx.accounts.protocol_state;
     
     protocol_state.authority = ctx.accounts.authority.key();
@@ -53,10 +75,189 @@ pub fn handler(ctx: Context<Initialize>, bump: u8) -> Result<()> {
     protocol_state.collateral_ratio = u64::MAX;
     protocol_state.min_collateral_ratio = MIN_COLLATERAL_RATIO;
     protocol_state.peg_deviation_threshold = PEG_DEVIATION_THRESHOLD;
-    protocol_state.is_paused = false;
+    protocol_state.paused_operations = 0;
     protocol_state.last_rebalance_timestamp = Clock::get()?.unix_timestamp;
-    protocol_state.bump = bump;
+    protocol_state.stability_fee = DEFAULT_STABILITY_FEE;
+    protocol_state.rate_index = RATE_INDEX_PRECISION;
+    protocol_state.last_fee_accrual_timestamp = Clock::get()?.unix_timestamp;
+    protocol_state.total_normalized_debt = 0;
+    protocol_state.accrued_fees = 0;
+    protocol_state.close_factor = DEFAULT_CLOSE_FACTOR;
+    protocol_state.total_bad_debt = 0;
+    protocol_state.total_auction_debt = 0;
+    protocol_state.redemption_base_rate = 0;
+    protocol_state.last_redemption_timestamp = Clock::get()?.unix_timestamp;
+    protocol_state.recovery_mode = false;
+    protocol_state.recovery_mode_since = 0;
+    protocol_state.collateral_pool_count = 0;
+    protocol_state.global_settlement = false;
+    protocol_state.settlement_timestamp = 0;
+    protocol_state.settlement_debt = 0;
+    protocol_state.settled_debt = 0;
+    protocol_state.settlement_shortfall = 0;
+    protocol_state.settlement_pools_frozen = 0;
+    protocol_state.settlement_redeemed = 0;
+    protocol_state.flash_mint_cap = 0;
+    protocol_state.flash_mint_fee = DEFAULT_FLASH_MINT_FEE;
+    protocol_state.flash_mint_outstanding = 0;
+    protocol_state.pending_authority = Pubkey::default();
+    protocol_state.guardian = Pubkey::default();
+    protocol_state.bump = ctx.bumps.protocol_state;
 
     msg!("SeedStable protocol initialized successfully");
     Ok(())
 }
+
+/// Creates the stablecoin mint with the protocol as mint authority, plus the
+/// metadata pointer, token metadata and transfer hook extensions if requested.
+fn create_stablecoin_mint(ctx: &Context<Initialize>, metadata: Option<&StablecoinMetadata>) -> Result<()> {
+    let token_program_id = ctx.accounts.token_program.key();
+    let mint = &ctx.accounts.stablecoin_mint;
+    let authority = ctx.accounts.authority.key();
+    let protocol_key = ctx.accounts.protocol_state.key();
+
+    let extensions = mint_extensions(metadata);
+    let space = ExtensionType::try_calculate_account_len::<SplMint>(&extensions)?;
+    // Token metadata is appended by a realloc, which must already be funded
+    let token_metadata = metadata.map(|metadata| TokenMetadata {
+        name: metadata.name.clone(),
+        symbol: metadata.symbol.clone(),
+        uri: metadata.uri.clone(),
+        mint: mint.key(),
+        ..Default::default()
+    });
+    let metadata_len = match &token_metadata {
+        Some(token_metadata) => token_metadata.tlv_size_of()?,
+        None => 0,
+    };
+    let lamports = Rent::get()?.minimum_balance(space + metadata_len);
+
+    system_program::create_account(
+        CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            CreateAccount {
+                from: ctx.accounts.authority.to_account_info(),
+                to: mint.to_account_info(),
+            },
+        ),
+        lamports,
+        space as u64,
+        &token_program_id,
+    )?;
+
+    // Extensions must be initialized before the mint
+    if let Some(metadata) = metadata {
+        invoke(
+            &metadata_pointer::instruction::initialize(
+                &token_program_id,
+                &mint.key(),
+                Some(authority),
+                Some(mint.key()),
+            )?,
+            &[mint.to_account_info()],
+        )?;
+        if metadata.transfer_hook_program.is_some() {
+            invoke(
+                &transfer_hook::instruction::initialize(
+                    &token_program_id,
+                    &mint.key(),
+                    Some(authority),
+                    metadata.transfer_hook_program,
+                )?,
+                &[mint.to_account_info()],
+            )?;
+        }
+    }
+
+    invoke(
+        &spl_token_2022::instruction::initialize_mint2(
+            &token_program_id,
+            &mint.key(),
+            &protocol_key,
+            None,
+            USD_DECIMALS,
+        )?,
+        &[mint.to_account_info()],
+    )?;
+
+    // Token metadata needs the mint authority's signature
+    if let Some(token_metadata) = token_metadata {
+        let seeds = &[
+            PROTOCOL_SEED,
+            &[ctx.bumps.protocol_state],
+        ];
+        invoke_signed(
+            &spl_token_metadata_interface::instruction::initialize(
+                &token_program_id,
+                &mint.key(),
+                &authority,
+                &mint.key(),
+                &protocol_key,
+                token_metadata.name,
+                token_metadata.symbol,
+                token_metadata.uri,
+            ),
+            &[
+                mint.to_account_info(),
+                ctx.accounts.authority.to_account_info(),
+                ctx.accounts.protocol_state.to_account_info(),
+            ],
+            &[&seeds[..]],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Creates the treasury PDA as a stablecoin token account owned by the protocol.
+fn create_treasury(ctx: &Context<Initialize>, metadata: Option<&StablecoinMetadata>) -> Result<()> {
+    let token_program_id = ctx.accounts.token_program.key();
+    let treasury = &ctx.accounts.treasury;
+
+    let extensions = ExtensionType::get_required_init_account_extensions(&mint_extensions(metadata));
+    let space = ExtensionType::try_calculate_account_len::<SplAccount>(&extensions)?;
+    let seeds = &[
+        TREASURY_SEED,
+        &[ctx.bumps.treasury],
+    ];
+
+    system_program::create_account(
+        CpiContext::new_with_signer(
+            ctx.accounts.system_program.to_account_info(),
+            CreateAccount {
+                from: ctx.accounts.authority.to_account_info(),
+                to: treasury.to_account_info(),
+            },
+            &[&seeds[..]],
+        ),
+        Rent::get()?.minimum_balance(space),
+        space as u64,
+        &token_program_id,
+    )?;
+
+    invoke(
+        &spl_token_2022::instruction::initialize_account3(
+            &token_program_id,
+            &treasury.key(),
+            &ctx.accounts.stablecoin_mint.key(),
+            &ctx.accounts.protocol_state.key(),
+        )?,
+        &[
+            treasury.to_account_info(),
+            ctx.accounts.stablecoin_mint.to_account_info(),
+        ],
+    )?;
+
+    Ok(())
+}
+
+fn mint_extensions(metadata: Option<&StablecoinMetadata>) -> Vec<ExtensionType> {
+    let mut extensions = Vec::new();
+    if let Some(metadata) = metadata {
+        extensions.push(ExtensionType::MetadataPointer);
+        if metadata.transfer_hook_program.is_some() {
+            extensions.push(ExtensionType::TransferHook);
+        }
+    }
+    extensions
+}
@@ -1,8 +1,11 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, Transfer, Burn};
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, TransferChecked, Burn};
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+use super::position_index::*;
 
 #[derive(Accounts)]
 pub struct Liquidate<'info> {
@@ -13,7 +16,7 @@ pub struct Liquidate<'info> {
         mut,
         seeds = [PROTOCOL_SEED],
         bump = protocol_state.bump,
-        constraint = !protocol_state.is_paused @ ErrorCode::ProtocolPaused
+        constraint = !protocol_state.is_paused(PAUSE_LIQUIDATE) @ ErrorCode::ProtocolPaused
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -30,7 +33,8 @@ pub struct Liquidate<'info> {
     #[account(
         mut,
         seeds = [COLLATERAL_POOL_SEED, collateral_vault.mint.as_ref()],
-        bump = collateral_pool.bump
+        bump = collateral_pool.bump,
+        constraint = collateral_pool.liquidation_mode == LiquidationMode::FixedDiscount @ ErrorCode::WrongLiquidationMode
     )]
     pub collateral_pool: Account<'info, CollateralPool>,
 
@@ -38,129 +42,327 @@ pub struct Liquidate<'info> {
         mut,
         address = protocol_state.stablecoin_mint
     )]
-    pub stablecoin_mint: Account<'info, Mint>,
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         address = collateral_pool.vault
     )]
-    pub collateral_vault: Account<'info, TokenAccount>,
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint = collateral_vault.mint,
         token::authority = liquidator
     )]
-    pub liquidator_collateral_account: Account<'info, TokenAccount>,
+    pub liquidator_collateral_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
+    /// Leave out to have the stability pool absorb the debt instead
     #[account(
         mut,
         token::mint = protocol_state.stablecoin_mint,
         token::authority = liquidator
     )]
-    pub liquidator_stablecoin_account: Account<'info, TokenAccount>,
+    pub liquidator_stablecoin_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_POOL_SEED],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Option<Box<Account<'info, StabilityPool>>>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_VAULT_SEED],
+        bump
+    )]
+    pub stability_seeds_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_COLLATERAL_SEED, collateral_pool.key().as_ref()],
+        bump
+    )]
+    pub stability_collateral_vault: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub index: PositionIndexHints<'info>,
 
-    pub token_program: Program<'info, Token>,
+    /// Token program of the stablecoin mint; `token_program` is the collateral's
+    pub stablecoin_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: [collateral_pool, price_history] per sub-position,
+    // pools writable so repaid debt can be released from their ceilings
 }
 
-pub fn handler(ctx: Context<Liquidate>, collateral_amount: u64) -> Result<()> {
+#[derive(Accounts)]
+pub struct SetCloseFactor<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+pub fn handler(ctx: Context<Liquidate>, debt_to_repay: u64) -> Result<()> {
+    require!(debt_to_repay > 0, ErrorCode::InvalidLiquidationAmount);
+
     let user_position = &mut ctx.accounts.user_position;
     let collateral_pool = &mut ctx.accounts.collateral_pool;
     let protocol_state = &mut ctx.accounts.protocol_state;
 
-    // Calculate total collateral value
-    let _total_collateral_value: u64 = user_position.collateral_pool
-        .as_ref()
-        .map(|pos| pos.value_usd)
-        .unwrap_or(0);
+    // Value all of the position's collateral at spot prices
+    let valuation = user_position
+        .collateral_value(ctx.remaining_accounts, Valuation::Spot)?;
 
-    // Check if position is liquidatable
+    // Check if debt, including accrued fees, exceeds the collateral's
+    // liquidation thresholds
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+    let debt = protocol_state.debt_for(user_position.normalized_debt)?;
     require!(
-        user_position.is_liquidatable(LIQUIDATION_THRESHOLD),
-        ErrorCode::PositionLiquidatable
+        protocol_state.is_liquidatable(&valuation, debt),
+        ErrorCode::PositionNotLiquidatable
     );
 
-    // Find the collateral pool position
+    // Find the targeted collateral sub-position
     let pool_key = collateral_pool.key();
-    let pool_position = user_position.collateral_pool
-        .as_mut()
-        .filter(|pos| pos.pool == pool_key)
+    let pool_position = user_position
+        .find_position(&pool_key)
+        .copied()
         .ok_or(ErrorCode::InsufficientFunds)?;
+    require!(pool_position.amount > 0, ErrorCode::InsufficientFunds);
 
-    require!(
-        pool_position.amount >= collateral_amount,
-        ErrorCode::InsufficientFunds
-    );
+    // The close factor caps the repayment; the bonus grows as health falls,
+    // up to the pool's liquidation bonus
+    let health_factor = valuation.health_factor(debt);
+    let max_repay = protocol_state.max_liquidation_repay(debt, health_factor);
+    let bonus = collateral_pool.liquidation_bonus
+        .min(BASIS_POINTS.saturating_sub(health_factor).max(MIN_LIQUIDATION_BONUS));
 
-    // Calculate liquidation values
-    let collateral_value = (pool_position.value_usd * collateral_amount) / pool_position.amount;
-    let liquidation_bonus = collateral_value / 20; // 5% bonus
-    let stablecoin_to_burn = collateral_value - liquidation_bonus;
+    // Without an external liquidator balance the stability pool absorbs
+    // the debt, up to what it can offset
+    let external_liquidator = ctx.accounts.liquidator_stablecoin_account.is_some()
+        && ctx.accounts.liquidator_collateral_account.is_some();
+    let mut stablecoin_to_burn = debt_to_repay.min(max_repay);
+    if !external_liquidator {
+        let stability_pool = ctx.accounts.stability_pool
+            .as_ref()
+            .ok_or(ErrorCode::MissingStabilityPoolAccounts)?;
+        stablecoin_to_burn = stablecoin_to_burn.min(stability_pool.max_offset());
+        require!(stablecoin_to_burn > 0, ErrorCode::StabilityPoolDepleted);
+    }
 
-    require!(
-        ctx.accounts.liquidator_stablecoin_account.amount >= stablecoin_to_burn,
-        ErrorCode::InsufficientFunds
-    );
+    // Seized collateral is worth the repaid debt plus the bonus at spot
+    let price = collateral_pool.current_price()?;
+    let mut seize_value = apply_bps(stablecoin_to_burn, BASIS_POINTS + bonus);
+    let mut collateral_amount = calculate_token_amount(seize_value, price, collateral_pool.decimals)?;
+
+    // Not enough collateral in this pool: seize all of it and repay only
+    // what it covers
+    if collateral_amount > pool_position.amount {
+        collateral_amount = pool_position.amount;
+        seize_value = calculate_usd_value(collateral_amount, price, collateral_pool.decimals)?;
+        stablecoin_to_burn = ((seize_value as u128 * BASIS_POINTS as u128)
+            / (BASIS_POINTS + bonus) as u128) as u64;
+    }
+    let liquidation_bonus = seize_value.saturating_sub(stablecoin_to_burn);
 
-    // Burn stablecoins from liquidator
-    let cpi_accounts = Burn {
-        mint: ctx.accounts.stablecoin_mint.to_account_info(),
-        from: ctx.accounts.liquidator_stablecoin_account.to_account_info(),
-        authority: ctx.accounts.liquidator.to_account_info(),
-    };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-    
-    token::burn(cpi_ctx, stablecoin_to_burn)?;
-
-    // Transfer collateral to liquidator
     let seeds = &[
         PROTOCOL_SEED,
         &[protocol_state.bump],
     ];
     let signer = &[&seeds[..]];
 
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.collateral_vault.to_account_info(),
-        to: ctx.accounts.liquidator_collateral_account.to_account_info(),
-        authority: protocol_state.to_account_info(),
-    };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-    
-    token::transfer(cpi_ctx, collateral_amount)?;
+    if external_liquidator {
+        let liquidator_stablecoin_account = ctx.accounts.liquidator_stablecoin_account
+            .as_ref()
+            .ok_or(ErrorCode::InsufficientFunds)?;
+        let liquidator_collateral_account = ctx.accounts.liquidator_collateral_account
+            .as_ref()
+            .ok_or(ErrorCode::InsufficientFunds)?;
+
+        require!(
+            liquidator_stablecoin_account.amount >= stablecoin_to_burn,
+            ErrorCode::InsufficientFunds
+        );
+
+        // Burn stablecoins from liquidator
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.stablecoin_mint.to_account_info(),
+            from: liquidator_stablecoin_account.to_account_info(),
+            authority: ctx.accounts.liquidator.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.stablecoin_token_program.to_account_info();
+        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+        token_interface::burn(cpi_ctx, stablecoin_to_burn)?;
+
+        // Transfer collateral to liquidator
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.collateral_vault.to_account_info(),
+            mint: ctx.accounts.collateral_mint.to_account_info(),
+            to: liquidator_collateral_account.to_account_info(),
+            authority: protocol_state.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token_interface::transfer_checked(cpi_ctx, collateral_amount, ctx.accounts.collateral_mint.decimals)?;
+    } else {
+        let stability_pool = ctx.accounts.stability_pool
+            .as_mut()
+            .ok_or(ErrorCode::MissingStabilityPoolAccounts)?;
+        let stability_seeds_vault = ctx.accounts.stability_seeds_vault
+            .as_ref()
+            .ok_or(ErrorCode::MissingStabilityPoolAccounts)?;
+        let stability_collateral_vault = ctx.accounts.stability_collateral_vault
+            .as_ref()
+            .ok_or(ErrorCode::MissingStabilityPoolAccounts)?;
+        let index = stability_pool
+            .find_collateral(&pool_key)
+            .ok_or(ErrorCode::StabilityPoolCollateralNotSupported)?;
+
+        // Burn pooled stablecoins
+        let cpi_accounts = Burn {
+            mint: ctx.accounts.stablecoin_mint.to_account_info(),
+            from: stability_seeds_vault.to_account_info(),
+            authority: protocol_state.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.stablecoin_token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token_interface::burn(cpi_ctx, stablecoin_to_burn)?;
+
+        // Move collateral to the stability pool for depositors to claim
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.collateral_vault.to_account_info(),
+            mint: ctx.accounts.collateral_mint.to_account_info(),
+            to: stability_collateral_vault.to_account_info(),
+            authority: protocol_state.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token_interface::transfer_checked(cpi_ctx, collateral_amount, ctx.accounts.collateral_mint.decimals)?;
+
+        stability_pool.offset(index, stablecoin_to_burn, collateral_amount)?;
+
+        emit!(StabilityPoolOffset {
+            pool: pool_key,
+            position: user_position.key(),
+            debt_offset: stablecoin_to_burn,
+            collateral_gained: collateral_amount,
+            total_deposits: stability_pool.total_deposits,
+            product: stability_pool.product,
+            scale: stability_pool.scale,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+    }
+
+    // Book value leaving the position
+    let collateral_value = ((pool_position.value_usd as u128 * collateral_amount as u128)
+        / pool_position.amount as u128) as u64;
 
     // Update user position
+    let pool_position = user_position
+        .find_position_mut(&pool_key)
+        .ok_or(ErrorCode::InsufficientFunds)?;
     pool_position.amount = pool_position.amount
         .checked_sub(collateral_amount)
         .ok_or(ErrorCode::MathOverflow)?;
     pool_position.value_usd = pool_position.value_usd
         .checked_sub(collateral_value)
         .ok_or(ErrorCode::MathOverflow)?;
+    user_position.collateral_deposited = user_position.collateral_deposited
+        .saturating_sub(collateral_amount);
 
-    user_position.stablecoin_minted = user_position.stablecoin_minted
-        .checked_sub(stablecoin_to_burn)
-        .ok_or(ErrorCode::MathOverflow)?;
+    let mut normalized_amount = protocol_state.normalize_repayment(user_position.normalized_debt, stablecoin_to_burn)?;
+
+    // Debt left on a position with no collateral can never be repaid by a
+    // liquidation; write it off as bad debt
+    let mut bad_debt = 0;
+    if user_position.has_no_collateral() {
+        let unbacked = user_position.normalized_debt - normalized_amount;
+        bad_debt = protocol_state.debt_for(unbacked)?;
+        normalized_amount = user_position.normalized_debt;
+    }
+
+    let repaid = user_position.repay_debt(&pool_key, normalized_amount)?;
+    release_pool_debt(collateral_pool, &repaid, ctx.remaining_accounts)?;
+    user_position.remove_empty_positions();
     user_position.last_update_timestamp = Clock::get()?.unix_timestamp;
 
     // Update collateral pool
     collateral_pool.total_deposited = collateral_pool.total_deposited
         .checked_sub(collateral_amount)
         .ok_or(ErrorCode::MathOverflow)?;
-    collateral_pool.total_value_usd = collateral_pool.total_value_usd
-        .checked_sub(collateral_value)
-        .ok_or(ErrorCode::MathOverflow)?;
 
     // Update protocol state
-    protocol_state.total_collateral_value = protocol_state.total_collateral_value
-        .checked_sub(collateral_value)
+    collateral_pool.sync_value(protocol_state)?;
+    protocol_state.total_normalized_debt = protocol_state.total_normalized_debt
+        .checked_sub(normalized_amount)
         .ok_or(ErrorCode::MathOverflow)?;
     protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
-        .checked_sub(stablecoin_to_burn)
+        .saturating_sub(stablecoin_to_burn);
+    protocol_state.total_bad_debt = protocol_state.total_bad_debt
+        .checked_add(bad_debt)
         .ok_or(ErrorCode::MathOverflow)?;
-    protocol_state.collateral_ratio = protocol_state.calculate_collateral_ratio();
+    protocol_state.refresh_collateral_ratio(Clock::get()?.unix_timestamp);
+
+    // Keep the position index sorted
+    let position_key = user_position.key();
+    ctx.accounts.index.reposition(position_key, user_position)?;
+
+    let position_debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    emit!(PositionLiquidated {
+        owner: user_position.owner,
+        position: position_key,
+        pool: pool_key,
+        liquidator: ctx.accounts.liquidator.key(),
+        stability_pool: !external_liquidator,
+        debt_repaid: stablecoin_to_burn,
+        collateral_seized: collateral_amount,
+        price,
+        liquidation_bonus,
+        bad_debt,
+        health_factor,
+        position_collateral_value: user_position.total_collateral_value(),
+        position_debt,
+        position_ratio: user_position.book_ratio(position_debt),
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: user_position.last_update_timestamp,
+    });
 
     msg!("Liquidated {} collateral for {} stablecoins with {} bonus", 
          collateral_amount, stablecoin_to_burn, liquidation_bonus);
+    if bad_debt > 0 {
+        msg!("Wrote off {} stablecoins of bad debt", bad_debt);
+    }
+    Ok(())
+}
+
+pub fn set_close_factor_handler(ctx: Context<SetCloseFactor>, close_factor: u64) -> Result<()> {
+    require!(
+        (MIN_CLOSE_FACTOR..=BASIS_POINTS).contains(&close_factor),
+        ErrorCode::InvalidCloseFactor
+    );
+
+    let old_close_factor = ctx.accounts.protocol_state.close_factor;
+    ctx.accounts.protocol_state.close_factor = close_factor;
+
+    emit_parameter_changes(None, &[(Parameter::CloseFactor, old_close_factor, close_factor)])?;
+
+    msg!("Liquidation close factor set to {} bps", close_factor);
     Ok(())
 }
@@ -1,8 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, MintTo};
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, MintTo};
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::position_index::*;
 
 #[derive(Accounts)]
 pub struct MintStable<'info> {
@@ -13,7 +15,7 @@ pub struct MintStable<'info> {
         mut,
         seeds = [PROTOCOL_SEED],
         bump = protocol_state.bump,
-        constraint = !protocol_state.is_paused @ ErrorCode::ProtocolPaused
+        constraint = !protocol_state.is_paused(PAUSE_MINT) @ ErrorCode::ProtocolPaused
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -24,44 +26,73 @@ pub struct MintStable<'info> {
     )]
     pub user_position: Account<'info, UserPosition>,
 
+    /// Pool whose debt ceiling the new debt counts against
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        constraint = collateral_pool.is_active @ ErrorCode::CollateralPoolInactive
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
     #[account(
         mut,
         address = protocol_state.stablecoin_mint
     )]
-    pub stablecoin_mint: Account<'info, Mint>,
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint = stablecoin_mint,
         token::authority = user
     )]
-    pub user_stablecoin_account: Account<'info, TokenAccount>,
+    pub user_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub index: PositionIndexHints<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: [collateral_pool, price_history] per sub-position
 }
 
 pub fn handler(ctx: Context<MintStable>, amount: u64) -> Result<()> {
     let protocol_state = &mut ctx.accounts.protocol_state;
     let user_position = &mut ctx.accounts.user_position;
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
 
-    // Calculate total collateral value for user
-    let total_collateral_value: u64 = user_position.collateral_pool
-        .as_ref()
-        .map(|pos| pos.value_usd)
-        .unwrap_or(0);
+    // Bring the rate index up to date before pricing the new debt
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+    let normalized_amount = protocol_state.normalize_debt(amount, true)?;
 
-    // Calculate new stablecoin amount after minting
-    let new_stablecoin_amount = user_position.stablecoin_minted
-        .checked_add(amount)
+    require!(
+        collateral_pool.can_take_debt(normalized_amount, protocol_state),
+        ErrorCode::DebtCeilingExceeded
+    );
+
+    // Value all of the user's collateral at the lower of spot and TWAP
+    let valuation = user_position
+        .collateral_value(ctx.remaining_accounts, Valuation::Conservative)?;
+
+    // Debt including accrued fees after minting
+    let new_normalized_debt = user_position.normalized_debt
+        .checked_add(normalized_amount)
         .ok_or(ErrorCode::MathOverflow)?;
+    let new_debt = protocol_state.debt_for(new_normalized_debt)?;
 
-    // Check if collateral ratio would be maintained
-    let new_health_factor = user_position.calculate_health_factor(total_collateral_value);
+    // Check the new debt stays within each collateral's max LTV
     require!(
-        new_health_factor >= protocol_state.min_collateral_ratio,
+        valuation.can_borrow(new_debt),
         ErrorCode::InsufficientCollateralRatio
     );
 
+    // In recovery mode a mint must leave the position above the system
+    // minimum, so it lifts the system ratio rather than dragging it down
+    if protocol_state.recovery_mode {
+        require!(
+            valuation.collateral_ratio(new_debt) >= protocol_state.min_collateral_ratio,
+            ErrorCode::RecoveryModeRestricted
+        );
+    }
+
     // Mint stablecoins to user
     let seeds = &[
         PROTOCOL_SEED,
@@ -77,18 +108,45 @@ pub fn handler(ctx: Context<MintStable>, amount: u64) -> Result<()> {
     let cpi_program = ctx.accounts.token_program.to_account_info();
     let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
     
-    token::mint_to(cpi_ctx, amount)?;
+    token_interface::mint_to(cpi_ctx, amount)?;
 
     // Update user position
-    user_position.stablecoin_minted = new_stablecoin_amount;
+    user_position.add_debt(&collateral_pool.key(), normalized_amount)?;
     user_position.last_update_timestamp = Clock::get()?.unix_timestamp;
 
+    // Update collateral pool
+    collateral_pool.total_normalized_debt = collateral_pool.total_normalized_debt
+        .checked_add(normalized_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
     // Update protocol state
+    protocol_state.total_normalized_debt = protocol_state.total_normalized_debt
+        .checked_add(normalized_amount)
+        .ok_or(ErrorCode::MathOverflow)?;
     protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
         .checked_add(amount)
         .ok_or(ErrorCode::MathOverflow)?;
     
-    protocol_state.collateral_ratio = protocol_state.calculate_collateral_ratio();
+    protocol_state.refresh_collateral_ratio(Clock::get()?.unix_timestamp);
+
+    // Keep the position index sorted
+    let position_key = ctx.accounts.user_position.key();
+    ctx.accounts.index.reposition(position_key, &mut ctx.accounts.user_position)?;
+
+    let user_position = &ctx.accounts.user_position;
+    let protocol_state = &ctx.accounts.protocol_state;
+    emit!(StablecoinMinted {
+        owner: user_position.owner,
+        position: position_key,
+        pool: ctx.accounts.collateral_pool.key(),
+        amount,
+        position_collateral_value: user_position.total_collateral_value(),
+        position_debt: new_debt,
+        position_ratio: user_position.book_ratio(new_debt),
+        total_stablecoin_supply: protocol_state.total_stablecoin_supply,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: user_position.last_update_timestamp,
+    });
 
     msg!("Minted {} stablecoins to user", amount);
     Ok(())
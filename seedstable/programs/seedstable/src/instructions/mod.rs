@@ -1,9 +1,46 @@
+// Every instruction module exposes its own `handler`; lib.rs calls them by path.
+#![allow(ambiguous_glob_reexports)]
+
 pub mod initialize;
 pub mod emergency_pause;
+pub mod deposit_collateral;
+pub mod withdraw_collateral;
 pub mod mint_stable;
 pub mod burn_stable;
+pub mod liquidate;
+pub mod rebalance;
+pub mod oracle_update;
+pub mod update_collateral_pool;
+pub mod stability_fee;
+pub mod auction;
+pub mod stability_pool;
+pub mod redeem;
+pub mod position_index;
+pub mod settlement;
+pub mod flash_mint;
+pub mod authority;
+pub mod native_sol;
+pub mod seed_lot;
+pub mod views;
 
 pub use initialize::*;
 pub use emergency_pause::*;
+pub use deposit_collateral::*;
+pub use withdraw_collateral::*;
 pub use mint_stable::*;
 pub use burn_stable::*;
+pub use liquidate::*;
+pub use rebalance::*;
+pub use oracle_update::*;
+pub use update_collateral_pool::*;
+pub use stability_fee::*;
+pub use auction::*;
+pub use stability_pool::*;
+pub use redeem::*;
+pub use position_index::*;
+pub use settlement::*;
+pub use flash_mint::*;
+pub use authority::*;
+pub use native_sol::*;
+pub use seed_lot::*;
+pub use views::*;
@@ -0,0 +1,189 @@
+use anchor_lang::prelude::*;
+use anchor_lang::system_program::{self, Transfer};
+use anchor_spl::token_interface::{
+    self, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface, TransferChecked,
+};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use super::deposit_collateral::book_deposit;
+use super::withdraw_collateral::book_withdrawal;
+use super::position_index::*;
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_DEPOSIT) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        init_if_needed,
+        payer = user,
+        space = UserPosition::LEN,
+        seeds = [USER_POSITION_SEED, user.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        constraint = collateral_pool.is_active @ ErrorCode::CollateralPoolInactive,
+        constraint = collateral_pool.collateral_type == CollateralType::SOL @ ErrorCode::InvalidCollateralType,
+        constraint = collateral_pool.is_native_sol() @ ErrorCode::InvalidCollateralType
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        mut,
+        address = collateral_pool.vault
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub index: PositionIndexHints<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawSol<'info> {
+    #[account(mut)]
+    pub user: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_WITHDRAW) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [USER_POSITION_SEED, user.key().as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        constraint = collateral_pool.is_native_sol() @ ErrorCode::InvalidCollateralType
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        seeds = [PRICE_HISTORY_SEED, collateral_pool.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    #[account(
+        mut,
+        address = collateral_pool.vault
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    /// Temporary wSOL account, closed to the user within the instruction
+    #[account(
+        init,
+        payer = user,
+        seeds = [SOL_UNWRAP_SEED, user.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = protocol_state
+    )]
+    pub unwrap_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub index: PositionIndexHints<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    // remaining_accounts: [collateral_pool, price_history] per sub-position
+}
+
+/// Deposits `amount` lamports from the signer into a wrapped SOL pool.
+pub fn deposit_sol_handler(ctx: Context<DepositSol>, amount: u64) -> Result<()> {
+    let usd_value = book_deposit(
+        &ctx.accounts.user,
+        &mut ctx.accounts.protocol_state,
+        &mut ctx.accounts.user_position,
+        ctx.bumps.user_position,
+        &mut ctx.accounts.collateral_pool,
+        &mut ctx.accounts.index,
+        amount,
+    )?;
+
+    // Move the lamports into the vault and wrap them
+    let cpi_accounts = Transfer {
+        from: ctx.accounts.user.to_account_info(),
+        to: ctx.accounts.collateral_vault.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.system_program.to_account_info(), cpi_accounts);
+    system_program::transfer(cpi_ctx, amount)?;
+
+    let cpi_accounts = SyncNative {
+        account: ctx.accounts.collateral_vault.to_account_info(),
+    };
+    let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
+    token_interface::sync_native(cpi_ctx)?;
+
+    msg!("Deposited {} lamports worth ${} USD", amount, usd_value);
+    Ok(())
+}
+
+/// Withdraws `amount` wrapped SOL from the pool and unwraps it to the
+/// signer's lamports through a temporary token account.
+pub fn withdraw_sol_handler(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
+    let withdrawal_value = book_withdrawal(
+        &mut ctx.accounts.protocol_state,
+        &mut ctx.accounts.user_position,
+        &mut ctx.accounts.collateral_pool,
+        &ctx.accounts.price_history,
+        &mut ctx.accounts.index,
+        ctx.remaining_accounts,
+        amount,
+    )?;
+
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[ctx.accounts.protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.collateral_vault.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+        to: ctx.accounts.unwrap_account.to_account_info(),
+        authority: ctx.accounts.protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.collateral_mint.decimals)?;
+
+    // Closing a native account pays out its wrapped balance and rent
+    let cpi_accounts = CloseAccount {
+        account: ctx.accounts.unwrap_account.to_account_info(),
+        destination: ctx.accounts.user.to_account_info(),
+        authority: ctx.accounts.protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::close_account(cpi_ctx)?;
+
+    msg!("Withdrew {} lamports worth ${} USD", amount, withdrawal_value);
+    Ok(())
+}
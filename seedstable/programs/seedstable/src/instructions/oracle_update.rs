@@ -1,20 +1,28 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{Mint, TokenInterface, TokenAccount};
 use crate::state::*;
 use crate::constants::*;
+use crate::oracle::{aggregate_pool_price, load_price_data};
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
 
 #[derive(Accounts)]
 #[instruction(collateral_type: CollateralType)]
 pub struct CreateCollateralPool<'info> {
     #[account(
-        constraint = authority.key() == protocol_state.authority @ ErrorCode::Unauthorized
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
     )]
     pub authority: Signer<'info>,
 
+    pub signers: AuthoritySigners<'info>,
+
     #[account(
         mut,
         seeds = [PROTOCOL_SEED],
-        bump = protocol_state.bump
+        bump = protocol_state.bump,
+        constraint = !protocol_state.global_settlement @ ErrorCode::SettlementActive
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -27,7 +35,7 @@ pub struct CreateCollateralPool<'info> {
     )]
     pub collateral_pool: Account<'info, CollateralPool>,
 
-    pub collateral_mint: Account<'info, Mint>,
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         init,
@@ -35,87 +43,534 @@ pub struct CreateCollateralPool<'info> {
         token::mint = collateral_mint,
         token::authority = protocol_state,
     )]
-    pub collateral_vault: Account<'info, TokenAccount>,
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
 
-    /// CHECK: Oracle account for price feeds
+    /// Receives collateral from auctions that expire short of their debt
+    #[account(
+        init,
+        payer = authority,
+        seeds = [TREASURY_SEED, collateral_mint.key().as_ref()],
+        bump,
+        token::mint = collateral_mint,
+        token::authority = protocol_state,
+    )]
+    pub treasury_collateral_account: Box<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = OracleConfig::LEN,
+        seeds = [ORACLE_CONFIG_SEED, collateral_pool.key().as_ref(), oracle_account.key().as_ref()],
+        bump
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PriceHistory::LEN,
+        seeds = [PRICE_HISTORY_SEED, collateral_pool.key().as_ref()],
+        bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
+    /// CHECK: Pyth or Switchboard feed, layout validated in instruction
     pub oracle_account: AccountInfo<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
     pub rent: Sysvar<'info, Rent>,
 }
 
+#[derive(Accounts)]
+pub struct AddOracleSource<'info> {
+    #[account(
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = OracleConfig::LEN,
+        seeds = [ORACLE_CONFIG_SEED, collateral_pool.key().as_ref(), oracle_account.key().as_ref()],
+        bump
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+
+    /// CHECK: Pyth or Switchboard feed, layout validated in instruction
+    pub oracle_account: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReplaceOracleSource<'info> {
+    #[account(
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        mut,
+        close = authority,
+        has_one = collateral_pool
+    )]
+    pub old_oracle_config: Account<'info, OracleConfig>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = OracleConfig::LEN,
+        seeds = [ORACLE_CONFIG_SEED, collateral_pool.key().as_ref(), oracle_account.key().as_ref()],
+        bump
+    )]
+    pub oracle_config: Account<'info, OracleConfig>,
+
+    /// CHECK: Pyth or Switchboard feed, layout validated in instruction
+    pub oracle_account: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetOracleQuorum<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetTwapWindow<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        mut,
+        seeds = [PRICE_HISTORY_SEED, collateral_pool.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+}
+
 #[derive(Accounts)]
 pub struct UpdateOraclePrice<'info> {
     #[account(mut)]
     pub authority: Signer<'info>,
 
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_ORACLE_UPDATE) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
     #[account(
         mut,
         seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
-        bump = collateral_pool.bump
+        bump = collateral_pool.bump,
+        has_one = protocol_state
     )]
     pub collateral_pool: Account<'info, CollateralPool>,
 
-    /// CHECK: Oracle account validation happens in instruction
-    pub oracle_account: AccountInfo<'info>,
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [PRICE_HISTORY_SEED, collateral_pool.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+    // remaining_accounts: [oracle_config, price_feed] pairs, see `aggregate_pool_price`
 }
 
 pub fn create_collateral_pool_handler(
     ctx: Context<CreateCollateralPool>, 
     collateral_type: CollateralType,
-    max_deposit: u64
+    oracle_type: OracleType,
+    max_deposit: u64,
+    debt_ceiling: u64,
 ) -> Result<()> {
+    CollateralPool::validate_caps(collateral_type, max_deposit, debt_ceiling)?;
+    validate_oracle_account(
+        oracle_type,
+        &ctx.accounts.oracle_account,
+        collateral_type,
+        &ctx.accounts.collateral_mint.key(),
+    )?;
+
     let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let oracle_config = &mut ctx.accounts.oracle_config;
+
+    oracle_config.collateral_pool = collateral_pool.key();
+    oracle_config.oracle_type = oracle_type;
+    oracle_config.price_feed = ctx.accounts.oracle_account.key();
+    oracle_config.max_staleness = MAX_PRICE_STALENESS;
+    oracle_config.max_deviation = MAX_PRICE_DEVIATION;
+    oracle_config.decimals = USD_DECIMALS;
+    oracle_config.is_active = true;
+    oracle_config.bump = ctx.bumps.oracle_config;
     
     collateral_pool.protocol_state = ctx.accounts.protocol_state.key();
     collateral_pool.collateral_type = collateral_type;
     collateral_pool.mint = ctx.accounts.collateral_mint.key();
+    collateral_pool.decimals = ctx.accounts.collateral_mint.decimals;
     collateral_pool.vault = ctx.accounts.collateral_vault.key();
-    collateral_pool.oracle = ctx.accounts.oracle_account.key();
+    collateral_pool.oracle_configs = [Pubkey::default(); MAX_ORACLE_SOURCES];
+    collateral_pool.oracle_configs[0] = oracle_config.key();
+    collateral_pool.num_oracle_sources = 1;
+    collateral_pool.fallback_oracle_config = Pubkey::default();
+    collateral_pool.min_oracle_quorum = 1;
+    collateral_pool.last_price = 0;
+    collateral_pool.last_price_timestamp = 0;
     collateral_pool.total_deposited = 0;
     collateral_pool.total_value_usd = 0;
-    collateral_pool.liquidation_threshold = LIQUIDATION_THRESHOLD;
+    let (max_ltv, liquidation_threshold, liquidation_bonus) = collateral_type.default_risk_params();
+    collateral_pool.max_ltv = max_ltv;
+    collateral_pool.liquidation_threshold = liquidation_threshold;
+    collateral_pool.liquidation_bonus = liquidation_bonus;
+    collateral_pool.debt_ceiling = debt_ceiling;
+    collateral_pool.total_normalized_debt = 0;
+    collateral_pool.liquidation_mode = LiquidationMode::FixedDiscount;
+    collateral_pool.auction_start_premium = DEFAULT_AUCTION_START_PREMIUM;
+    collateral_pool.auction_floor = DEFAULT_AUCTION_FLOOR;
+    collateral_pool.auction_duration = DEFAULT_AUCTION_DURATION;
+    collateral_pool.sunset_start = 0;
+    collateral_pool.sunset_duration = 0;
+    collateral_pool.sunset_threshold = 0;
+    collateral_pool.settlement_price = 0;
+    collateral_pool.settlement_collateral = 0;
     collateral_pool.max_deposit_amount = max_deposit;
     collateral_pool.is_active = true;
-    collateral_pool.bump = *ctx.bumps.get("collateral_pool").unwrap();
+    collateral_pool.bump = ctx.bumps.collateral_pool;
+
+    let price_history = &mut ctx.accounts.price_history;
+    price_history.collateral_pool = collateral_pool.key();
+    price_history.twap_window = DEFAULT_TWAP_WINDOW;
+    price_history.bump = ctx.bumps.price_history;
+
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    protocol_state.collateral_pool_count = protocol_state.collateral_pool_count
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(CollateralPoolCreated {
+        pool: collateral_pool.key(),
+        mint: collateral_pool.mint,
+        collateral_type,
+        oracle_type,
+        price_feed: oracle_config.price_feed,
+        max_ltv,
+        liquidation_threshold,
+        liquidation_bonus,
+        debt_ceiling,
+        max_deposit,
+        twap_window: price_history.twap_window,
+        created_by: ctx.accounts.authority.key(),
+        timestamp: Clock::get()?.unix_timestamp,
+    });
 
     msg!("Created collateral pool for {:?}", collateral_type);
     Ok(())
 }
 
+/// Rejects oracle accounts that do not match the declared oracle family. A
+/// seed lot registry can only price the tokenized seeds mint it issues.
+fn validate_oracle_account(
+    oracle_type: OracleType,
+    oracle_account: &AccountInfo,
+    collateral_type: CollateralType,
+    collateral_mint: &Pubkey,
+) -> Result<()> {
+    load_price_data(oracle_type, oracle_account)?;
+    if oracle_type == OracleType::SeedLotRegistry {
+        let registry: SeedLotRegistry = load_program_account(oracle_account)?;
+        require!(
+            collateral_type == CollateralType::TokenizedSeeds && registry.mint == *collateral_mint,
+            ErrorCode::InvalidOracleConfig
+        );
+    }
+    Ok(())
+}
+
+fn validate_source_limits(max_staleness: i64, max_deviation: u64) -> Result<()> {
+    require!(
+        max_staleness > 0 && max_staleness <= MAX_PRICE_STALENESS,
+        ErrorCode::InvalidOracleConfig
+    );
+    require!(
+        max_deviation > 0 && max_deviation <= MAX_PRICE_DEVIATION,
+        ErrorCode::InvalidOracleConfig
+    );
+    Ok(())
+}
+
+pub fn add_oracle_source_handler(
+    ctx: Context<AddOracleSource>,
+    oracle_type: OracleType,
+    max_staleness: i64,
+    max_deviation: u64,
+    is_fallback: bool,
+) -> Result<()> {
+    validate_source_limits(max_staleness, max_deviation)?;
+
+    validate_oracle_account(
+        oracle_type,
+        &ctx.accounts.oracle_account,
+        ctx.accounts.collateral_pool.collateral_type,
+        &ctx.accounts.collateral_pool.mint,
+    )?;
+
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let oracle_config = &mut ctx.accounts.oracle_config;
+
+    oracle_config.collateral_pool = collateral_pool.key();
+    oracle_config.oracle_type = oracle_type;
+    oracle_config.price_feed = ctx.accounts.oracle_account.key();
+    oracle_config.max_staleness = max_staleness;
+    oracle_config.max_deviation = max_deviation;
+    oracle_config.decimals = USD_DECIMALS;
+    oracle_config.is_active = true;
+    oracle_config.bump = ctx.bumps.oracle_config;
+
+    if is_fallback {
+        // An existing fallback is swapped through replace_oracle_source
+        require_keys_eq!(
+            collateral_pool.fallback_oracle_config,
+            Pubkey::default(),
+            ErrorCode::FallbackOracleExists
+        );
+        collateral_pool.fallback_oracle_config = oracle_config.key();
+    } else {
+        let index = collateral_pool.num_oracle_sources as usize;
+        require!(index < MAX_ORACLE_SOURCES, ErrorCode::TooManyOracleSources);
+        collateral_pool.oracle_configs[index] = oracle_config.key();
+        collateral_pool.num_oracle_sources += 1;
+    }
+
+    emit!(OracleSourceAdded {
+        pool: collateral_pool.key(),
+        price_feed: oracle_config.price_feed,
+        oracle_type,
+        max_staleness,
+        max_deviation,
+        is_fallback,
+        num_oracle_sources: collateral_pool.num_oracle_sources,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!(
+        "Added {:?} oracle source {} (fallback: {})",
+        oracle_type,
+        oracle_config.price_feed,
+        is_fallback
+    );
+    Ok(())
+}
+
+/// Swaps one of the pool's primary or fallback sources for a new feed in the
+/// same slot, closing the old config.
+pub fn replace_oracle_source_handler(
+    ctx: Context<ReplaceOracleSource>,
+    oracle_type: OracleType,
+    max_staleness: i64,
+    max_deviation: u64,
+) -> Result<()> {
+    validate_source_limits(max_staleness, max_deviation)?;
+
+    validate_oracle_account(
+        oracle_type,
+        &ctx.accounts.oracle_account,
+        ctx.accounts.collateral_pool.collateral_type,
+        &ctx.accounts.collateral_pool.mint,
+    )?;
+
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let old_oracle_config = &ctx.accounts.old_oracle_config;
+    let oracle_config = &mut ctx.accounts.oracle_config;
+
+    oracle_config.collateral_pool = collateral_pool.key();
+    oracle_config.oracle_type = oracle_type;
+    oracle_config.price_feed = ctx.accounts.oracle_account.key();
+    oracle_config.max_staleness = max_staleness;
+    oracle_config.max_deviation = max_deviation;
+    oracle_config.decimals = USD_DECIMALS;
+    oracle_config.is_active = true;
+    oracle_config.bump = ctx.bumps.oracle_config;
+
+    let is_fallback = collateral_pool.fallback_oracle_config == old_oracle_config.key();
+    if is_fallback {
+        collateral_pool.fallback_oracle_config = oracle_config.key();
+    } else {
+        let index = collateral_pool
+            .active_oracle_configs()
+            .iter()
+            .position(|config| *config == old_oracle_config.key())
+            .ok_or(ErrorCode::InvalidOracleConfig)?;
+        collateral_pool.oracle_configs[index] = oracle_config.key();
+    }
+
+    emit!(OracleSourceReplaced {
+        pool: collateral_pool.key(),
+        old_price_feed: old_oracle_config.price_feed,
+        new_price_feed: oracle_config.price_feed,
+        oracle_type,
+        is_fallback,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!(
+        "Replaced oracle source {} with {:?} source {} (fallback: {})",
+        old_oracle_config.price_feed,
+        oracle_type,
+        oracle_config.price_feed,
+        is_fallback
+    );
+    Ok(())
+}
+
+pub fn set_oracle_quorum_handler(ctx: Context<SetOracleQuorum>, min_quorum: u8) -> Result<()> {
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+
+    require!(
+        min_quorum > 0 && min_quorum <= collateral_pool.num_oracle_sources,
+        ErrorCode::InvalidOracleConfig
+    );
+    let old_quorum = collateral_pool.min_oracle_quorum;
+    collateral_pool.min_oracle_quorum = min_quorum;
+
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[(Parameter::OracleQuorum, old_quorum as u64, min_quorum as u64)],
+    )?;
+
+    msg!("Oracle quorum set to {} of {}", min_quorum, collateral_pool.num_oracle_sources);
+    Ok(())
+}
+
+pub fn set_twap_window_handler(ctx: Context<SetTwapWindow>, twap_window: i64) -> Result<()> {
+    require!(
+        (MIN_TWAP_WINDOW..=MAX_TWAP_WINDOW).contains(&twap_window),
+        ErrorCode::InvalidOracleConfig
+    );
+    let old_window = ctx.accounts.price_history.twap_window;
+    ctx.accounts.price_history.twap_window = twap_window;
+
+    emit_parameter_changes(
+        Some(ctx.accounts.collateral_pool.key()),
+        &[(Parameter::TwapWindow, old_window as u64, twap_window as u64)],
+    )?;
+
+    msg!("TWAP window set to {} seconds", twap_window);
+    Ok(())
+}
+
 pub fn update_oracle_price_handler(ctx: Context<UpdateOraclePrice>) -> Result<()> {
     let collateral_pool = &mut ctx.accounts.collateral_pool;
     
-    // Get updated price from oracle
-    let price_data = get_oracle_price_data(&ctx.accounts.oracle_account)?;
-    
-    // Validate price data
-    require!(price_data.is_valid, ErrorCode::InvalidOracleConfig);
-    require!(!price_data.is_stale(MAX_PRICE_STALENESS), ErrorCode::StalePriceData);
-    require!(price_data.has_high_confidence(MAX_PRICE_DEVIATION), ErrorCode::LowConfidencePriceData);
+    // Aggregate the pool's oracle set, skipping stale or low-confidence sources
+    let price_data = aggregate_pool_price(collateral_pool, ctx.remaining_accounts)?;
+    collateral_pool.last_price = price_data.price;
+    collateral_pool.last_price_timestamp = price_data.timestamp;
+    ctx.accounts.price_history.record(price_data.price, price_data.timestamp)?;
     
     // Update collateral pool value
-    let decimals = get_mint_decimals(&collateral_pool.mint)?;
+    let decimals = ctx.accounts.collateral_mint.decimals;
+    let previous_value = collateral_pool.total_value_usd;
     collateral_pool.update_value(price_data.price, decimals);
+
+    // Carry the revaluation through to the protocol totals
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    protocol_state.total_collateral_value = protocol_state.total_collateral_value
+        .saturating_sub(previous_value)
+        .checked_add(collateral_pool.total_value_usd)
+        .ok_or(ErrorCode::MathOverflow)?;
+    let now = Clock::get()?.unix_timestamp;
+    protocol_state.refresh_collateral_ratio(now);
+
+    let price_history = &ctx.accounts.price_history;
+    emit!(OraclePriceUpdated {
+        pool: collateral_pool.key(),
+        price: price_data.price,
+        confidence: price_data.confidence,
+        price_timestamp: price_data.timestamp,
+        twap: price_history.twap(now)?,
+        ema: price_history.ema()?,
+        pool_value_usd: collateral_pool.total_value_usd,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: now,
+    });
     
     msg!("Updated oracle price: ${}", price_data.price);
     Ok(())
 }
-
-fn get_oracle_price_data(_oracle_account: &AccountInfo) -> Result<PriceData> {
-    // Simplified oracle integration - in production would use Pyth/Switchboard SDK
-    Ok(PriceData {
-        price: 100_000_000, // $100.00 with 6 decimals
-        confidence: 50_000, // $0.05 confidence interval
-        timestamp: Clock::get()?.unix_timestamp,
-        is_valid: true,
-    })
-}
-
-fn get_mint_decimals(_mint: &Pubkey) -> Result<u8> {
-    // Simplified - in production would read from mint account
-    Ok(9) // SOL has 9 decimals
-}
-
-use anchor_spl::token::{Mint, TokenAccount};
@@ -0,0 +1,119 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use super::authority::*;
+
+#[derive(Accounts)]
+pub struct InitializePositionIndex<'info> {
+    #[account(
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = PositionIndex::LEN,
+        seeds = [POSITION_INDEX_SEED],
+        bump
+    )]
+    pub position_index: Account<'info, PositionIndex>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Accounts needed to move a position within the index. `prev_position` and
+/// `next_position` are its current neighbours; `insert_prev` and
+/// `insert_next` are the neighbours it should sit between afterwards, found
+/// off-chain by walking the list. Any of them may be omitted at the ends of
+/// the list.
+#[derive(Accounts)]
+pub struct PositionIndexHints<'info> {
+    #[account(
+        mut,
+        seeds = [POSITION_INDEX_SEED],
+        bump = position_index.bump
+    )]
+    pub position_index: Account<'info, PositionIndex>,
+
+    /// CHECK: must match user_position.prev_position, deserialized in reposition
+    #[account(mut)]
+    pub prev_position: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: must match user_position.next_position, deserialized in reposition
+    #[account(mut)]
+    pub next_position: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: validated against the list in reposition
+    #[account(mut)]
+    pub insert_prev: Option<UncheckedAccount<'info>>,
+
+    /// CHECK: validated against the list in reposition
+    #[account(mut)]
+    pub insert_next: Option<UncheckedAccount<'info>>,
+}
+
+impl<'info> PositionIndexHints<'info> {
+    /// Unlinks `position` and re-inserts it at its current nominal ratio.
+    /// Positions without debt are left out of the index.
+    pub fn reposition(&mut self, position_key: Pubkey, position: &mut UserPosition) -> Result<()> {
+        let hints = [&self.prev_position, &self.next_position, &self.insert_prev, &self.insert_next];
+        let accounts: Vec<AccountInfo<'info>> = hints
+            .iter()
+            .filter_map(|hint| hint.as_ref().map(|a| a.to_account_info()))
+            .collect();
+        let insert_prev = self.insert_prev.as_ref().map(|a| a.key()).unwrap_or_default();
+        let insert_next = self.insert_next.as_ref().map(|a| a.key()).unwrap_or_default();
+
+        self.position_index.reposition(&accounts, position_key, position, insert_prev, insert_next)
+    }
+}
+
+#[derive(Accounts)]
+pub struct ReindexPosition<'info> {
+    #[account(
+        mut,
+        seeds = [USER_POSITION_SEED, user_position.owner.as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    pub index: PositionIndexHints<'info>,
+}
+
+pub fn initialize_position_index_handler(ctx: Context<InitializePositionIndex>) -> Result<()> {
+    let position_index = &mut ctx.accounts.position_index;
+    position_index.protocol_state = ctx.accounts.protocol_state.key();
+    position_index.head = Pubkey::default();
+    position_index.tail = Pubkey::default();
+    position_index.size = 0;
+    position_index.bump = ctx.bumps.position_index;
+
+    msg!("Position index initialized");
+    Ok(())
+}
+
+/// Moves a position to its current nominal ratio. Instructions that change a
+/// position's collateral or debt reposition it themselves; anyone can call
+/// this to re-sort an entry whose key is out of date.
+pub fn reindex_position_handler(ctx: Context<ReindexPosition>) -> Result<()> {
+    let position_key = ctx.accounts.user_position.key();
+    ctx.accounts.index.reposition(position_key, &mut ctx.accounts.user_position)?;
+
+    msg!(
+        "Position {} reindexed at nominal ratio {}",
+        position_key,
+        ctx.accounts.user_position.sort_key
+    );
+    Ok(())
+}
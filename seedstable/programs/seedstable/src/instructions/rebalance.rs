@@ -1,8 +1,9 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount, MintTo, Burn};
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, MintTo, Burn};
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
 
 #[derive(Accounts)]
 pub struct Rebalance<'info> {
@@ -13,7 +14,7 @@ pub struct Rebalance<'info> {
         mut,
         seeds = [PROTOCOL_SEED],
         bump = protocol_state.bump,
-        constraint = !protocol_state.is_paused @ ErrorCode::ProtocolPaused
+        constraint = !protocol_state.is_paused(PAUSE_REBALANCE) @ ErrorCode::ProtocolPaused
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -21,18 +22,18 @@ pub struct Rebalance<'info> {
         mut,
         address = protocol_state.stablecoin_mint
     )]
-    pub stablecoin_mint: Account<'info, Mint>,
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         address = protocol_state.treasury
     )]
-    pub treasury: Account<'info, TokenAccount>,
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
 
     /// CHECK: Oracle account for USD price feed
     pub usd_oracle: AccountInfo<'info>,
 
-    pub token_program: Program<'info, Token>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 pub fn handler(ctx: Context<Rebalance>) -> Result<()> {
@@ -78,7 +79,7 @@ pub fn handler(ctx: Context<Rebalance>) -> Result<()> {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
         
-        token::mint_to(cpi_ctx, rebalance_amount)?;
+        token_interface::mint_to(cpi_ctx, rebalance_amount)?;
         
         protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
             .checked_add(rebalance_amount)
@@ -100,7 +101,7 @@ pub fn handler(ctx: Context<Rebalance>) -> Result<()> {
         let cpi_program = ctx.accounts.token_program.to_account_info();
         let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
         
-        token::burn(cpi_ctx, rebalance_amount)?;
+        token_interface::burn(cpi_ctx, rebalance_amount)?;
         
         protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
             .checked_sub(rebalance_amount)
@@ -111,7 +112,17 @@ pub fn handler(ctx: Context<Rebalance>) -> Result<()> {
 
     // Update protocol state
     protocol_state.last_rebalance_timestamp = Clock::get()?.unix_timestamp;
-    protocol_state.collateral_ratio = protocol_state.calculate_collateral_ratio();
+    protocol_state.refresh_collateral_ratio(Clock::get()?.unix_timestamp);
+
+    emit!(Rebalanced {
+        stablecoin_price: current_price,
+        deviation,
+        expanded: current_price > target_price,
+        amount: rebalance_amount,
+        total_stablecoin_supply: protocol_state.total_stablecoin_supply,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: protocol_state.last_rebalance_timestamp,
+    });
 
     Ok(())
 }
@@ -126,8 +137,9 @@ fn calculate_rebalance_amount(total_supply: u64, deviation_bps: u64) -> Result<u
     // Calculate rebalance amount as percentage of total supply based on deviation
     let rebalance_percentage = std::cmp::min(deviation_bps / 10, 1000); // Max 10% rebalance
     
-    total_supply
+    let amount = total_supply
         .checked_mul(rebalance_percentage)
         .and_then(|x| x.checked_div(10000))
-        .ok_or(ErrorCode::MathOverflow)?
+        .ok_or(ErrorCode::MathOverflow)?;
+    Ok(amount)
 }
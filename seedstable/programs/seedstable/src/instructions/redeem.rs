@@ -0,0 +1,284 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, TransferChecked, Burn};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+
+#[derive(Accounts)]
+pub struct Redeem<'info> {
+    pub redeemer: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_WITHDRAW) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(
+        mut,
+        seeds = [POSITION_INDEX_SEED],
+        bump = position_index.bump
+    )]
+    pub position_index: Account<'info, PositionIndex>,
+
+    #[account(
+        mut,
+        address = protocol_state.stablecoin_mint
+    )]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = collateral_pool.vault
+    )]
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = collateral_pool.mint,
+        token::authority = redeemer
+    )]
+    pub redeemer_collateral_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint,
+        token::authority = redeemer
+    )]
+    pub redeemer_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token program of the stablecoin mint; `token_program` is the collateral's
+    pub stablecoin_token_program: Interface<'info, TokenInterface>,
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts, per position following the position index from its head:
+    // [user_position, insert_prev, insert_next, then a writable
+    // [collateral_pool, price_history] pair per sub-position]. The insert
+    // hints are the neighbours the position moves between once redeemed, with
+    // the program id standing in for an end of the list. The position after
+    // the last one redeemed must be passed too, since unlinking updates it.
+}
+
+/// Burns up to `amount` SEEDS for $1 of collateral each, less the redemption
+/// fee, taken from the positions at the head of the position index. The fee
+/// is priced on the amount actually redeemed and its share of the collateral
+/// stays with the redeemed positions, which are then moved to their new place
+/// in the index. Positions below 100% collateral ratio are skipped and left
+/// for liquidation.
+pub fn handler(ctx: Context<Redeem>, amount: u64, max_fee: u64) -> Result<()> {
+    require!(amount > 0, ErrorCode::InsufficientFunds);
+    require!(
+        ctx.accounts.redeemer_stablecoin_account.amount >= amount,
+        ErrorCode::InsufficientFunds
+    );
+
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let position_index = &mut ctx.accounts.position_index;
+    let now = Clock::get()?.unix_timestamp;
+
+    protocol_state.accrue_stability_fee(now)?;
+
+    let pool_key = collateral_pool.key();
+    let price = collateral_pool.current_price()?;
+
+    // Walk the index from its head and decide how much each position
+    // redeems, before anything is moved
+    let mut remaining = amount;
+    let mut total_redeemed: u64 = 0;
+    let mut redemptions = Vec::new();
+    let mut expected_position = position_index.head;
+
+    let accounts = ctx.remaining_accounts;
+    let mut cursor = 0;
+    while cursor < accounts.len() && remaining > 0 {
+        let start = cursor;
+        let position_info = &accounts[start];
+        require_keys_eq!(position_info.key(), expected_position, ErrorCode::RedemptionOrderViolated);
+        let position: UserPosition = load_program_account(position_info)?;
+        expected_position = position.next_position;
+        let pairs_end = start + 3 + position.collateral_positions.len() * 2;
+        require!(pairs_end <= accounts.len(), ErrorCode::MissingCollateralAccounts);
+        let pool_accounts = &accounts[start + 3..pairs_end];
+        cursor = pairs_end;
+
+        let debt = protocol_state.debt_for(position.normalized_debt)?;
+        if debt == 0 {
+            continue;
+        }
+        let valuation = position.collateral_value(pool_accounts, Valuation::Spot)?;
+        let ratio = valuation.collateral_ratio(debt);
+        if ratio < BASIS_POINTS {
+            continue;
+        }
+
+        let pool_position = match position.find_position(&pool_key) {
+            Some(pool_position) if pool_position.amount > 0 => *pool_position,
+            _ => continue,
+        };
+
+        // Redeem no more than the debt or the collateral's value at $1
+        let pool_value = calculate_usd_value(pool_position.amount, price, collateral_pool.decimals)?;
+        let redeemed = remaining.min(debt).min(pool_value);
+        redemptions.push((start, pairs_end, redeemed));
+        remaining -= redeemed;
+        total_redeemed += redeemed;
+    }
+
+    require!(total_redeemed > 0, ErrorCode::NothingToRedeem);
+
+    let fee = protocol_state.update_redemption_rate(total_redeemed, now);
+    require!(fee <= max_fee, ErrorCode::RedemptionFeeExceeded);
+
+    let mut total_collateral: u64 = 0;
+    let mut total_normalized: u64 = 0;
+    let mut events = Vec::with_capacity(redemptions.len());
+    for (start, pairs_end, redeemed) in redemptions {
+        let position_info = &accounts[start];
+        let position_key = position_info.key();
+        let pool_accounts = &accounts[start + 3..pairs_end];
+        let mut position: UserPosition = load_program_account(position_info)?;
+        let pool_position = position
+            .find_position(&pool_key)
+            .copied()
+            .ok_or(ErrorCode::InsufficientFunds)?;
+
+        let collateral_amount = calculate_token_amount(
+            apply_bps(redeemed, BASIS_POINTS - fee),
+            price,
+            collateral_pool.decimals,
+        )?
+        .min(pool_position.amount);
+        let book_value = ((pool_position.value_usd as u128 * collateral_amount as u128)
+            / pool_position.amount as u128) as u64;
+        let normalized_amount = protocol_state.normalize_repayment(position.normalized_debt, redeemed)?;
+
+        // Update the redeemed position
+        let position_entry = position
+            .find_position_mut(&pool_key)
+            .ok_or(ErrorCode::InsufficientFunds)?;
+        position_entry.amount -= collateral_amount;
+        position_entry.value_usd -= book_value;
+        position.collateral_deposited = position.collateral_deposited.saturating_sub(collateral_amount);
+
+        let repaid = position.repay_debt(&pool_key, normalized_amount)?;
+        release_pool_debt(collateral_pool, &repaid, pool_accounts)?;
+        position.remove_empty_positions();
+        position.last_update_timestamp = now;
+
+        // Keep the position index sorted
+        let insert_prev = index_hint(&accounts[start + 1]);
+        let insert_next = index_hint(&accounts[start + 2]);
+        position_index.reposition(accounts, position_key, &mut position, insert_prev, insert_next)?;
+        save_program_account(position_info, &position)?;
+
+        total_collateral += collateral_amount;
+        total_normalized += normalized_amount;
+
+        let position_debt = protocol_state.debt_for(position.normalized_debt)?;
+        events.push(PositionRedeemed {
+            owner: position.owner,
+            position: position_key,
+            pool: pool_key,
+            redeemer: ctx.accounts.redeemer.key(),
+            debt_redeemed: redeemed,
+            collateral_redeemed: collateral_amount,
+            price,
+            position_collateral_value: position.total_collateral_value(),
+            position_debt,
+            position_ratio: position.book_ratio(position_debt),
+            system_collateral_ratio: 0,
+            timestamp: now,
+        });
+    }
+
+    // Burn stablecoins from redeemer
+    let cpi_accounts = Burn {
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        from: ctx.accounts.redeemer_stablecoin_account.to_account_info(),
+        authority: ctx.accounts.redeemer.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.stablecoin_token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    token_interface::burn(cpi_ctx, total_redeemed)?;
+
+    // Transfer collateral to redeemer
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.collateral_vault.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+        to: ctx.accounts.redeemer_collateral_account.to_account_info(),
+        authority: protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+    token_interface::transfer_checked(cpi_ctx, total_collateral, ctx.accounts.collateral_mint.decimals)?;
+
+    // Update collateral pool
+    collateral_pool.total_deposited = collateral_pool.total_deposited
+        .checked_sub(total_collateral)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Update protocol state
+    collateral_pool.sync_value(protocol_state)?;
+    protocol_state.total_normalized_debt = protocol_state.total_normalized_debt
+        .checked_sub(total_normalized)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
+        .saturating_sub(total_redeemed);
+    protocol_state.refresh_collateral_ratio(now);
+
+    for mut event in events {
+        event.system_collateral_ratio = protocol_state.collateral_ratio;
+        emit!(event);
+    }
+    emit!(StablecoinRedeemed {
+        redeemer: ctx.accounts.redeemer.key(),
+        pool: pool_key,
+        amount: total_redeemed,
+        collateral: total_collateral,
+        fee,
+        price,
+        redemption_base_rate: protocol_state.redemption_base_rate,
+        total_stablecoin_supply: protocol_state.total_stablecoin_supply,
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: now,
+    });
+
+    msg!(
+        "Redeemed {} stablecoins for {} collateral with a {} bps fee",
+        total_redeemed,
+        total_collateral,
+        fee
+    );
+    Ok(())
+}
+
+/// Key of an insert hint passed through `remaining_accounts`; the program id
+/// marks an end of the list.
+fn index_hint(info: &AccountInfo) -> Pubkey {
+    if info.key() == crate::ID {
+        Pubkey::default()
+    } else {
+        info.key()
+    }
+}
@@ -0,0 +1,326 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_option::COption;
+use anchor_spl::token_interface::{self, Mint, MintTo, TokenAccount, TokenInterface};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+
+/// Description and first attestation of a seed lot.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct SeedLotParams {
+    pub crop: String,
+    pub variety: String,
+    pub warehouse: String,
+    pub harvest_date: i64,
+    pub germination_rate: u64, // Basis points
+    pub quantity: u64, // Tokens to mint, in base units
+    pub attested_price: u64, // Per whole token, USD_DECIMALS
+    pub attestation_expiry: i64,
+}
+
+#[derive(Accounts)]
+pub struct CreateSeedLotRegistry<'info> {
+    #[account(
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = SeedLotRegistry::LEN,
+        seeds = [SEED_LOT_REGISTRY_SEED, mint.key().as_ref()],
+        bump
+    )]
+    pub seed_lot_registry: Account<'info, SeedLotRegistry>,
+
+    /// Tokenized seeds mint; its mint authority must already be the registry
+    #[account(
+        constraint = mint.mint_authority == COption::Some(seed_lot_registry.key()) @ ErrorCode::InvalidSeedLotMint,
+        constraint = mint.supply == 0 @ ErrorCode::InvalidSeedLotMint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetSeedLotAttestor<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [SEED_LOT_REGISTRY_SEED, seed_lot_registry.mint.as_ref()],
+        bump = seed_lot_registry.bump
+    )]
+    pub seed_lot_registry: Account<'info, SeedLotRegistry>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterSeedLot<'info> {
+    #[account(
+        mut,
+        constraint = seed_lot_registry.is_attestor(attestor.key) @ ErrorCode::InvalidAttestor
+    )]
+    pub attestor: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [SEED_LOT_REGISTRY_SEED, seed_lot_registry.mint.as_ref()],
+        bump = seed_lot_registry.bump
+    )]
+    pub seed_lot_registry: Account<'info, SeedLotRegistry>,
+
+    #[account(
+        init,
+        payer = attestor,
+        space = SeedLot::LEN,
+        seeds = [
+            SEED_LOT_SEED,
+            seed_lot_registry.key().as_ref(),
+            &seed_lot_registry.lot_count.to_le_bytes()
+        ],
+        bump
+    )]
+    pub seed_lot: Account<'info, SeedLot>,
+
+    #[account(
+        mut,
+        address = seed_lot_registry.mint
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = mint
+    )]
+    pub recipient_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AttestSeedLot<'info> {
+    #[account(
+        constraint = seed_lot_registry.is_attestor(attestor.key) @ ErrorCode::InvalidAttestor
+    )]
+    pub attestor: Signer<'info>,
+
+    #[account(
+        seeds = [SEED_LOT_REGISTRY_SEED, seed_lot_registry.mint.as_ref()],
+        bump = seed_lot_registry.bump
+    )]
+    pub seed_lot_registry: Account<'info, SeedLotRegistry>,
+
+    #[account(
+        mut,
+        constraint = seed_lot.registry == seed_lot_registry.key() @ ErrorCode::InvalidSeedLot
+    )]
+    pub seed_lot: Account<'info, SeedLot>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshSeedLotRegistry<'info> {
+    #[account(
+        mut,
+        seeds = [SEED_LOT_REGISTRY_SEED, seed_lot_registry.mint.as_ref()],
+        bump = seed_lot_registry.bump
+    )]
+    pub seed_lot_registry: Account<'info, SeedLotRegistry>,
+    // remaining_accounts: seed lots in id order from `next_lot`, or from 0
+    // when a new round starts (see `SeedLotRegistry::begin_round`)
+}
+
+pub fn create_seed_lot_registry_handler(ctx: Context<CreateSeedLotRegistry>) -> Result<()> {
+    let registry = &mut ctx.accounts.seed_lot_registry;
+    registry.mint = ctx.accounts.mint.key();
+    registry.attestors = [Pubkey::default(); MAX_SEED_LOT_ATTESTORS];
+    registry.attestor_count = 0;
+    registry.lot_count = 0;
+    registry.total_quantity = 0;
+    registry.price = 0;
+    registry.price_timestamp = 0;
+    registry.next_lot = 0;
+    registry.pending_value = 0;
+    registry.pending_quantity = 0;
+    registry.round_started = 0;
+    registry.bump = ctx.bumps.seed_lot_registry;
+
+    msg!("Created seed lot registry for mint {}", registry.mint);
+    Ok(())
+}
+
+pub fn set_seed_lot_attestor_handler(
+    ctx: Context<SetSeedLotAttestor>,
+    attestor: Pubkey,
+    allowed: bool,
+) -> Result<()> {
+    let registry = &mut ctx.accounts.seed_lot_registry;
+    if allowed {
+        registry.add_attestor(attestor)?;
+    } else {
+        registry.remove_attestor(&attestor)?;
+    }
+
+    emit!(SeedLotAttestorChanged {
+        registry: registry.key(),
+        attestor,
+        allowed,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!("Seed lot attestor {} allowed: {}", attestor, allowed);
+    Ok(())
+}
+
+/// Registers a lot under the next id and mints its tokens to the recipient.
+pub fn register_seed_lot_handler(ctx: Context<RegisterSeedLot>, params: SeedLotParams) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    SeedLot::validate_description(&params.crop, &params.variety, &params.warehouse)?;
+    require!(params.harvest_date <= now, ErrorCode::InvalidSeedLot);
+    require!(params.germination_rate <= BASIS_POINTS, ErrorCode::InvalidSeedLot);
+    require!(params.quantity > 0, ErrorCode::InvalidSeedLot);
+    SeedLot::validate_attestation(params.attested_price, params.attestation_expiry, now)?;
+
+    let registry = &mut ctx.accounts.seed_lot_registry;
+    let lot = &mut ctx.accounts.seed_lot;
+    lot.registry = registry.key();
+    lot.lot_id = registry.lot_count;
+    lot.attestor = ctx.accounts.attestor.key();
+    lot.crop = params.crop;
+    lot.variety = params.variety;
+    lot.warehouse = params.warehouse;
+    lot.harvest_date = params.harvest_date;
+    lot.germination_rate = params.germination_rate;
+    lot.quantity = params.quantity;
+    lot.attested_price = params.attested_price;
+    lot.attested_at = now;
+    lot.attestation_expiry = params.attestation_expiry;
+    lot.bump = ctx.bumps.seed_lot;
+
+    registry.lot_count += 1;
+    registry.total_quantity = registry.total_quantity
+        .checked_add(params.quantity)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Mint the lot's tokens, signed by the registry
+    let mint_key = registry.mint;
+    let seeds = &[
+        SEED_LOT_REGISTRY_SEED,
+        mint_key.as_ref(),
+        &[registry.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = MintTo {
+        mint: ctx.accounts.mint.to_account_info(),
+        to: ctx.accounts.recipient_token_account.to_account_info(),
+        authority: registry.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    token_interface::mint_to(cpi_ctx, params.quantity)?;
+
+    emit!(SeedLotRegistered {
+        registry: registry.key(),
+        lot: lot.key(),
+        lot_id: lot.lot_id,
+        attestor: lot.attestor,
+        recipient: ctx.accounts.recipient_token_account.key(),
+        quantity: lot.quantity,
+        attested_price: lot.attested_price,
+        attestation_expiry: lot.attestation_expiry,
+        timestamp: now,
+    });
+
+    msg!(
+        "Registered seed lot {} ({} {}): {} tokens at ${}",
+        lot.lot_id,
+        lot.crop,
+        lot.variety,
+        lot.quantity,
+        lot.attested_price
+    );
+    Ok(())
+}
+
+/// Re-prices a lot and extends its attestation.
+pub fn attest_seed_lot_handler(
+    ctx: Context<AttestSeedLot>,
+    attested_price: u64,
+    attestation_expiry: i64,
+) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    SeedLot::validate_attestation(attested_price, attestation_expiry, now)?;
+
+    let lot = &mut ctx.accounts.seed_lot;
+    let old_price = lot.attested_price;
+    lot.attestor = ctx.accounts.attestor.key();
+    lot.attested_price = attested_price;
+    lot.attested_at = now;
+    lot.attestation_expiry = attestation_expiry;
+
+    emit!(SeedLotAttested {
+        registry: lot.registry,
+        lot: lot.key(),
+        attestor: lot.attestor,
+        old_price,
+        new_price: attested_price,
+        attestation_expiry,
+        timestamp: now,
+    });
+
+    msg!("Attested seed lot {} at ${} until {}", lot.lot_id, attested_price, attestation_expiry);
+    Ok(())
+}
+
+/// Folds the passed lots into the current pricing round and publishes the
+/// registry price once every lot is in (permissionless).
+pub fn refresh_seed_lot_registry_handler(ctx: Context<RefreshSeedLotRegistry>) -> Result<()> {
+    let now = Clock::get()?.unix_timestamp;
+    let registry_key = ctx.accounts.seed_lot_registry.key();
+    let registry = &mut ctx.accounts.seed_lot_registry;
+
+    registry.begin_round(now);
+    for lot_info in ctx.remaining_accounts {
+        let lot: SeedLot = load_program_account(lot_info)?;
+        registry.fold_lot(&registry_key, &lot, now)?;
+    }
+
+    if registry.finish_round() {
+        emit!(SeedLotRegistryPriced {
+            registry: registry_key,
+            price: registry.price,
+            total_quantity: registry.total_quantity,
+            lot_count: registry.lot_count,
+            timestamp: now,
+        });
+        msg!("Seed lot registry priced at ${}", registry.price);
+    } else {
+        msg!("Seed lot registry round at lot {} of {}", registry.next_lot, registry.lot_count);
+    }
+    Ok(())
+}
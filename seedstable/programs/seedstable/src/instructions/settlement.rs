@@ -0,0 +1,494 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Burn, Mint, TokenInterface, TokenAccount, TransferChecked};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+use super::position_index::*;
+
+#[derive(Accounts)]
+pub struct TriggerSettlement<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.global_settlement @ ErrorCode::SettlementActive
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct FreezeSettlementPrice<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = protocol_state.global_settlement @ ErrorCode::SettlementNotActive
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        constraint = collateral_pool.settlement_price == 0 @ ErrorCode::SettlementPriceAlreadyFrozen
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SettlePosition<'info> {
+    pub caller: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = protocol_state.global_settlement @ ErrorCode::SettlementNotActive
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [USER_POSITION_SEED, user_position.owner.as_ref()],
+        bump = user_position.bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    pub index: PositionIndexHints<'info>,
+    // remaining_accounts, one group per sub-position in order:
+    // [collateral_pool, collateral_vault, collateral_mint,
+    //  owner_collateral_account, collateral_token_program]
+}
+
+#[derive(Accounts)]
+pub struct RedeemSettlement<'info> {
+    pub holder: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = protocol_state.global_settlement @ ErrorCode::SettlementNotActive
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        address = protocol_state.stablecoin_mint
+    )]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint,
+        token::authority = holder
+    )]
+    pub holder_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts, one group per collateral pool:
+    // [collateral_pool, collateral_vault, holder_collateral_account,
+    //  collateral_mint, collateral_token_program]
+}
+
+/// Shuts the protocol down for good: debt stops accruing and every
+/// instruction gated on the pause is closed. Only the settlement
+/// instructions below remain.
+pub fn trigger_settlement_handler(ctx: Context<TriggerSettlement>) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    let now = Clock::get()?.unix_timestamp;
+
+    // Book fees up to now, then freeze the rate index
+    protocol_state.accrue_stability_fee(now)?;
+    protocol_state.global_settlement = true;
+    // Prices keep updating until each pool's final price is frozen
+    let previous_operations = protocol_state.paused_operations;
+    protocol_state.paused_operations = PAUSE_ALL & !PAUSE_ORACLE_UPDATE;
+    protocol_state.settlement_timestamp = now;
+    protocol_state.settlement_debt = protocol_state.debt_for(protocol_state.total_normalized_debt)?;
+
+    emit!(PauseChanged {
+        previous_operations,
+        paused_operations: protocol_state.paused_operations,
+        changed_by: ctx.accounts.authority.key(),
+        timestamp: now,
+    });
+
+    msg!(
+        "Global settlement triggered with {} debt outstanding",
+        protocol_state.settlement_debt
+    );
+    Ok(())
+}
+
+/// Fixes the price positions in this pool are settled at.
+pub fn freeze_settlement_price_handler(ctx: Context<FreezeSettlementPrice>) -> Result<()> {
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    let protocol_state = &mut ctx.accounts.protocol_state;
+
+    collateral_pool.settlement_price = collateral_pool.current_price()?;
+    protocol_state.settlement_pools_frozen = protocol_state.settlement_pools_frozen
+        .checked_add(1)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    emit!(SettlementPriceFrozen {
+        pool: collateral_pool.key(),
+        mint: collateral_pool.mint,
+        settlement_price: collateral_pool.settlement_price,
+        pools_frozen: protocol_state.settlement_pools_frozen,
+        pool_count: protocol_state.collateral_pool_count,
+        settlement_debt: protocol_state.settlement_debt,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+
+    msg!(
+        "Settlement price for {} frozen at ${}",
+        collateral_pool.mint,
+        collateral_pool.settlement_price
+    );
+    Ok(())
+}
+
+/// Settles a whole position at the frozen prices: its total debt is netted
+/// against every sub-position, and only the collateral left once the whole
+/// debt is covered goes back to the owner. The rest stays behind for SEEDS
+/// holders. Anyone can call it.
+pub fn settle_position_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, SettlePosition<'info>>,
+) -> Result<()> {
+    let user_position = &mut ctx.accounts.user_position;
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    require!(!user_position.collateral_positions.is_empty(), ErrorCode::InsufficientFunds);
+
+    let accounts = ctx.remaining_accounts;
+    let positions = user_position.collateral_positions.clone();
+    require!(accounts.len() == positions.len() * 5, ErrorCode::MissingCollateralAccounts);
+
+    // Value every sub-position at its pool's frozen price
+    let mut pools = Vec::with_capacity(positions.len());
+    let mut shares = Vec::with_capacity(positions.len());
+    for (position, group) in positions.iter().zip(accounts.chunks(5)) {
+        require_keys_eq!(group[0].key(), position.pool, ErrorCode::MissingCollateralAccounts);
+        let collateral_pool: CollateralPool = load_program_account(&group[0])?;
+        require!(collateral_pool.settlement_price > 0, ErrorCode::SettlementPriceNotFrozen);
+
+        shares.push(SettlementShare {
+            amount: position.amount,
+            price: collateral_pool.settlement_price,
+            decimals: collateral_pool.decimals,
+            debt: protocol_state.debt_for(position.normalized_debt)?,
+        });
+        pools.push(collateral_pool);
+    }
+
+    // Net the whole debt before any collateral is released
+    let debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    let (kept, covered) = plan_settlement(&shares, debt)?;
+    let shortfall = debt - covered;
+
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let owner = user_position.owner;
+    let position_key = user_position.key();
+    let now = Clock::get()?.unix_timestamp;
+    for (((position, group), mut collateral_pool), owed) in positions
+        .iter()
+        .zip(accounts.chunks(5))
+        .zip(pools)
+        .zip(kept)
+    {
+        require_keys_eq!(group[1].key(), collateral_pool.vault, ErrorCode::MissingCollateralAccounts);
+        require_keys_eq!(group[2].key(), collateral_pool.mint, ErrorCode::MissingCollateralAccounts);
+        let collateral_mint = InterfaceAccount::<Mint>::try_from(&group[2])?;
+        let owner_collateral_account = InterfaceAccount::<TokenAccount>::try_from(&group[3])?;
+        require_keys_eq!(owner_collateral_account.mint, collateral_pool.mint, ErrorCode::MissingCollateralAccounts);
+        require_keys_eq!(owner_collateral_account.owner, owner, ErrorCode::Unauthorized);
+        let collateral_token_program = Interface::<TokenInterface>::try_from(&group[4])?;
+        require_keys_eq!(*group[1].owner, collateral_token_program.key(), ErrorCode::InvalidTokenProgram);
+
+        // Return what the whole debt did not need to the owner
+        let excess = position.amount - owed;
+        if excess > 0 {
+            let cpi_accounts = TransferChecked {
+                from: group[1].clone(),
+                mint: group[2].clone(),
+                to: group[3].clone(),
+                authority: protocol_state.to_account_info(),
+            };
+            let cpi_program = collateral_token_program.to_account_info();
+            let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+            token_interface::transfer_checked(cpi_ctx, excess, collateral_mint.decimals)?;
+        }
+
+        // Update collateral pool, keeping its value at market
+        collateral_pool.total_deposited = collateral_pool.total_deposited
+            .checked_sub(position.amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        collateral_pool.sync_value(protocol_state)?;
+        collateral_pool.total_normalized_debt = collateral_pool.total_normalized_debt
+            .saturating_sub(position.normalized_debt);
+        collateral_pool.settlement_collateral = collateral_pool.settlement_collateral
+            .checked_add(owed)
+            .ok_or(ErrorCode::MathOverflow)?;
+        save_program_account(&group[0], &collateral_pool)?;
+
+        emit!(PositionPoolSettled {
+            owner,
+            position: position_key,
+            pool: position.pool,
+            settlement_price: collateral_pool.settlement_price,
+            collateral: position.amount,
+            collateral_settled: owed,
+            excess,
+            settlement_collateral: collateral_pool.settlement_collateral,
+            timestamp: now,
+        });
+    }
+
+    // Clear every sub-position
+    let normalized_debt = user_position.normalized_debt;
+    let collateral = positions.iter().fold(0u64, |total, pos| total.saturating_add(pos.amount));
+    user_position.collateral_positions.clear();
+    user_position.normalized_debt = 0;
+    user_position.collateral_deposited = user_position.collateral_deposited.saturating_sub(collateral);
+    user_position.last_update_timestamp = now;
+
+    // Record settlement progress
+    protocol_state.total_normalized_debt = protocol_state.total_normalized_debt
+        .checked_sub(normalized_debt)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.settled_debt = protocol_state.settled_debt
+        .checked_add(debt)
+        .ok_or(ErrorCode::MathOverflow)?;
+    protocol_state.settlement_shortfall = protocol_state.settlement_shortfall
+        .checked_add(shortfall)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    // Positions with no debt left leave the index
+    ctx.accounts.index.reposition(position_key, user_position)?;
+
+    emit!(PositionSettled {
+        owner,
+        position: position_key,
+        caller: ctx.accounts.caller.key(),
+        debt,
+        covered,
+        shortfall,
+        settled_debt: protocol_state.settled_debt,
+        settlement_shortfall: protocol_state.settlement_shortfall,
+        timestamp: now,
+    });
+
+    msg!(
+        "Settled {} debt across {} pools, {} left uncovered",
+        debt,
+        positions.len(),
+        shortfall
+    );
+    Ok(())
+}
+
+/// Burns SEEDS for a pro-rata share of every pool's settled collateral.
+/// Opens once all pool prices are frozen and all position debt is settled.
+pub fn redeem_settlement_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, RedeemSettlement<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::InsufficientFunds);
+
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    require!(
+        protocol_state.settlement_pools_frozen == protocol_state.collateral_pool_count
+            && protocol_state.total_normalized_debt == 0,
+        ErrorCode::SettlementIncomplete
+    );
+
+    let accounts = ctx.remaining_accounts;
+    require!(
+        accounts.len() as u64 == protocol_state.collateral_pool_count * 5,
+        ErrorCode::MissingCollateralAccounts
+    );
+
+    // Share of the outstanding supply being redeemed
+    let supply = ctx.accounts.stablecoin_mint.supply;
+    require!(amount <= supply, ErrorCode::InsufficientFunds);
+
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let protocol_key = protocol_state.key();
+    let mut seen_pools: Vec<Pubkey> = Vec::with_capacity(accounts.len() / 5);
+    for group in accounts.chunks(5) {
+        let pool_info = &group[0];
+        let mut collateral_pool: CollateralPool = load_program_account(pool_info)?;
+        require_keys_eq!(collateral_pool.protocol_state, protocol_key, ErrorCode::MissingCollateralAccounts);
+        require_keys_eq!(group[1].key(), collateral_pool.vault, ErrorCode::MissingCollateralAccounts);
+        require_keys_eq!(group[3].key(), collateral_pool.mint, ErrorCode::MissingCollateralAccounts);
+        require!(!seen_pools.contains(&pool_info.key()), ErrorCode::MissingCollateralAccounts);
+        seen_pools.push(pool_info.key());
+
+        let share = ((collateral_pool.settlement_collateral as u128 * amount as u128)
+            / supply as u128) as u64;
+        if share == 0 {
+            continue;
+        }
+
+        let collateral_mint = InterfaceAccount::<Mint>::try_from(&group[3])?;
+        let collateral_token_program = Interface::<TokenInterface>::try_from(&group[4])?;
+        require_keys_eq!(*group[1].owner, collateral_token_program.key(), ErrorCode::InvalidTokenProgram);
+
+        let cpi_accounts = TransferChecked {
+            from: group[1].clone(),
+            mint: group[3].clone(),
+            to: group[2].clone(),
+            authority: protocol_state.to_account_info(),
+        };
+        let cpi_program = collateral_token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token_interface::transfer_checked(cpi_ctx, share, collateral_mint.decimals)?;
+
+        collateral_pool.settlement_collateral -= share;
+        save_program_account(pool_info, &collateral_pool)?;
+    }
+
+    // Burn stablecoins from holder
+    let cpi_accounts = Burn {
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        from: ctx.accounts.holder_stablecoin_account.to_account_info(),
+        authority: ctx.accounts.holder.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
+
+    token_interface::burn(cpi_ctx, amount)?;
+
+    protocol_state.total_stablecoin_supply = protocol_state.total_stablecoin_supply
+        .saturating_sub(amount);
+    protocol_state.settlement_redeemed = protocol_state.settlement_redeemed
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    msg!("Redeemed {} stablecoins for a settlement basket", amount);
+    Ok(())
+}
+
+/// A sub-position valued at its pool's frozen price.
+struct SettlementShare {
+    amount: u64,
+    price: u64,
+    decimals: u8,
+    debt: u64, // Debt attributed to the sub-position
+}
+
+/// Collateral kept back from each sub-position to cover `total_debt`, and
+/// the debt it covers. Each sub-position first covers the debt attributed
+/// to it; any debt left is then taken from the remaining collateral in
+/// order, so a short pool is made up from the others before any excess is
+/// released.
+fn plan_settlement(shares: &[SettlementShare], total_debt: u64) -> Result<(Vec<u64>, u64)> {
+    let mut kept = vec![0u64; shares.len()];
+    let mut remaining = total_debt;
+
+    for (share, kept) in shares.iter().zip(kept.iter_mut()) {
+        let (taken, covered) = cover_debt(share, share.amount, share.debt.min(remaining))?;
+        *kept = taken;
+        remaining -= covered;
+    }
+    for (share, kept) in shares.iter().zip(kept.iter_mut()) {
+        if remaining == 0 {
+            break;
+        }
+        let (taken, covered) = cover_debt(share, share.amount - *kept, remaining)?;
+        *kept += taken;
+        remaining -= covered;
+    }
+
+    Ok((kept, total_debt - remaining))
+}
+
+/// Collateral out of `available` needed to cover `debt`, rounded up so no
+/// fraction of a token is left uncovered, and the debt it covers.
+fn cover_debt(share: &SettlementShare, available: u64, debt: u64) -> Result<(u64, u64)> {
+    if debt == 0 || available == 0 {
+        return Ok((0, 0));
+    }
+    let mut tokens = calculate_token_amount(debt, share.price, share.decimals)?;
+    if calculate_usd_value(tokens, share.price, share.decimals)? < debt {
+        tokens = tokens.saturating_add(1);
+    }
+    let tokens = tokens.min(available);
+    let covered = calculate_usd_value(tokens, share.price, share.decimals)?.min(debt);
+    Ok((tokens, covered))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn share(amount: u64, price: u64, debt: u64) -> SettlementShare {
+        SettlementShare { amount, price, decimals: 6, debt }
+    }
+
+    #[test]
+    fn excess_in_one_pool_covers_a_short_pool() {
+        // All debt is booked against the small pool
+        let shares = [share(1_000_000_000, 1_000_000, 0), share(100_000_000, 1_000_000, 500_000_000)];
+        let (kept, covered) = plan_settlement(&shares, 500_000_000).unwrap();
+
+        assert_eq!(kept, vec![400_000_000, 100_000_000]);
+        assert_eq!(covered, 500_000_000);
+    }
+
+    #[test]
+    fn shortfall_only_once_every_pool_is_used() {
+        let shares = [share(100_000_000, 1_000_000, 0), share(100_000_000, 1_000_000, 500_000_000)];
+        let (kept, covered) = plan_settlement(&shares, 500_000_000).unwrap();
+
+        assert_eq!(kept, vec![100_000_000, 100_000_000]);
+        assert_eq!(covered, 200_000_000);
+    }
+
+    #[test]
+    fn attributed_debt_is_covered_by_its_own_pool_first() {
+        let shares = [share(1_000_000_000, 1_000_000, 200_000_000), share(1_000_000_000, 2_000_000, 300_000_000)];
+        let (kept, covered) = plan_settlement(&shares, 500_000_000).unwrap();
+
+        assert_eq!(kept, vec![200_000_000, 150_000_000]);
+        assert_eq!(covered, 500_000_000);
+    }
+
+    #[test]
+    fn collateral_rounds_up_to_cover_the_debt() {
+        // One whole token is worth $3, the debt is $10
+        let shares = [SettlementShare { amount: 10, price: 3_000_000, decimals: 0, debt: 10_000_000 }];
+        let (kept, covered) = plan_settlement(&shares, 10_000_000).unwrap();
+
+        assert_eq!(kept, vec![4]);
+        assert_eq!(covered, 10_000_000);
+    }
+}
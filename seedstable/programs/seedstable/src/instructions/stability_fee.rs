@@ -0,0 +1,99 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, MintTo};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+
+#[derive(Accounts)]
+pub struct SetStabilityFee<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct CollectStabilityFees<'info> {
+    #[account(
+        mut,
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.global_settlement @ ErrorCode::SettlementActive
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        address = protocol_state.stablecoin_mint
+    )]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = protocol_state.treasury
+    )]
+    pub treasury: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn set_stability_fee_handler(ctx: Context<SetStabilityFee>, stability_fee: u64) -> Result<()> {
+    require!(stability_fee <= MAX_STABILITY_FEE, ErrorCode::InvalidStabilityFee);
+
+    let protocol_state = &mut ctx.accounts.protocol_state;
+
+    // Debt accrued so far is charged at the old rate
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+    let old_fee = protocol_state.stability_fee;
+    protocol_state.stability_fee = stability_fee;
+
+    emit_parameter_changes(None, &[(Parameter::StabilityFee, old_fee, stability_fee)])?;
+
+    msg!("Stability fee set to {} bps per year", stability_fee);
+    Ok(())
+}
+
+pub fn collect_stability_fees_handler(ctx: Context<CollectStabilityFees>) -> Result<()> {
+    let protocol_state = &mut ctx.accounts.protocol_state;
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+
+    let fees = protocol_state.accrued_fees;
+    if fees == 0 {
+        msg!("No stability fees to collect");
+        return Ok(());
+    }
+
+    // Mint accrued fees to the treasury
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = MintTo {
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        to: ctx.accounts.treasury.to_account_info(),
+        authority: protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+    token_interface::mint_to(cpi_ctx, fees)?;
+
+    // The fees joined the supply as they accrued
+    protocol_state.accrued_fees = 0;
+
+    msg!("Collected {} stablecoins of stability fees to the treasury", fees);
+    Ok(())
+}
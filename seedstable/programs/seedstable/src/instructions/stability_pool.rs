@@ -0,0 +1,394 @@
+use anchor_lang::prelude::*;
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, TransferChecked};
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use super::authority::*;
+use crate::stablecoin::transfer_checked_with_hook;
+
+#[derive(Accounts)]
+pub struct InitializeStabilityPool<'info> {
+    #[account(
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = StabilityPool::LEN,
+        seeds = [STABILITY_POOL_SEED],
+        bump
+    )]
+    pub stability_pool: Box<Account<'info, StabilityPool>>,
+
+    #[account(address = protocol_state.stablecoin_mint)]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = stablecoin_mint,
+        token::authority = protocol_state,
+        seeds = [STABILITY_VAULT_SEED],
+        bump
+    )]
+    pub seeds_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct AddStabilityPoolCollateral<'info> {
+    #[account(
+        mut,
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_POOL_SEED],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Box<Account<'info, StabilityPool>>,
+
+    #[account(
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = authority,
+        token::mint = collateral_mint,
+        token::authority = protocol_state,
+        seeds = [STABILITY_COLLATERAL_SEED, collateral_pool.key().as_ref()],
+        bump
+    )]
+    pub stability_collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct DepositToStabilityPool<'info> {
+    #[account(mut)]
+    pub depositor: Signer<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump,
+        constraint = !protocol_state.is_paused(PAUSE_DEPOSIT) @ ErrorCode::ProtocolPaused
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_POOL_SEED],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Box<Account<'info, StabilityPool>>,
+
+    #[account(
+        init_if_needed,
+        payer = depositor,
+        space = StabilityDeposit::LEN,
+        seeds = [STABILITY_DEPOSIT_SEED, depositor.key().as_ref()],
+        bump
+    )]
+    pub stability_deposit: Box<Account<'info, StabilityDeposit>>,
+
+    #[account(address = protocol_state.stablecoin_mint)]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = stability_pool.seeds_vault
+    )]
+    pub seeds_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint,
+        token::authority = depositor
+    )]
+    pub depositor_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct WithdrawFromStabilityPool<'info> {
+    pub depositor: Signer<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_POOL_SEED],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Box<Account<'info, StabilityPool>>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_DEPOSIT_SEED, depositor.key().as_ref()],
+        bump = stability_deposit.bump
+    )]
+    pub stability_deposit: Box<Account<'info, StabilityDeposit>>,
+
+    #[account(address = protocol_state.stablecoin_mint)]
+    pub stablecoin_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        address = stability_pool.seeds_vault
+    )]
+    pub seeds_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = protocol_state.stablecoin_mint,
+        token::authority = depositor
+    )]
+    pub depositor_stablecoin_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStabilityPoolGain<'info> {
+    pub depositor: Signer<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        seeds = [STABILITY_POOL_SEED],
+        bump = stability_pool.bump
+    )]
+    pub stability_pool: Box<Account<'info, StabilityPool>>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_DEPOSIT_SEED, depositor.key().as_ref()],
+        bump = stability_deposit.bump
+    )]
+    pub stability_deposit: Box<Account<'info, StabilityDeposit>>,
+
+    pub collateral_pool: Account<'info, CollateralPool>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [STABILITY_COLLATERAL_SEED, collateral_pool.key().as_ref()],
+        bump
+    )]
+    pub stability_collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = collateral_pool.mint,
+        token::authority = depositor
+    )]
+    pub depositor_collateral_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+pub fn initialize_stability_pool_handler(ctx: Context<InitializeStabilityPool>) -> Result<()> {
+    let stability_pool = &mut ctx.accounts.stability_pool;
+
+    stability_pool.protocol_state = ctx.accounts.protocol_state.key();
+    stability_pool.seeds_vault = ctx.accounts.seeds_vault.key();
+    stability_pool.total_deposits = 0;
+    stability_pool.product = PRODUCT_PRECISION;
+    stability_pool.scale = 0;
+    stability_pool.collaterals = Vec::new();
+    stability_pool.bump = ctx.bumps.stability_pool;
+
+    msg!("Stability pool initialized");
+    Ok(())
+}
+
+pub fn add_stability_pool_collateral_handler(ctx: Context<AddStabilityPoolCollateral>) -> Result<()> {
+    let stability_pool = &mut ctx.accounts.stability_pool;
+    let collateral_pool = &ctx.accounts.collateral_pool;
+
+    require!(
+        stability_pool.find_collateral(&collateral_pool.key()).is_none(),
+        ErrorCode::InvalidCollateralType
+    );
+    require!(
+        stability_pool.collaterals.len() < MAX_STABILITY_POOL_COLLATERALS,
+        ErrorCode::TooManyStabilityPoolCollaterals
+    );
+
+    stability_pool.collaterals.push(StabilityPoolCollateral {
+        collateral_pool: collateral_pool.key(),
+        vault: ctx.accounts.stability_collateral_vault.key(),
+        sums: [0; STABILITY_SUM_HISTORY],
+    });
+
+    msg!("Stability pool now absorbs {:?} liquidations", collateral_pool.collateral_type);
+    Ok(())
+}
+
+pub fn deposit_to_stability_pool_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, DepositToStabilityPool<'info>>,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, ErrorCode::InsufficientFunds);
+
+    let stability_pool = &mut ctx.accounts.stability_pool;
+    let stability_deposit = &mut ctx.accounts.stability_deposit;
+
+    // Initialize the deposit if needed
+    if stability_deposit.owner == Pubkey::default() {
+        stability_deposit.owner = ctx.accounts.depositor.key();
+        stability_deposit.deposit = 0;
+        stability_deposit.gains = Vec::new();
+        stability_deposit.bump = ctx.bumps.stability_deposit;
+    }
+
+    // Realize gains and losses before changing the deposit
+    stability_deposit.sync(stability_pool)?;
+
+    // Transfer hook accounts, if any, come in through remaining_accounts
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.depositor_stablecoin_account.to_account_info(),
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        to: ctx.accounts.seeds_vault.to_account_info(),
+        authority: ctx.accounts.depositor.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts)
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+
+    transfer_checked_with_hook(cpi_ctx, amount, ctx.accounts.stablecoin_mint.decimals)?;
+
+    stability_deposit.deposit = stability_deposit.deposit
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    stability_pool.total_deposits = stability_pool.total_deposits
+        .checked_add(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    msg!("Deposited {} stablecoins into the stability pool", amount);
+    Ok(())
+}
+
+pub fn withdraw_from_stability_pool_handler<'info>(
+    ctx: Context<'_, '_, 'info, 'info, WithdrawFromStabilityPool<'info>>,
+    amount: u64,
+) -> Result<()> {
+    let stability_pool = &mut ctx.accounts.stability_pool;
+    let stability_deposit = &mut ctx.accounts.stability_deposit;
+    let protocol_state = &ctx.accounts.protocol_state;
+
+    stability_deposit.sync(stability_pool)?;
+    require!(
+        stability_deposit.deposit >= amount,
+        ErrorCode::InsufficientFunds
+    );
+
+    // Rounding in P can leave the pool a few units short of its depositors
+    let amount = amount.min(ctx.accounts.seeds_vault.amount);
+
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    // Transfer hook accounts, if any, come in through remaining_accounts
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.seeds_vault.to_account_info(),
+        mint: ctx.accounts.stablecoin_mint.to_account_info(),
+        to: ctx.accounts.depositor_stablecoin_account.to_account_info(),
+        authority: protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer)
+        .with_remaining_accounts(ctx.remaining_accounts.to_vec());
+
+    transfer_checked_with_hook(cpi_ctx, amount, ctx.accounts.stablecoin_mint.decimals)?;
+
+    stability_deposit.deposit -= amount;
+    stability_pool.total_deposits = stability_pool.total_deposits.saturating_sub(amount);
+
+    msg!("Withdrew {} stablecoins from the stability pool", amount);
+    Ok(())
+}
+
+pub fn claim_stability_pool_gain_handler(ctx: Context<ClaimStabilityPoolGain>) -> Result<()> {
+    let stability_pool = &ctx.accounts.stability_pool;
+    let stability_deposit = &mut ctx.accounts.stability_deposit;
+    let protocol_state = &ctx.accounts.protocol_state;
+
+    let index = stability_pool
+        .find_collateral(&ctx.accounts.collateral_pool.key())
+        .ok_or(ErrorCode::StabilityPoolCollateralNotSupported)?;
+
+    stability_deposit.sync(stability_pool)?;
+    let gain = stability_deposit.take_gain(index, ctx.accounts.stability_collateral_vault.amount);
+
+    if gain > 0 {
+        let seeds = &[
+            PROTOCOL_SEED,
+            &[protocol_state.bump],
+        ];
+        let signer = &[&seeds[..]];
+
+        let cpi_accounts = TransferChecked {
+            from: ctx.accounts.stability_collateral_vault.to_account_info(),
+            mint: ctx.accounts.collateral_mint.to_account_info(),
+            to: ctx.accounts.depositor_collateral_account.to_account_info(),
+            authority: protocol_state.to_account_info(),
+        };
+        let cpi_program = ctx.accounts.token_program.to_account_info();
+        let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+
+        token_interface::transfer_checked(cpi_ctx, gain, ctx.accounts.collateral_mint.decimals)?;
+    }
+
+    msg!("Claimed {} collateral from the stability pool", gain);
+    Ok(())
+}
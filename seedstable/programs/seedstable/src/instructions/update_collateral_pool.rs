@@ -0,0 +1,241 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::*;
+use super::authority::*;
+
+#[derive(Accounts)]
+pub struct UpdateCollateralPoolParams<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+#[derive(Accounts)]
+pub struct SetLiquidationMode<'info> {
+    #[account(
+        constraint = signers.approve(&protocol_state.authority, &authority) @ ErrorCode::Unauthorized
+    )]
+    pub authority: Signer<'info>,
+
+    pub signers: AuthoritySigners<'info>,
+
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(
+        mut,
+        seeds = [COLLATERAL_POOL_SEED, collateral_pool.mint.as_ref()],
+        bump = collateral_pool.bump,
+        has_one = protocol_state
+    )]
+    pub collateral_pool: Account<'info, CollateralPool>,
+}
+
+pub fn handler(
+    ctx: Context<UpdateCollateralPoolParams>,
+    max_ltv: u64,
+    liquidation_threshold: u64,
+    liquidation_bonus: u64,
+    debt_ceiling: u64,
+) -> Result<()> {
+    CollateralPool::validate_risk_params(max_ltv, liquidation_threshold, liquidation_bonus)?;
+
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    // The sunset winds down from the threshold it started at
+    require!(!collateral_pool.is_sunsetting(), ErrorCode::CollateralPoolSunsetting);
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[
+            (Parameter::MaxLtv, collateral_pool.max_ltv, max_ltv),
+            (Parameter::LiquidationThreshold, collateral_pool.liquidation_threshold, liquidation_threshold),
+            (Parameter::LiquidationBonus, collateral_pool.liquidation_bonus, liquidation_bonus),
+            (Parameter::DebtCeiling, collateral_pool.debt_ceiling, debt_ceiling),
+        ],
+    )?;
+    collateral_pool.max_ltv = max_ltv;
+    collateral_pool.liquidation_threshold = liquidation_threshold;
+    collateral_pool.liquidation_bonus = liquidation_bonus;
+    collateral_pool.debt_ceiling = debt_ceiling;
+
+    msg!(
+        "Updated {:?} pool: max LTV {} bps, liquidation threshold {} bps, bonus {} bps, debt ceiling {}",
+        collateral_pool.collateral_type,
+        max_ltv,
+        liquidation_threshold,
+        liquidation_bonus,
+        debt_ceiling
+    );
+    Ok(())
+}
+
+pub fn set_liquidation_mode_handler(
+    ctx: Context<SetLiquidationMode>,
+    liquidation_mode: LiquidationMode,
+    auction_start_premium: u64,
+    auction_floor: u64,
+    auction_duration: i64,
+) -> Result<()> {
+    CollateralPool::validate_auction_params(auction_start_premium, auction_floor, auction_duration)?;
+
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[
+            (Parameter::LiquidationMode, collateral_pool.liquidation_mode as u64, liquidation_mode as u64),
+            (Parameter::AuctionStartPremium, collateral_pool.auction_start_premium, auction_start_premium),
+            (Parameter::AuctionFloor, collateral_pool.auction_floor, auction_floor),
+            (Parameter::AuctionDuration, collateral_pool.auction_duration as u64, auction_duration as u64),
+        ],
+    )?;
+    collateral_pool.liquidation_mode = liquidation_mode;
+    collateral_pool.auction_start_premium = auction_start_premium;
+    collateral_pool.auction_floor = auction_floor;
+    collateral_pool.auction_duration = auction_duration;
+
+    msg!(
+        "Set {:?} pool liquidation mode to {:?}: auction from {} bps to {} bps over {}s",
+        collateral_pool.collateral_type,
+        liquidation_mode,
+        auction_start_premium,
+        auction_floor,
+        auction_duration
+    );
+    Ok(())
+}
+
+/// Resizes the pool's deposit cap and debt ceiling. Caps below the current
+/// totals only block new deposits and mints.
+pub fn set_pool_caps_handler(
+    ctx: Context<UpdateCollateralPoolParams>,
+    max_deposit_amount: u64,
+    debt_ceiling: u64,
+) -> Result<()> {
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    CollateralPool::validate_caps(collateral_pool.collateral_type, max_deposit_amount, debt_ceiling)?;
+
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[
+            (Parameter::MaxDeposit, collateral_pool.max_deposit_amount, max_deposit_amount),
+            (Parameter::DebtCeiling, collateral_pool.debt_ceiling, debt_ceiling),
+        ],
+    )?;
+    collateral_pool.max_deposit_amount = max_deposit_amount;
+    collateral_pool.debt_ceiling = debt_ceiling;
+
+    msg!(
+        "Set {:?} pool caps: max deposit {}, debt ceiling {}",
+        collateral_pool.collateral_type,
+        max_deposit_amount,
+        debt_ceiling
+    );
+    Ok(())
+}
+
+/// Stops or resumes new deposits into and mints against the pool. Withdrawals,
+/// repayments and liquidations are unaffected.
+pub fn set_pool_frozen_handler(ctx: Context<UpdateCollateralPoolParams>, frozen: bool) -> Result<()> {
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[(Parameter::PoolFrozen, !collateral_pool.is_active as u64, frozen as u64)],
+    )?;
+    collateral_pool.is_active = !frozen;
+
+    msg!("{:?} pool frozen: {}", collateral_pool.collateral_type, frozen);
+    Ok(())
+}
+
+/// Freezes the pool and lowers its liquidation threshold linearly to
+/// `sunset_threshold` over `sunset_duration` seconds, pushing positions out
+/// of a deprecated collateral.
+pub fn start_pool_sunset_handler(
+    ctx: Context<UpdateCollateralPoolParams>,
+    sunset_threshold: u64,
+    sunset_duration: i64,
+) -> Result<()> {
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    require!(!collateral_pool.is_sunsetting(), ErrorCode::CollateralPoolSunsetting);
+    require!(
+        sunset_threshold > 0 && sunset_threshold < collateral_pool.liquidation_threshold,
+        ErrorCode::InvalidSunsetParameters
+    );
+    require!(
+        (MIN_SUNSET_DURATION..=MAX_SUNSET_DURATION).contains(&sunset_duration),
+        ErrorCode::InvalidSunsetParameters
+    );
+
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[
+            (Parameter::PoolFrozen, !collateral_pool.is_active as u64, 1),
+            (Parameter::SunsetThreshold, collateral_pool.liquidation_threshold, sunset_threshold),
+            (Parameter::SunsetDuration, 0, sunset_duration as u64),
+        ],
+    )?;
+    collateral_pool.is_active = false;
+    collateral_pool.sunset_start = Clock::get()?.unix_timestamp;
+    collateral_pool.sunset_duration = sunset_duration;
+    collateral_pool.sunset_threshold = sunset_threshold;
+
+    msg!(
+        "Sunsetting {:?} pool: liquidation threshold {} bps to {} bps over {}s",
+        collateral_pool.collateral_type,
+        collateral_pool.liquidation_threshold,
+        sunset_threshold,
+        sunset_duration
+    );
+    Ok(())
+}
+
+/// Ends a sunset, restoring the pool's original liquidation threshold. The
+/// pool stays frozen until `set_pool_frozen` reopens it.
+pub fn cancel_pool_sunset_handler(ctx: Context<UpdateCollateralPoolParams>) -> Result<()> {
+    let collateral_pool = &mut ctx.accounts.collateral_pool;
+    require!(collateral_pool.is_sunsetting(), ErrorCode::CollateralPoolNotSunsetting);
+
+    let now = Clock::get()?.unix_timestamp;
+    emit_parameter_changes(
+        Some(collateral_pool.key()),
+        &[
+            (
+                Parameter::SunsetThreshold,
+                collateral_pool.liquidation_threshold_at(now),
+                collateral_pool.liquidation_threshold,
+            ),
+            (Parameter::SunsetDuration, collateral_pool.sunset_duration as u64, 0),
+        ],
+    )?;
+    collateral_pool.sunset_start = 0;
+    collateral_pool.sunset_duration = 0;
+    collateral_pool.sunset_threshold = 0;
+
+    msg!(
+        "Cancelled {:?} pool sunset, liquidation threshold back to {} bps",
+        collateral_pool.collateral_type,
+        collateral_pool.liquidation_threshold
+    );
+    Ok(())
+}
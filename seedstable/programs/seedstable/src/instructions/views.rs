@@ -0,0 +1,160 @@
+use anchor_lang::prelude::*;
+use crate::state::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+
+#[derive(Accounts)]
+pub struct GetPositionHealth<'info> {
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+
+    #[account(has_one = protocol_state)]
+    pub user_position: Account<'info, UserPosition>,
+    // remaining_accounts: [collateral_pool, price_history] per sub-position
+}
+
+#[derive(Accounts)]
+pub struct GetProtocolStats<'info> {
+    #[account(
+        seeds = [PROTOCOL_SEED],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+/// One collateral of a position as seen by `get_position_health`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PoolHealth {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub price: u64, // Spot price
+    pub value: u64, // At the spot price
+    pub liquidation_price: u64, // Spot price at which the position becomes liquidatable, others unchanged; 0 if none
+    pub max_withdrawable: u64, // Collateral that can be withdrawn now
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct PositionHealth {
+    pub collateral_value: u64, // At spot prices
+    pub debt: u64, // Including the stability fee accrued up to now
+    pub health_factor: u64, // Liquidation limit over debt in basis points, u64::MAX without debt
+    pub collateral_ratio: u64, // Basis points, u64::MAX without debt
+    pub liquidatable: bool,
+    pub max_mintable: u64, // SEEDS mintable now, before any pool debt ceiling
+    pub pools: Vec<PoolHealth>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct ProtocolStats {
+    pub total_collateral_value: u64,
+    pub total_stablecoin_supply: u64,
+    pub total_debt: u64, // Position debt including the stability fee accrued up to now
+    pub collateral_ratio: u64, // Basis points
+    pub min_collateral_ratio: u64,
+    pub recovery_mode: bool,
+    pub paused_operations: u16,
+    pub global_settlement: bool,
+}
+
+/// Health of a position at current prices, mirroring the checks `liquidate`
+/// (spot prices) and `mint_stable`/`withdraw_collateral` (conservative
+/// prices) apply. Read-only; fees are accrued on a copy of the protocol state.
+pub fn get_position_health_handler(ctx: Context<GetPositionHealth>) -> Result<PositionHealth> {
+    let now = Clock::get()?.unix_timestamp;
+    let mut protocol_state = ProtocolState::clone(&ctx.accounts.protocol_state);
+    protocol_state.accrue_stability_fee(now)?;
+
+    let user_position = &ctx.accounts.user_position;
+    let debt = protocol_state.debt_for(user_position.normalized_debt)?;
+
+    let spot = user_position.pool_valuations(ctx.remaining_accounts, Valuation::Spot)?;
+    let conservative = user_position.pool_valuations(ctx.remaining_accounts, Valuation::Conservative)?;
+    let spot_total = sum_valuations(&spot)?;
+    let conservative_total = sum_valuations(&conservative)?;
+
+    // Borrowing room left under every collateral's max LTV, and under the
+    // system minimum while in recovery mode
+    let mut room = conservative_total.borrow_limit.saturating_sub(debt);
+    if protocol_state.recovery_mode {
+        let recovery_limit = (conservative_total.total_value as u128 * BASIS_POINTS as u128
+            / protocol_state.min_collateral_ratio.max(1) as u128) as u64;
+        room = room.min(recovery_limit.saturating_sub(debt));
+    }
+
+    let pools = spot
+        .iter()
+        .zip(conservative.iter())
+        .map(|(spot_pool, conservative_pool)| PoolHealth {
+            pool: spot_pool.pool,
+            amount: spot_pool.amount,
+            price: spot_pool.price,
+            value: spot_pool.valuation.total_value,
+            liquidation_price: liquidation_price(spot_pool, &spot_total, debt),
+            max_withdrawable: max_withdrawable(conservative_pool, room, debt, protocol_state.recovery_mode),
+        })
+        .collect();
+
+    Ok(PositionHealth {
+        collateral_value: spot_total.total_value,
+        debt,
+        health_factor: spot_total.health_factor(debt),
+        collateral_ratio: spot_total.collateral_ratio(debt),
+        liquidatable: debt > 0 && protocol_state.is_liquidatable(&spot_total, debt),
+        max_mintable: room,
+        pools,
+    })
+}
+
+pub fn get_protocol_stats_handler(ctx: Context<GetProtocolStats>) -> Result<ProtocolStats> {
+    let mut protocol_state = ProtocolState::clone(&ctx.accounts.protocol_state);
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+
+    Ok(ProtocolStats {
+        total_collateral_value: protocol_state.total_collateral_value,
+        total_stablecoin_supply: protocol_state.total_stablecoin_supply,
+        total_debt: protocol_state.debt_for(protocol_state.total_normalized_debt)?,
+        collateral_ratio: protocol_state.collateral_ratio,
+        min_collateral_ratio: protocol_state.min_collateral_ratio,
+        recovery_mode: protocol_state.recovery_mode,
+        paused_operations: protocol_state.paused_operations,
+        global_settlement: protocol_state.global_settlement,
+    })
+}
+
+fn sum_valuations(pools: &[PoolValuation]) -> Result<CollateralValuation> {
+    pools
+        .iter()
+        .try_fold(CollateralValuation::default(), |total, pool| total.checked_add(&pool.valuation))
+        .ok_or(ErrorCode::MathOverflow.into())
+}
+
+/// Price of `pool` at which the position's liquidation limit falls to its
+/// debt, holding its other collateral at current prices.
+fn liquidation_price(pool: &PoolValuation, total: &CollateralValuation, debt: u64) -> u64 {
+    let pool_limit = pool.valuation.liquidation_limit;
+    let other_limit = total.liquidation_limit.saturating_sub(pool_limit);
+    if debt == 0 || pool_limit == 0 || other_limit >= debt {
+        return 0;
+    }
+    let price = pool.price as u128 * (debt - other_limit) as u128 / pool_limit as u128;
+    u64::try_from(price).unwrap_or(u64::MAX)
+}
+
+/// Collateral of `pool` whose borrowing power fits in `room`; none against
+/// debt in recovery mode, where `withdraw_collateral` refuses it.
+fn max_withdrawable(pool: &PoolValuation, room: u64, debt: u64, recovery_mode: bool) -> u64 {
+    if debt == 0 {
+        return pool.amount;
+    }
+    if recovery_mode {
+        return 0;
+    }
+    let pool_limit = pool.valuation.borrow_limit;
+    if pool_limit <= room {
+        return pool.amount;
+    }
+    (pool.amount as u128 * room as u128 / pool_limit as u128) as u64
+}
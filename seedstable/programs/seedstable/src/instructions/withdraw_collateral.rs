@@ -1,8 +1,10 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer};
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount, TransferChecked};
 use crate::state::*;
 use crate::constants::*;
 use crate::error::ErrorCode;
+use crate::events::*;
+use super::position_index::*;
 
 #[derive(Accounts)]
 pub struct WithdrawCollateral<'info> {
@@ -13,7 +15,7 @@ pub struct WithdrawCollateral<'info> {
         mut,
         seeds = [PROTOCOL_SEED],
         bump = protocol_state.bump,
-        constraint = !protocol_state.is_paused @ ErrorCode::ProtocolPaused
+        constraint = !protocol_state.is_paused(PAUSE_WITHDRAW) @ ErrorCode::ProtocolPaused
     )]
     pub protocol_state: Account<'info, ProtocolState>,
 
@@ -31,32 +33,84 @@ pub struct WithdrawCollateral<'info> {
     )]
     pub collateral_pool: Account<'info, CollateralPool>,
 
+    #[account(
+        seeds = [PRICE_HISTORY_SEED, collateral_pool.key().as_ref()],
+        bump = price_history.bump
+    )]
+    pub price_history: Box<Account<'info, PriceHistory>>,
+
     #[account(
         mut,
         address = collateral_pool.vault
     )]
-    pub collateral_vault: Account<'info, TokenAccount>,
+    pub collateral_vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(address = collateral_pool.mint)]
+    pub collateral_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
         token::mint = collateral_vault.mint,
         token::authority = user
     )]
-    pub user_collateral_account: Account<'info, TokenAccount>,
+    pub user_collateral_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub index: PositionIndexHints<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+    // remaining_accounts: [collateral_pool, price_history] per sub-position
 }
 
 pub fn handler(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
-    let user_position = &mut ctx.accounts.user_position;
-    let collateral_pool = &mut ctx.accounts.collateral_pool;
-    let protocol_state = &mut ctx.accounts.protocol_state;
+    let withdrawal_value = book_withdrawal(
+        &mut ctx.accounts.protocol_state,
+        &mut ctx.accounts.user_position,
+        &mut ctx.accounts.collateral_pool,
+        &ctx.accounts.price_history,
+        &mut ctx.accounts.index,
+        ctx.remaining_accounts,
+        amount,
+    )?;
 
-    // Find user's position in this collateral pool and validate amount
+    // Transfer collateral from vault to user
+    let seeds = &[
+        PROTOCOL_SEED,
+        &[ctx.accounts.protocol_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+
+    let cpi_accounts = TransferChecked {
+        from: ctx.accounts.collateral_vault.to_account_info(),
+        mint: ctx.accounts.collateral_mint.to_account_info(),
+        to: ctx.accounts.user_collateral_account.to_account_info(),
+        authority: ctx.accounts.protocol_state.to_account_info(),
+    };
+    let cpi_program = ctx.accounts.token_program.to_account_info();
+    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
+    
+    token_interface::transfer_checked(cpi_ctx, amount, ctx.accounts.collateral_mint.decimals)?;
+
+    msg!("Withdrew {} collateral tokens worth ${} USD", amount, withdrawal_value);
+    Ok(())
+}
+
+/// Debits a withdrawal of `amount` from the user's position, the pool and the
+/// protocol totals after checking the remaining collateral still covers the
+/// debt, re-sorts the position and emits `CollateralWithdrawn`. Returns the
+/// book value withdrawn; the caller moves the tokens.
+pub(crate) fn book_withdrawal<'info>(
+    protocol_state: &mut Account<'info, ProtocolState>,
+    user_position: &mut Account<'info, UserPosition>,
+    collateral_pool: &mut Account<'info, CollateralPool>,
+    price_history: &PriceHistory,
+    index: &mut PositionIndexHints<'info>,
+    pool_accounts: &[AccountInfo],
+    amount: u64,
+) -> Result<u64> {
+    // Find user's sub-position in this collateral pool and validate amount
     let pool_key = collateral_pool.key();
-    let (pool_amount, pool_value_usd) = user_position.collateral_pool
-        .as_ref()
-        .filter(|pos| pos.pool == pool_key)
+    let (pool_amount, pool_value_usd) = user_position
+        .find_position(&pool_key)
         .map(|pos| (pos.amount, pos.value_usd))
         .ok_or(ErrorCode::InsufficientFunds)?;
 
@@ -65,46 +119,41 @@ pub fn handler(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
         ErrorCode::InsufficientFunds
     );
 
-    // Calculate remaining collateral value after withdrawal
-    let withdrawal_value = (pool_value_usd * amount) / pool_amount;
-    let remaining_collateral_value = pool_value_usd.saturating_sub(withdrawal_value);
+    // Book value leaving the position
+    let withdrawal_value = ((pool_value_usd as u128 * amount as u128) / pool_amount as u128) as u64;
+
+    // Check the remaining collateral still covers the debt within its max
+    // LTV, valuing every sub-position at the lower of spot and TWAP
+    protocol_state.accrue_stability_fee(Clock::get()?.unix_timestamp)?;
+    let debt = protocol_state.debt_for(user_position.normalized_debt)?;
+    if debt > 0 {
+        // Any withdrawal against debt lowers the position's ratio
+        require!(!protocol_state.recovery_mode, ErrorCode::RecoveryModeRestricted);
+
+        let valuation = user_position
+            .collateral_value(pool_accounts, Valuation::Conservative)?;
+        let now = Clock::get()?.unix_timestamp;
+        let price = collateral_pool.valuation_price(price_history, Valuation::Conservative, now)?;
+        let withdrawn_collateral_value = calculate_usd_value(amount, price, collateral_pool.decimals)?;
+        let remaining = valuation.saturating_sub(&collateral_pool.valuation_of(withdrawn_collateral_value, now));
 
-    // Check if withdrawal would maintain minimum collateral ratio
-    let stablecoin_minted = user_position.stablecoin_minted;
-    if stablecoin_minted > 0 {
-        let new_health_factor = user_position.calculate_health_factor(remaining_collateral_value);
         require!(
-            new_health_factor >= protocol_state.min_collateral_ratio,
+            remaining.can_borrow(debt),
             ErrorCode::InsufficientCollateralRatio
         );
     }
 
-    // Transfer collateral from vault to user
-    let seeds = &[
-        PROTOCOL_SEED,
-        &[protocol_state.bump],
-    ];
-    let signer = &[&seeds[..]];
-
-    let cpi_accounts = Transfer {
-        from: ctx.accounts.collateral_vault.to_account_info(),
-        to: ctx.accounts.user_collateral_account.to_account_info(),
-        authority: protocol_state.to_account_info(),
-    };
-    let cpi_program = ctx.accounts.token_program.to_account_info();
-    let cpi_ctx = CpiContext::new_with_signer(cpi_program, cpi_accounts, signer);
-    
-    token::transfer(cpi_ctx, amount)?;
-
-    // Update pool position
-    if let Some(pool_position) = &mut user_position.collateral_pool {
-        pool_position.amount = pool_position.amount
-            .checked_sub(amount)
-            .ok_or(ErrorCode::MathOverflow)?;
-        pool_position.value_usd = pool_position.value_usd
-            .checked_sub(withdrawal_value)
-            .ok_or(ErrorCode::MathOverflow)?;
-    }
+    // Update pool sub-position
+    let pool_position = user_position
+        .find_position_mut(&pool_key)
+        .ok_or(ErrorCode::InsufficientFunds)?;
+    pool_position.amount = pool_position.amount
+        .checked_sub(amount)
+        .ok_or(ErrorCode::MathOverflow)?;
+    pool_position.value_usd = pool_position.value_usd
+        .checked_sub(withdrawal_value)
+        .ok_or(ErrorCode::MathOverflow)?;
+    user_position.remove_empty_positions();
 
     // Update user position
     user_position.collateral_deposited = user_position.collateral_deposited
@@ -116,16 +165,28 @@ pub fn handler(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
     collateral_pool.total_deposited = collateral_pool.total_deposited
         .checked_sub(amount)
         .ok_or(ErrorCode::MathOverflow)?;
-    collateral_pool.total_value_usd = collateral_pool.total_value_usd
-        .checked_sub(withdrawal_value)
-        .ok_or(ErrorCode::MathOverflow)?;
 
     // Update protocol state
-    protocol_state.total_collateral_value = protocol_state.total_collateral_value
-        .checked_sub(withdrawal_value)
-        .ok_or(ErrorCode::MathOverflow)?;
-    protocol_state.collateral_ratio = protocol_state.calculate_collateral_ratio();
-
-    msg!("Withdrew {} collateral tokens worth ${} USD", amount, withdrawal_value);
-    Ok(())
+    collateral_pool.sync_value(protocol_state)?;
+    protocol_state.refresh_collateral_ratio(Clock::get()?.unix_timestamp);
+
+    // Keep the position index sorted
+    let position_key = user_position.key();
+    index.reposition(position_key, user_position)?;
+
+    emit!(CollateralWithdrawn {
+        owner: user_position.owner,
+        position: position_key,
+        pool: pool_key,
+        amount,
+        value_usd: withdrawal_value,
+        price: collateral_pool.last_price,
+        position_collateral_value: user_position.total_collateral_value(),
+        position_debt: debt,
+        position_ratio: user_position.book_ratio(debt),
+        system_collateral_ratio: protocol_state.collateral_ratio,
+        timestamp: user_position.last_update_timestamp,
+    });
+
+    Ok(withdrawal_value)
 }
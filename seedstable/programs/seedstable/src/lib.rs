@@ -1,63 +1,580 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Mint, Token, TokenAccount};
+use anchor_lang::solana_program::program_option::COption;
+use anchor_lang::system_program;
+use anchor_lang::Discriminator;
+use anchor_spl::token_interface::{self, Mint, TokenInterface, TokenAccount};
+
+pub mod constants;
+pub mod error;
+pub mod events;
+pub mod instructions;
+pub mod oracle;
+pub mod stablecoin;
+pub mod state;
+
+use constants::*;
+use error::ErrorCode;
+use instructions::*;
+use state::*;
 
 declare_id!("HBktVPq8VSR42ugq3WoeHhqWqjSP8y2S4coVN7iRAAVD");
 
 #[program]
 pub mod seed_stable_mini {
     use super::*;
-    
-    /// Initialize program state
-    pub fn initialize(ctx: Context<Initialize>) -> Result<()> {
+
+    /// Initialize protocol state, stablecoin mint and treasury; with `metadata`
+    /// the mint is created under Token-2022 with on-mint metadata
+    pub fn initialize(ctx: Context<Initialize>, bump: u8, metadata: Option<StablecoinMetadata>) -> Result<()> {
+        instructions::initialize::handler(ctx, bump, metadata)
+    }
+
+    /// Pause the risk-increasing operations, or lift every pause (authority only)
+    pub fn emergency_pause(ctx: Context<EmergencyPause>, pause: bool) -> Result<()> {
+        instructions::emergency_pause::handler(ctx, pause)
+    }
+
+    /// Set exactly which operations are paused, as `PAUSE_*` flags (authority only)
+    pub fn set_paused_operations(ctx: Context<EmergencyPause>, operations: u16) -> Result<()> {
+        instructions::emergency_pause::set_paused_operations_handler(ctx, operations)
+    }
+
+    /// Pause more operations, without being able to unpause any (guardian only)
+    pub fn guardian_pause(ctx: Context<GuardianPause>, operations: u16) -> Result<()> {
+        instructions::emergency_pause::guardian_pause_handler(ctx, operations)
+    }
+
+    /// Set or remove the guardian (authority only)
+    pub fn set_guardian(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+        instructions::emergency_pause::set_guardian_handler(ctx, guardian)
+    }
+
+    /// Create an m-of-n multisig that can be made the protocol or PSM authority
+    pub fn create_multisig(ctx: Context<CreateMultisig>, owners: Vec<Pubkey>, threshold: u8) -> Result<()> {
+        instructions::authority::create_multisig_handler(ctx, owners, threshold)
+    }
+
+    /// Propose a new protocol authority, or cancel with the default key (authority only)
+    pub fn propose_authority(ctx: Context<ProposeAuthority>, new_authority: Pubkey) -> Result<()> {
+        instructions::authority::propose_authority_handler(ctx, new_authority)
+    }
+
+    /// Take over as protocol authority (proposed authority only)
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        instructions::authority::accept_authority_handler(ctx)
+    }
+
+    /// Create a collateral pool for a new collateral mint (authority only)
+    pub fn create_collateral_pool(
+        ctx: Context<CreateCollateralPool>,
+        collateral_type: CollateralType,
+        oracle_type: OracleType,
+        max_deposit: u64,
+        debt_ceiling: u64,
+    ) -> Result<()> {
+        instructions::oracle_update::create_collateral_pool_handler(
+            ctx,
+            collateral_type,
+            oracle_type,
+            max_deposit,
+            debt_ceiling,
+        )
+    }
+
+    /// Update a collateral pool's LTV, liquidation threshold, bonus and debt ceiling (authority only)
+    pub fn update_collateral_pool_params(
+        ctx: Context<UpdateCollateralPoolParams>,
+        max_ltv: u64,
+        liquidation_threshold: u64,
+        liquidation_bonus: u64,
+        debt_ceiling: u64,
+    ) -> Result<()> {
+        instructions::update_collateral_pool::handler(
+            ctx,
+            max_ltv,
+            liquidation_threshold,
+            liquidation_bonus,
+            debt_ceiling,
+        )
+    }
+
+    /// Resize a collateral pool's deposit cap and debt ceiling (authority only)
+    pub fn set_pool_caps(
+        ctx: Context<UpdateCollateralPoolParams>,
+        max_deposit_amount: u64,
+        debt_ceiling: u64,
+    ) -> Result<()> {
+        instructions::update_collateral_pool::set_pool_caps_handler(ctx, max_deposit_amount, debt_ceiling)
+    }
+
+    /// Freeze or reopen new deposits and mints for a collateral pool (authority only)
+    pub fn set_pool_frozen(ctx: Context<UpdateCollateralPoolParams>, frozen: bool) -> Result<()> {
+        instructions::update_collateral_pool::set_pool_frozen_handler(ctx, frozen)
+    }
+
+    /// Freeze a collateral pool and wind its liquidation threshold down over time (authority only)
+    pub fn start_pool_sunset(
+        ctx: Context<UpdateCollateralPoolParams>,
+        sunset_threshold: u64,
+        sunset_duration: i64,
+    ) -> Result<()> {
+        instructions::update_collateral_pool::start_pool_sunset_handler(ctx, sunset_threshold, sunset_duration)
+    }
+
+    /// Stop a collateral pool's sunset and restore its liquidation threshold (authority only)
+    pub fn cancel_pool_sunset(ctx: Context<UpdateCollateralPoolParams>) -> Result<()> {
+        instructions::update_collateral_pool::cancel_pool_sunset_handler(ctx)
+    }
+
+    /// Add a primary or fallback oracle source to a collateral pool (authority only)
+    pub fn add_oracle_source(
+        ctx: Context<AddOracleSource>,
+        oracle_type: OracleType,
+        max_staleness: i64,
+        max_deviation: u64,
+        is_fallback: bool,
+    ) -> Result<()> {
+        instructions::oracle_update::add_oracle_source_handler(
+            ctx,
+            oracle_type,
+            max_staleness,
+            max_deviation,
+            is_fallback,
+        )
+    }
+
+    /// Swap one of a collateral pool's oracle sources for a new feed (authority only)
+    pub fn replace_oracle_source(
+        ctx: Context<ReplaceOracleSource>,
+        oracle_type: OracleType,
+        max_staleness: i64,
+        max_deviation: u64,
+    ) -> Result<()> {
+        instructions::oracle_update::replace_oracle_source_handler(
+            ctx,
+            oracle_type,
+            max_staleness,
+            max_deviation,
+        )
+    }
+
+    /// Set how many primary oracle sources must agree (authority only)
+    pub fn set_oracle_quorum(ctx: Context<SetOracleQuorum>, min_quorum: u8) -> Result<()> {
+        instructions::oracle_update::set_oracle_quorum_handler(ctx, min_quorum)
+    }
+
+    /// Set the TWAP/EMA window of a collateral pool's price history (authority only)
+    pub fn set_twap_window(ctx: Context<SetTwapWindow>, twap_window: i64) -> Result<()> {
+        instructions::oracle_update::set_twap_window_handler(ctx, twap_window)
+    }
+
+    /// Choose fixed-discount or auction liquidations for a pool and set auction pricing (authority only)
+    pub fn set_liquidation_mode(
+        ctx: Context<SetLiquidationMode>,
+        liquidation_mode: LiquidationMode,
+        auction_start_premium: u64,
+        auction_floor: u64,
+        auction_duration: i64,
+    ) -> Result<()> {
+        instructions::update_collateral_pool::set_liquidation_mode_handler(
+            ctx,
+            liquidation_mode,
+            auction_start_premium,
+            auction_floor,
+            auction_duration,
+        )
+    }
+
+    /// Set the annualized stability fee charged on debt (authority only)
+    pub fn set_stability_fee(ctx: Context<SetStabilityFee>, stability_fee: u64) -> Result<()> {
+        instructions::stability_fee::set_stability_fee_handler(ctx, stability_fee)
+    }
+
+    /// Mint accrued stability fees to the treasury
+    pub fn collect_stability_fees(ctx: Context<CollectStabilityFees>) -> Result<()> {
+        instructions::stability_fee::collect_stability_fees_handler(ctx)
+    }
+
+    /// Deposit collateral into the user's position
+    pub fn deposit_collateral(
+        ctx: Context<DepositCollateral>,
+        collateral_type: CollateralType,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::deposit_collateral::handler(ctx, collateral_type, amount)
+    }
+
+    /// Withdraw collateral while keeping the minimum collateral ratio
+    pub fn withdraw_collateral(ctx: Context<WithdrawCollateral>, amount: u64) -> Result<()> {
+        instructions::withdraw_collateral::handler(ctx, amount)
+    }
+
+    /// Create the lot registry that issues and prices a tokenized seeds mint (authority only)
+    pub fn create_seed_lot_registry(ctx: Context<CreateSeedLotRegistry>) -> Result<()> {
+        instructions::seed_lot::create_seed_lot_registry_handler(ctx)
+    }
+
+    /// Allow or revoke a seed lot attestor (authority only)
+    pub fn set_seed_lot_attestor(ctx: Context<SetSeedLotAttestor>, attestor: Pubkey, allowed: bool) -> Result<()> {
+        instructions::seed_lot::set_seed_lot_attestor_handler(ctx, attestor, allowed)
+    }
+
+    /// Register a seed lot and mint its tokens (attestor only)
+    pub fn register_seed_lot(ctx: Context<RegisterSeedLot>, params: SeedLotParams) -> Result<()> {
+        instructions::seed_lot::register_seed_lot_handler(ctx, params)
+    }
+
+    /// Re-price a seed lot and extend its attestation (attestor only)
+    pub fn attest_seed_lot(
+        ctx: Context<AttestSeedLot>,
+        attested_price: u64,
+        attestation_expiry: i64,
+    ) -> Result<()> {
+        instructions::seed_lot::attest_seed_lot_handler(ctx, attested_price, attestation_expiry)
+    }
+
+    /// Fold seed lots into the registry's pricing round and publish its price
+    pub fn refresh_seed_lot_registry(ctx: Context<RefreshSeedLotRegistry>) -> Result<()> {
+        instructions::seed_lot::refresh_seed_lot_registry_handler(ctx)
+    }
+
+    /// Deposit lamports into a wrapped SOL pool, wrapping them in the vault
+    pub fn deposit_sol(ctx: Context<DepositSol>, amount: u64) -> Result<()> {
+        instructions::native_sol::deposit_sol_handler(ctx, amount)
+    }
+
+    /// Withdraw from a wrapped SOL pool straight to lamports
+    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
+        instructions::native_sol::withdraw_sol_handler(ctx, amount)
+    }
+
+    /// Mint SEEDS against deposited collateral
+    pub fn mint_stable(ctx: Context<MintStable>, amount: u64) -> Result<()> {
+        instructions::mint_stable::handler(ctx, amount)
+    }
+
+    /// Burn SEEDS to repay position debt
+    pub fn burn_stable(ctx: Context<BurnStable>, amount: u64) -> Result<()> {
+        instructions::burn_stable::handler(ctx, amount)
+    }
+
+    /// Burn stablecoins for $1 of collateral each, less a dynamic fee, from the riskiest positions
+    pub fn redeem(ctx: Context<Redeem>, amount: u64, max_fee: u64) -> Result<()> {
+        instructions::redeem::handler(ctx, amount, max_fee)
+    }
+
+    /// Create the position index that keeps positions sorted by collateral ratio (authority only)
+    pub fn initialize_position_index(ctx: Context<InitializePositionIndex>) -> Result<()> {
+        instructions::position_index::initialize_position_index_handler(ctx)
+    }
+
+    /// Move a position to its current place in the position index
+    pub fn reindex_position(ctx: Context<ReindexPosition>) -> Result<()> {
+        instructions::position_index::reindex_position_handler(ctx)
+    }
+
+    /// Liquidate an undercollateralized position, repaying up to the close factor
+    pub fn liquidate(ctx: Context<Liquidate>, debt_to_repay: u64) -> Result<()> {
+        instructions::liquidate::handler(ctx, debt_to_repay)
+    }
+
+    /// Put a position's collateral in an auction-mode pool up for a descending-price sale
+    pub fn start_auction(ctx: Context<StartAuction>) -> Result<()> {
+        instructions::auction::start_auction_handler(ctx)
+    }
+
+    /// Buy auctioned collateral with stablecoins at the current auction price
+    pub fn buy_auction_collateral(
+        ctx: Context<BuyAuctionCollateral>,
+        max_collateral: u64,
+        max_price: u64,
+    ) -> Result<()> {
+        instructions::auction::buy_auction_collateral_handler(ctx, max_collateral, max_price)
+    }
+
+    /// Close a settled or expired auction, returning unsold collateral
+    pub fn close_auction(ctx: Context<CloseAuction>) -> Result<()> {
+        instructions::auction::close_auction_handler(ctx)
+    }
+
+    /// Move collateral left in a pool's treasury by expired auctions (authority only)
+    pub fn sweep_collateral_treasury(ctx: Context<SweepCollateralTreasury>, amount: u64) -> Result<()> {
+        instructions::auction::sweep_collateral_treasury_handler(ctx, amount)
+    }
+
+    /// Create the stability pool and its SEEDS vault (authority only)
+    pub fn initialize_stability_pool(ctx: Context<InitializeStabilityPool>) -> Result<()> {
+        instructions::stability_pool::initialize_stability_pool_handler(ctx)
+    }
+
+    /// Let the stability pool absorb liquidations of a collateral pool (authority only)
+    pub fn add_stability_pool_collateral(ctx: Context<AddStabilityPoolCollateral>) -> Result<()> {
+        instructions::stability_pool::add_stability_pool_collateral_handler(ctx)
+    }
+
+    /// Deposit stablecoins into the stability pool
+    pub fn deposit_to_stability_pool<'info>(
+        ctx: Context<'_, '_, 'info, 'info, DepositToStabilityPool<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::stability_pool::deposit_to_stability_pool_handler(ctx, amount)
+    }
+
+    /// Withdraw remaining stablecoins from the stability pool
+    pub fn withdraw_from_stability_pool<'info>(
+        ctx: Context<'_, '_, 'info, 'info, WithdrawFromStabilityPool<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::stability_pool::withdraw_from_stability_pool_handler(ctx, amount)
+    }
+
+    /// Claim collateral earned by a stability pool deposit
+    pub fn claim_stability_pool_gain(ctx: Context<ClaimStabilityPoolGain>) -> Result<()> {
+        instructions::stability_pool::claim_stability_pool_gain_handler(ctx)
+    }
+
+    /// Set the share of a position's debt one liquidation may repay (authority only)
+    pub fn set_close_factor(ctx: Context<SetCloseFactor>, close_factor: u64) -> Result<()> {
+        instructions::liquidate::set_close_factor_handler(ctx, close_factor)
+    }
+
+    /// Mint stablecoins that a `flash_repay` later in the same transaction burns again, plus a fee
+    pub fn flash_mint(ctx: Context<FlashMint>, amount: u64) -> Result<()> {
+        instructions::flash_mint::flash_mint_handler(ctx, amount)
+    }
+
+    /// Burn a flash mint's principal and pay its fee to the treasury
+    pub fn flash_repay<'info>(
+        ctx: Context<'_, '_, 'info, 'info, FlashRepay<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::flash_mint::flash_repay_handler(ctx, amount)
+    }
+
+    /// Set the flash mint cap and fee (authority only)
+    pub fn set_flash_mint_params(ctx: Context<SetFlashMintParams>, cap: u64, fee: u64) -> Result<()> {
+        instructions::flash_mint::set_flash_mint_params_handler(ctx, cap, fee)
+    }
+
+    /// Shut the protocol down for good and start global settlement (authority only)
+    pub fn trigger_settlement(ctx: Context<TriggerSettlement>) -> Result<()> {
+        instructions::settlement::trigger_settlement_handler(ctx)
+    }
+
+    /// Freeze a collateral pool's final settlement price (authority only)
+    pub fn freeze_settlement_price(ctx: Context<FreezeSettlementPrice>) -> Result<()> {
+        instructions::settlement::freeze_settlement_price_handler(ctx)
+    }
+
+    /// Settle a position's whole debt at the frozen prices, returning excess collateral to the owner
+    pub fn settle_position<'info>(
+        ctx: Context<'_, '_, 'info, 'info, SettlePosition<'info>>,
+    ) -> Result<()> {
+        instructions::settlement::settle_position_handler(ctx)
+    }
+
+    /// Burn stablecoins for a pro-rata basket of settled collateral
+    pub fn redeem_settlement<'info>(
+        ctx: Context<'_, '_, 'info, 'info, RedeemSettlement<'info>>,
+        amount: u64,
+    ) -> Result<()> {
+        instructions::settlement::redeem_settlement_handler(ctx, amount)
+    }
+
+    /// Adjust treasury supply when SEEDS drifts off peg
+    pub fn rebalance(ctx: Context<Rebalance>) -> Result<()> {
+        instructions::rebalance::handler(ctx)
+    }
+
+    /// Refresh a collateral pool's valuation from its oracle set
+    pub fn update_oracle_price(ctx: Context<UpdateOraclePrice>) -> Result<()> {
+        instructions::oracle_update::update_oracle_price_handler(ctx)
+    }
+
+    /// Position collateral, debt, health, liquidation prices and headroom, as return data
+    pub fn get_position_health(ctx: Context<GetPositionHealth>) -> Result<PositionHealth> {
+        instructions::views::get_position_health_handler(ctx)
+    }
+
+    /// Protocol collateral, supply, debt, ratio and mode flags, as return data
+    pub fn get_protocol_stats(ctx: Context<GetProtocolStats>) -> Result<ProtocolStats> {
+        instructions::views::get_protocol_stats_handler(ctx)
+    }
+
+    /// Initialize the PSM: bind the SEEDS and USDC mints, create the USDC vault
+    /// and record the fee account
+    pub fn initialize_mini(ctx: Context<InitializeMini>, tin: u64, tout: u64, exposure_cap: u64) -> Result<()> {
+        require!(tin <= MAX_PSM_FEE && tout <= MAX_PSM_FEE, ErrorCode::InvalidPsmFee);
+
         let state = &mut ctx.accounts.state;
         state.auth = ctx.accounts.auth.key();
         state.paused = 0;
+        state.seed_mint = ctx.accounts.seed_mint.key();
+        state.usdc_mint = ctx.accounts.usdc_mint.key();
+        state.vault = ctx.accounts.vault.key();
+        state.fee_account = ctx.accounts.fee_account.key();
+        state.tin = tin;
+        state.tout = tout;
+        state.exposure_cap = exposure_cap;
+        state.usdc_exposure = 0;
+        state.pending_auth = Pubkey::default();
+        state.bump = ctx.bumps.state;
+        Ok(())
+    }
+
+    /// Rewrite a PSM `State` created before the PSM bound its accounts: bind
+    /// the mints, move the USDC backing existing SEEDS into the vault PDA and
+    /// set the fees (auth only)
+    pub fn migrate_state(ctx: Context<MigrateState>, tin: u64, tout: u64, exposure_cap: u64) -> Result<()> {
+        require!(tin <= MAX_PSM_FEE && tout <= MAX_PSM_FEE, ErrorCode::InvalidPsmFee);
+
+        let state_info = ctx.accounts.state.to_account_info();
+        require!(state_info.data_len() == LegacyState::LEN, ErrorCode::StateAlreadyMigrated);
+
+        // Decode the auth/paused layout
+        let legacy = {
+            let data = state_info.try_borrow_data()?;
+            require!(data[..8] == State::DISCRIMINATOR, ErrorCode::StateAlreadyMigrated);
+            LegacyState::deserialize(&mut &data[8..])?
+        };
+        require_keys_eq!(legacy.auth, ctx.accounts.auth.key(), ErrorCode::Unauthorized);
+
+        // USDC held by the old vault backs SEEDS already in circulation
+        let backing = ctx.accounts.legacy_vault.amount;
+        if backing > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token_interface::TransferChecked {
+                        from: ctx.accounts.legacy_vault.to_account_info(),
+                        mint: ctx.accounts.usdc_mint.to_account_info(),
+                        to: ctx.accounts.vault.to_account_info(),
+                        authority: state_info.clone(),
+                    },
+                    &[&[PSM_STATE_SEED, &[ctx.bumps.state]]],
+                ),
+                backing,
+                ctx.accounts.usdc_mint.decimals,
+            )?;
+        }
+
+        let state = State {
+            auth: legacy.auth,
+            paused: legacy.paused,
+            seed_mint: ctx.accounts.seed_mint.key(),
+            usdc_mint: ctx.accounts.usdc_mint.key(),
+            vault: ctx.accounts.vault.key(),
+            fee_account: ctx.accounts.fee_account.key(),
+            tin,
+            tout,
+            exposure_cap,
+            usdc_exposure: backing,
+            pending_auth: Pubkey::default(),
+            bump: ctx.bumps.state,
+        };
+
+        // Top up rent for the larger account
+        let required_lamports = Rent::get()?.minimum_balance(State::LEN);
+        let shortfall = required_lamports.saturating_sub(state_info.lamports());
+        if shortfall > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.auth.to_account_info(),
+                        to: state_info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+
+        state_info.realloc(State::LEN, true)?;
+        let mut data = state_info.try_borrow_mut_data()?;
+        state.try_serialize(&mut &mut data[..])?;
+
+        msg!("PSM state migrated with {} USDC backing existing SEEDS", backing);
         Ok(())
     }
-    
-    /// Mint SEEDS by depositing USDC (1:1 ratio)
+
+    /// Mint SEEDS by depositing USDC, less the tin fee
     pub fn mint(ctx: Context<MintOp>, amt: u64) -> Result<()> {
-        require!(ctx.accounts.state.paused == 0, SeedError::Paused);
-        
+        require!(ctx.accounts.state.paused == 0, ErrorCode::ProtocolPaused);
+
+        let fee = apply_bps(amt, ctx.accounts.state.tin);
+        let net = amt.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+        let exposure = ctx.accounts.state.usdc_exposure
+            .checked_add(net)
+            .ok_or(ErrorCode::MathOverflow)?;
+        require!(exposure <= ctx.accounts.state.exposure_cap, ErrorCode::PsmExposureCapExceeded);
+
         // Transfer USDC to vault
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
+                token_interface::TransferChecked {
                     from: ctx.accounts.user_usdc.to_account_info(),
+                    mint: ctx.accounts.usdc_mint.to_account_info(),
                     to: ctx.accounts.vault.to_account_info(),
                     authority: ctx.accounts.user.to_account_info(),
                 },
             ),
-            amt,
+            net,
+            ctx.accounts.usdc_mint.decimals,
         )?;
-        
+
+        // Transfer the fee
+        if fee > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new(
+                    ctx.accounts.token_program.to_account_info(),
+                    token_interface::TransferChecked {
+                        from: ctx.accounts.user_usdc.to_account_info(),
+                        mint: ctx.accounts.usdc_mint.to_account_info(),
+                        to: ctx.accounts.fee_account.to_account_info(),
+                        authority: ctx.accounts.user.to_account_info(),
+                    },
+                ),
+                fee,
+                ctx.accounts.usdc_mint.decimals,
+            )?;
+        }
+
         // Mint SEEDS
-        token::mint_to(
+        token_interface::mint_to(
             CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                token::MintTo {
+                ctx.accounts.seed_token_program.to_account_info(),
+                token_interface::MintTo {
                     mint: ctx.accounts.seed_mint.to_account_info(),
                     to: ctx.accounts.user_seeds.to_account_info(),
                     authority: ctx.accounts.state.to_account_info(),
                 },
-                &[&[b"state", &[ctx.bumps.state]]],
+                &[&[PSM_STATE_SEED, &[ctx.accounts.state.bump]]],
             ),
-            amt,
+            net,
         )?;
-        
+
+        ctx.accounts.state.usdc_exposure = exposure;
+        msg!("PSM minted {} SEEDS for {} USDC ({} fee)", net, amt, fee);
         Ok(())
     }
-    
-    /// Burn SEEDS to redeem USDC (1:1 ratio)
+
+    /// Burn SEEDS to redeem USDC, less the tout fee
     pub fn burn(ctx: Context<BurnOp>, amt: u64) -> Result<()> {
-        require!(ctx.accounts.state.paused == 0, SeedError::Paused);
-        
+        require!(ctx.accounts.state.paused == 0, ErrorCode::ProtocolPaused);
+
+        let fee = apply_bps(amt, ctx.accounts.state.tout);
+        let net = amt.checked_sub(fee).ok_or(ErrorCode::MathOverflow)?;
+        let exposure = ctx.accounts.state.usdc_exposure
+            .checked_sub(amt)
+            .ok_or(ErrorCode::InsufficientFunds)?;
+        let seeds: &[&[&[u8]]] = &[&[PSM_STATE_SEED, &[ctx.accounts.state.bump]]];
+
         // Burn SEEDS
-        token::burn(
+        token_interface::burn(
             CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                token::Burn {
+                ctx.accounts.seed_token_program.to_account_info(),
+                token_interface::Burn {
                     mint: ctx.accounts.seed_mint.to_account_info(),
                     from: ctx.accounts.user_seeds.to_account_info(),
                     authority: ctx.accounts.user.to_account_info(),
@@ -65,95 +582,275 @@ pub mod seed_stable_mini {
             ),
             amt,
         )?;
-        
+
         // Return USDC
-        token::transfer(
+        token_interface::transfer_checked(
             CpiContext::new_with_signer(
                 ctx.accounts.token_program.to_account_info(),
-                token::Transfer {
+                token_interface::TransferChecked {
                     from: ctx.accounts.vault.to_account_info(),
+                    mint: ctx.accounts.usdc_mint.to_account_info(),
                     to: ctx.accounts.user_usdc.to_account_info(),
                     authority: ctx.accounts.state.to_account_info(),
                 },
-                &[&[b"state", &[ctx.bumps.state]]],
+                seeds,
             ),
-            amt,
+            net,
+            ctx.accounts.usdc_mint.decimals,
         )?;
-        
+
+        // Transfer the fee
+        if fee > 0 {
+            token_interface::transfer_checked(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token_interface::TransferChecked {
+                        from: ctx.accounts.vault.to_account_info(),
+                        mint: ctx.accounts.usdc_mint.to_account_info(),
+                        to: ctx.accounts.fee_account.to_account_info(),
+                        authority: ctx.accounts.state.to_account_info(),
+                    },
+                    seeds,
+                ),
+                fee,
+                ctx.accounts.usdc_mint.decimals,
+            )?;
+        }
+
+        ctx.accounts.state.usdc_exposure = exposure;
+        msg!("PSM redeemed {} SEEDS for {} USDC ({} fee)", amt, net, fee);
         Ok(())
     }
-    
+
+    /// Set the PSM fees, exposure cap and fee account (auth only)
+    pub fn set_psm_params(ctx: Context<SetPsmParams>, tin: u64, tout: u64, exposure_cap: u64) -> Result<()> {
+        require!(tin <= MAX_PSM_FEE && tout <= MAX_PSM_FEE, ErrorCode::InvalidPsmFee);
+
+        let state = &mut ctx.accounts.state;
+        state.tin = tin;
+        state.tout = tout;
+        state.exposure_cap = exposure_cap;
+        state.fee_account = ctx.accounts.fee_account.key();
+        msg!("PSM fees set to tin {} / tout {} bps, exposure cap {}", tin, tout, exposure_cap);
+        Ok(())
+    }
+
     /// Toggle pause (auth only)
     pub fn pause(ctx: Context<Auth>) -> Result<()> {
         ctx.accounts.state.paused = 1 - ctx.accounts.state.paused;
         Ok(())
     }
+
+    /// Propose a new PSM auth, or cancel with the default key (auth only)
+    pub fn propose_auth(ctx: Context<Auth>, new_auth: Pubkey) -> Result<()> {
+        ctx.accounts.state.pending_auth = new_auth;
+        msg!("Proposed {} as PSM auth", new_auth);
+        Ok(())
+    }
+
+    /// Take over as PSM auth (proposed auth only)
+    pub fn accept_auth(ctx: Context<AcceptAuth>) -> Result<()> {
+        let state = &mut ctx.accounts.state;
+        state.auth = state.pending_auth;
+        state.pending_auth = Pubkey::default();
+        msg!("PSM auth transferred to {}", state.auth);
+        Ok(())
+    }
 }
 
 #[derive(Accounts)]
-pub struct Initialize<'info> {
+pub struct InitializeMini<'info> {
     #[account(
         init,
         payer = auth,
-        space = 8 + 33,
-        seeds = [b"state"],
+        space = State::LEN,
+        seeds = [PSM_STATE_SEED],
         bump
     )]
     pub state: Account<'info, State>,
     #[account(mut)]
     pub auth: Signer<'info>,
+    #[account(constraint = seed_mint.mint_authority == COption::Some(state.key()) @ ErrorCode::InvalidPsmMint)]
+    pub seed_mint: InterfaceAccount<'info, Mint>,
+    #[account(constraint = usdc_mint.decimals == seed_mint.decimals @ ErrorCode::InvalidPsmMint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = auth,
+        token::mint = usdc_mint,
+        token::authority = state,
+        seeds = [PSM_VAULT_SEED],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(token::mint = usdc_mint)]
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    /// CHECK: Pre-PSM layout is decoded by hand in the instruction
+    #[account(
+        mut,
+        seeds = [PSM_STATE_SEED],
+        bump,
+        owner = crate::ID @ ErrorCode::InvalidAccountOwner
+    )]
+    pub state: UncheckedAccount<'info>,
+    #[account(mut)]
+    pub auth: Signer<'info>,
+    #[account(constraint = seed_mint.mint_authority == COption::Some(state.key()) @ ErrorCode::InvalidPsmMint)]
+    pub seed_mint: InterfaceAccount<'info, Mint>,
+    #[account(constraint = usdc_mint.decimals == seed_mint.decimals @ ErrorCode::InvalidPsmMint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// USDC account the pre-PSM `mint`/`burn` used as their vault
+    #[account(mut, token::mint = usdc_mint, token::authority = state)]
+    pub legacy_vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        init,
+        payer = auth,
+        token::mint = usdc_mint,
+        token::authority = state,
+        seeds = [PSM_VAULT_SEED],
+        bump
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(token::mint = usdc_mint)]
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    pub token_program: Interface<'info, TokenInterface>,
     pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
 }
 
 #[derive(Accounts)]
 pub struct MintOp<'info> {
-    #[account(seeds = [b"state"], bump)]
+    #[account(mut, seeds = [PSM_STATE_SEED], bump = state.bump)]
     pub state: Account<'info, State>,
     #[account(mut)]
     pub user: Signer<'info>,
-    #[account(mut)]
-    pub seed_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub user_usdc: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_seeds: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    #[account(mut, address = state.seed_mint)]
+    pub seed_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = state.usdc_mint)]
+    pub user_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = state.seed_mint)]
+    pub user_seeds: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = state.vault)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = state.fee_account)]
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = state.usdc_mint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// Token program of the USDC mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the SEEDS mint
+    pub seed_token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
 pub struct BurnOp<'info> {
-    #[account(seeds = [b"state"], bump)]
+    #[account(mut, seeds = [PSM_STATE_SEED], bump = state.bump)]
     pub state: Account<'info, State>,
     #[account(mut)]
     pub user: Signer<'info>,
-    #[account(mut)]
-    pub seed_mint: Account<'info, Mint>,
-    #[account(mut)]
-    pub user_usdc: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub user_seeds: Account<'info, TokenAccount>,
-    #[account(mut)]
-    pub vault: Account<'info, TokenAccount>,
-    pub token_program: Program<'info, Token>,
+    #[account(mut, address = state.seed_mint)]
+    pub seed_mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = state.usdc_mint)]
+    pub user_usdc: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = state.seed_mint)]
+    pub user_seeds: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = state.vault)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, address = state.fee_account)]
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
+    #[account(address = state.usdc_mint)]
+    pub usdc_mint: InterfaceAccount<'info, Mint>,
+    /// Token program of the USDC mint
+    pub token_program: Interface<'info, TokenInterface>,
+    /// Token program of the SEEDS mint
+    pub seed_token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SetPsmParams<'info> {
+    #[account(mut, seeds = [PSM_STATE_SEED], bump = state.bump)]
+    pub state: Account<'info, State>,
+    #[account(constraint = signers.approve(&state.auth, &auth) @ ErrorCode::Unauthorized)]
+    pub auth: Signer<'info>,
+    pub signers: AuthoritySigners<'info>,
+    #[account(token::mint = state.usdc_mint)]
+    pub fee_account: InterfaceAccount<'info, TokenAccount>,
 }
 
 #[derive(Accounts)]
 pub struct Auth<'info> {
-    #[account(mut, seeds = [b"state"], bump, has_one = auth)]
+    #[account(mut, seeds = [PSM_STATE_SEED], bump = state.bump)]
     pub state: Account<'info, State>,
+    #[account(constraint = signers.approve(&state.auth, &auth) @ ErrorCode::Unauthorized)]
     pub auth: Signer<'info>,
+    pub signers: AuthoritySigners<'info>,
 }
 
+#[derive(Accounts)]
+pub struct AcceptAuth<'info> {
+    #[account(
+        mut,
+        seeds = [PSM_STATE_SEED],
+        bump = state.bump,
+        constraint = state.pending_auth != Pubkey::default() @ ErrorCode::NoPendingAuthority
+    )]
+    pub state: Account<'info, State>,
+    #[account(constraint = signers.approve(&state.pending_auth, &new_auth) @ ErrorCode::Unauthorized)]
+    pub new_auth: Signer<'info>,
+    pub signers: AuthoritySigners<'info>,
+}
+
+/// Peg stability module: swaps USDC and SEEDS at par, less the tin (mint)
+/// and tout (redeem) fees, up to `exposure_cap` USDC held in `vault`.
 #[account]
 pub struct State {
     pub auth: Pubkey,
     pub paused: u8,
+    pub seed_mint: Pubkey,
+    pub usdc_mint: Pubkey,
+    pub vault: Pubkey,
+    pub fee_account: Pubkey, // USDC account receiving tin/tout fees
+    pub tin: u64, // Mint fee in basis points
+    pub tout: u64, // Redeem fee in basis points
+    pub exposure_cap: u64, // Maximum USDC backing PSM-minted SEEDS
+    pub usdc_exposure: u64, // USDC backing PSM-minted SEEDS
+    pub pending_auth: Pubkey, // Proposed auth, default when no transfer is pending
+    pub bump: u8,
+}
+
+impl State {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // auth
+        1 + // paused
+        32 + // seed_mint
+        32 + // usdc_mint
+        32 + // vault
+        32 + // fee_account
+        8 + // tin
+        8 + // tout
+        8 + // exposure_cap
+        8 + // usdc_exposure
+        32 + // pending_auth
+        1; // bump
+}
+
+/// `State` layout before the PSM, holding only `auth` and `paused`. Accounts of
+/// this size are rewritten by `migrate_state`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct LegacyState {
+    pub auth: Pubkey,
+    pub paused: u8,
 }
 
-#[error_code]
-pub enum SeedError {
-    #[msg("Program is paused")]
-    Paused,
+impl LegacyState {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // auth
+        1; // paused
 }
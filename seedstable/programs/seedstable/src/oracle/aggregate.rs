@@ -0,0 +1,323 @@
+use anchor_lang::prelude::*;
+use crate::constants::BASIS_POINTS;
+use crate::error::ErrorCode;
+use crate::state::{load_program_account, CollateralPool, OracleConfig, PriceData};
+
+/// Aggregates the pool's oracle set into a single price.
+///
+/// `oracle_accounts` holds `[oracle_config, price_feed]` pairs for every
+/// primary source in pool order, followed by the fallback pair if one is
+/// configured. Stale, low-confidence or unreadable sources are skipped, as
+/// are sources further than their `max_deviation` from the median of the
+/// rest. The median of the remaining primaries is used when they meet the
+/// pool's quorum, otherwise the fallback source is used on its own.
+pub fn aggregate_pool_price(pool: &CollateralPool, oracle_accounts: &[AccountInfo]) -> Result<PriceData> {
+    let num_sources = pool.num_oracle_sources as usize;
+    let has_fallback = pool.fallback_oracle_config != Pubkey::default();
+    let expected_accounts = (num_sources + usize::from(has_fallback)) * 2;
+    require!(oracle_accounts.len() >= expected_accounts, ErrorCode::InvalidOracleConfig);
+
+    let mut sources: Vec<(PriceData, u64)> = Vec::with_capacity(num_sources);
+    for (index, expected_config) in pool.active_oracle_configs().iter().enumerate() {
+        let pair = &oracle_accounts[index * 2..index * 2 + 2];
+        if let Some(source) = read_source(expected_config, &pair[0], &pair[1])? {
+            sources.push(source);
+        }
+    }
+    let valid_prices = discard_outliers(sources);
+
+    if valid_prices.len() >= pool.min_oracle_quorum as usize && !valid_prices.is_empty() {
+        return Ok(median_price(valid_prices));
+    }
+
+    if has_fallback {
+        let pair = &oracle_accounts[num_sources * 2..num_sources * 2 + 2];
+        if let Some((price_data, _)) = read_source(&pool.fallback_oracle_config, &pair[0], &pair[1])? {
+            msg!(
+                "Oracle quorum not met ({} of {}), using fallback feed",
+                valid_prices.len(),
+                pool.min_oracle_quorum
+            );
+            return Ok(price_data);
+        }
+    }
+
+    err!(ErrorCode::OracleQuorumNotMet)
+}
+
+/// Returns the source's price and its `max_deviation`, or `None` for a source
+/// that is down or misbehaving. Passing the wrong config or feed account is a
+/// caller error and fails the instruction.
+fn read_source(
+    expected_config: &Pubkey,
+    config_info: &AccountInfo,
+    feed_info: &AccountInfo,
+) -> Result<Option<(PriceData, u64)>> {
+    require_keys_eq!(config_info.key(), *expected_config, ErrorCode::InvalidOracleConfig);
+    let config: OracleConfig = load_program_account(config_info)?;
+    require_keys_eq!(feed_info.key(), config.price_feed, ErrorCode::InvalidOracleConfig);
+
+    if !config.is_active {
+        return Ok(None);
+    }
+
+    let price_data = match config.load_price_data(feed_info) {
+        Ok(price_data) => price_data,
+        Err(_) => return Ok(None),
+    };
+
+    if !price_data.is_valid
+        || price_data.is_stale(config.max_staleness)
+        || !price_data.has_high_confidence(config.max_deviation)
+    {
+        return Ok(None);
+    }
+
+    Ok(Some((price_data, config.max_deviation)))
+}
+
+/// Drops sources further than their `max_deviation` (basis points) from the
+/// median of all sources, so one bad feed cannot pull an even-sized set's
+/// median. Two sources that disagree are both dropped.
+fn discard_outliers(sources: Vec<(PriceData, u64)>) -> Vec<PriceData> {
+    if sources.is_empty() {
+        return Vec::new();
+    }
+    let median = median_price(sources.iter().map(|(price_data, _)| price_data.clone()).collect()).price as u128;
+    sources
+        .into_iter()
+        .filter(|(price_data, max_deviation)| {
+            let deviation = (price_data.price as u128).abs_diff(median) * BASIS_POINTS as u128;
+            deviation <= *max_deviation as u128 * median
+        })
+        .map(|(price_data, _)| price_data)
+        .collect()
+}
+
+/// Median price; the aggregate confidence and timestamp take the most
+/// conservative value among the inputs.
+fn median_price(mut prices: Vec<PriceData>) -> PriceData {
+    prices.sort_by_key(|price_data| price_data.price);
+
+    let mid = prices.len() / 2;
+    let price = if prices.len() % 2 == 1 {
+        prices[mid].price
+    } else {
+        ((prices[mid - 1].price as u128 + prices[mid].price as u128) / 2) as u64
+    };
+
+    PriceData {
+        price,
+        confidence: prices.iter().map(|p| p.confidence).max().unwrap_or(0),
+        timestamp: prices.iter().map(|p| p.timestamp).min().unwrap_or(0),
+        is_valid: true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::MAX_ORACLE_SOURCES;
+    use crate::state::OracleType;
+    use anchor_lang::solana_program::{entrypoint::SUCCESS, program_stubs};
+    use std::sync::Once;
+
+    const NOW: i64 = 1_700_000_000;
+
+    /// Serves `Clock::get` at `NOW` so staleness checks run off-chain.
+    struct TestClock;
+
+    impl program_stubs::SyscallStubs for TestClock {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: NOW, ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            SUCCESS
+        }
+    }
+
+    fn set_clock() {
+        static CLOCK: Once = Once::new();
+        CLOCK.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestClock));
+        });
+    }
+
+    fn price(price: u64, confidence: u64, timestamp: i64) -> PriceData {
+        PriceData { price, confidence, timestamp, is_valid: true }
+    }
+
+    struct Slot {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+        owner: Pubkey,
+    }
+
+    /// An inactive oracle config and its (empty) price feed.
+    fn inactive_source() -> [Slot; 2] {
+        let feed = Pubkey::new_unique();
+        let mut config = OracleConfig::deserialize(&mut &vec![0; OracleConfig::LEN - 8][..]).unwrap();
+        config.price_feed = feed;
+        config.is_active = false;
+        let mut data = vec![0; OracleConfig::LEN];
+        config.try_serialize(&mut &mut data[..]).unwrap();
+        [
+            Slot { key: Pubkey::new_unique(), lamports: 1, data, owner: crate::ID },
+            Slot { key: feed, lamports: 1, data: Vec::new(), owner: Pubkey::default() },
+        ]
+    }
+
+    /// A Pyth v2 price account trading at `price` (exponent -8) with a 0.1%
+    /// confidence interval, published at `NOW`.
+    fn pyth_feed(price: i64) -> Vec<u8> {
+        let mut data = vec![0u8; crate::oracle::PYTH_PRICE_ACCOUNT_MIN_LEN];
+        data[0..4].copy_from_slice(&crate::oracle::PYTH_MAGIC.to_le_bytes());
+        data[4..8].copy_from_slice(&crate::oracle::PYTH_VERSION.to_le_bytes());
+        data[8..12].copy_from_slice(&crate::oracle::PYTH_ACCOUNT_TYPE_PRICE.to_le_bytes());
+        data[20..24].copy_from_slice(&(-8i32).to_le_bytes());
+        data[96..104].copy_from_slice(&NOW.to_le_bytes());
+        data[208..216].copy_from_slice(&price.to_le_bytes());
+        data[216..224].copy_from_slice(&(price / 1000).to_le_bytes());
+        data[224..228].copy_from_slice(&crate::oracle::PYTH_STATUS_TRADING.to_le_bytes());
+        data
+    }
+
+    /// An active Pyth oracle config and its price feed.
+    fn pyth_source(price: i64) -> [Slot; 2] {
+        let feed = Pubkey::new_unique();
+        let mut config = OracleConfig::deserialize(&mut &vec![0; OracleConfig::LEN - 8][..]).unwrap();
+        config.oracle_type = OracleType::Pyth;
+        config.price_feed = feed;
+        config.max_staleness = 60;
+        config.max_deviation = 500;
+        config.is_active = true;
+        let mut data = vec![0; OracleConfig::LEN];
+        config.try_serialize(&mut &mut data[..]).unwrap();
+        [
+            Slot { key: Pubkey::new_unique(), lamports: 1, data, owner: crate::ID },
+            Slot { key: feed, lamports: 1, data: pyth_feed(price), owner: Pubkey::new_unique() },
+        ]
+    }
+
+    fn pool_with(sources: &[Slot], fallback: Option<&Slot>, quorum: u8) -> CollateralPool {
+        let mut pool = CollateralPool::deserialize(&mut &vec![0; CollateralPool::LEN - 8][..]).unwrap();
+        let configs: Vec<Pubkey> = sources.chunks(2).map(|pair| pair[0].key).collect();
+        pool.oracle_configs = [Pubkey::default(); MAX_ORACLE_SOURCES];
+        pool.oracle_configs[..configs.len()].copy_from_slice(&configs);
+        pool.num_oracle_sources = configs.len() as u8;
+        pool.fallback_oracle_config = fallback.map(|slot| slot.key).unwrap_or_default();
+        pool.min_oracle_quorum = quorum;
+        pool
+    }
+
+    fn infos(slots: &mut [Slot]) -> Vec<AccountInfo<'_>> {
+        slots
+            .iter_mut()
+            .map(|slot| {
+                AccountInfo::new(&slot.key, false, false, &mut slot.lamports, &mut slot.data, &slot.owner, false, 0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn median_of_an_odd_set_is_the_middle_price() {
+        let median = median_price(vec![price(103, 1, 30), price(100, 4, 10), price(101, 2, 20)]);
+        assert_eq!(median.price, 101);
+        assert_eq!(median.confidence, 4);
+        assert_eq!(median.timestamp, 10);
+        assert!(median.is_valid);
+    }
+
+    #[test]
+    fn median_of_an_even_set_averages_the_middle_pair() {
+        let median = median_price(vec![price(100, 1, 10), price(110, 1, 10), price(104, 1, 10), price(90, 1, 10)]);
+        assert_eq!(median.price, 102);
+
+        // No overflow when averaging near u64::MAX
+        let median = median_price(vec![price(u64::MAX, 0, 0), price(u64::MAX - 2, 0, 0)]);
+        assert_eq!(median.price, u64::MAX - 1);
+    }
+
+    #[test]
+    fn fails_when_no_source_meets_quorum() {
+        let mut slots: Vec<Slot> = (0..3).flat_map(|_| inactive_source()).collect();
+        let pool = pool_with(&slots, None, 2);
+        let accounts = infos(&mut slots);
+
+        let result = aggregate_pool_price(&pool, &accounts);
+        assert_eq!(result.err().unwrap(), ErrorCode::OracleQuorumNotMet.into());
+    }
+
+    #[test]
+    fn fails_when_the_fallback_is_down_too() {
+        let mut slots: Vec<Slot> = (0..3).flat_map(|_| inactive_source()).collect();
+        let pool = pool_with(&slots[..4], Some(&slots[4]), 1);
+        let accounts = infos(&mut slots);
+
+        let result = aggregate_pool_price(&pool, &accounts);
+        assert_eq!(result.err().unwrap(), ErrorCode::OracleQuorumNotMet.into());
+    }
+
+    #[test]
+    fn rejects_missing_or_mismatched_accounts() {
+        let mut slots: Vec<Slot> = (0..2).flat_map(|_| inactive_source()).collect();
+        let pool = pool_with(&slots, None, 1);
+        let accounts = infos(&mut slots);
+
+        let result = aggregate_pool_price(&pool, &accounts[..3]);
+        assert_eq!(result.err().unwrap(), ErrorCode::InvalidOracleConfig.into());
+
+        let swapped = [accounts[2].clone(), accounts[3].clone(), accounts[0].clone(), accounts[1].clone()];
+        let result = aggregate_pool_price(&pool, &swapped);
+        assert_eq!(result.err().unwrap(), ErrorCode::InvalidOracleConfig.into());
+    }
+
+    #[test]
+    fn an_outlier_feed_does_not_move_the_price() {
+        set_clock();
+        // $150.00 and $150.50 agree, $200.00 is off by a third
+        let mut slots: Vec<Slot> = [15_000_000_000, 15_050_000_000, 20_000_000_000]
+            .into_iter()
+            .flat_map(pyth_source)
+            .collect();
+        let pool = pool_with(&slots, None, 2);
+        let accounts = infos(&mut slots);
+
+        let price_data = aggregate_pool_price(&pool, &accounts).unwrap();
+        assert_eq!(price_data.price, 150_250_000);
+        assert_eq!(price_data.timestamp, NOW);
+    }
+
+    #[test]
+    fn two_disagreeing_feeds_defer_to_the_fallback() {
+        set_clock();
+        let mut slots: Vec<Slot> = [15_000_000_000, 20_000_000_000, 15_100_000_000]
+            .into_iter()
+            .flat_map(pyth_source)
+            .collect();
+        let pool = pool_with(&slots[..4], Some(&slots[4]), 1);
+        let without_fallback = pool_with(&slots[..4], None, 1);
+        let accounts = infos(&mut slots);
+
+        // Neither primary can be trusted, so the even-set average is not used
+        let price_data = aggregate_pool_price(&pool, &accounts).unwrap();
+        assert_eq!(price_data.price, 151_000_000);
+
+        let result = aggregate_pool_price(&without_fallback, &accounts[..4]);
+        assert_eq!(result.err().unwrap(), ErrorCode::OracleQuorumNotMet.into());
+    }
+
+    #[test]
+    fn feeds_within_their_deviation_are_averaged() {
+        set_clock();
+        let mut slots: Vec<Slot> = [15_000_000_000, 15_400_000_000]
+            .into_iter()
+            .flat_map(pyth_source)
+            .collect();
+        let pool = pool_with(&slots, None, 2);
+        let accounts = infos(&mut slots);
+
+        let price_data = aggregate_pool_price(&pool, &accounts).unwrap();
+        assert_eq!(price_data.price, 152_000_000);
+    }
+}
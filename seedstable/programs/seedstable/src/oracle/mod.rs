@@ -0,0 +1,53 @@
+pub mod aggregate;
+pub mod pyth;
+pub mod switchboard;
+pub mod seed_lots;
+
+pub use aggregate::*;
+pub use pyth::*;
+pub use switchboard::*;
+pub use seed_lots::*;
+
+use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::state::{OracleType, PriceData};
+
+/// Reads a price feed of the given oracle family. Callers must check the
+/// account key against the configured feed before trusting the result.
+pub fn load_price_data(oracle_type: OracleType, oracle_account: &AccountInfo) -> Result<PriceData> {
+    match oracle_type {
+        OracleType::Pyth => load_pyth_price_data(oracle_account),
+        OracleType::Switchboard => load_switchboard_price_data(oracle_account),
+        OracleType::SeedLotRegistry => load_seed_lot_registry_price_data(oracle_account),
+    }
+}
+
+/// Rescales a raw oracle value with exponent `exponent` to `USD_DECIMALS`.
+pub fn normalize_price(value: u128, exponent: i32) -> Result<u64> {
+    let shift = exponent + USD_DECIMALS as i32;
+    let scaled = if shift >= 0 {
+        10_u128
+            .checked_pow(shift as u32)
+            .and_then(|factor| value.checked_mul(factor))
+    } else {
+        10_u128
+            .checked_pow(shift.unsigned_abs())
+            .map(|factor| value / factor)
+    }
+    .ok_or(ErrorCode::MathOverflow)?;
+
+    u64::try_from(scaled).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+pub(crate) fn read_u32(data: &[u8], offset: usize) -> u32 {
+    let mut bytes = [0u8; 4];
+    bytes.copy_from_slice(&data[offset..offset + 4]);
+    u32::from_le_bytes(bytes)
+}
+
+pub(crate) fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(bytes)
+}
@@ -0,0 +1,66 @@
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use crate::state::PriceData;
+use super::{normalize_price, read_u32, read_u64};
+
+// Pyth v2 price account layout (pyth-client `PriceAccount`)
+pub const PYTH_MAGIC: u32 = 0xa1b2_c3d4;
+pub const PYTH_VERSION: u32 = 2;
+pub const PYTH_ACCOUNT_TYPE_PRICE: u32 = 3;
+pub const PYTH_STATUS_TRADING: u32 = 1;
+
+const MAGIC_OFFSET: usize = 0;
+const VERSION_OFFSET: usize = 4;
+const ACCOUNT_TYPE_OFFSET: usize = 8;
+const EXPONENT_OFFSET: usize = 20;
+const TIMESTAMP_OFFSET: usize = 96;
+const AGG_PRICE_OFFSET: usize = 208;
+const AGG_CONF_OFFSET: usize = 216;
+const AGG_STATUS_OFFSET: usize = 224;
+pub const PYTH_PRICE_ACCOUNT_MIN_LEN: usize = 240;
+
+pub struct PythPrice {
+    pub price: i64,
+    pub confidence: u64,
+    pub exponent: i32,
+    pub publish_time: i64,
+    pub status: u32,
+}
+
+impl PythPrice {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        require!(data.len() >= PYTH_PRICE_ACCOUNT_MIN_LEN, ErrorCode::InvalidOracleConfig);
+        require!(read_u32(data, MAGIC_OFFSET) == PYTH_MAGIC, ErrorCode::InvalidOracleConfig);
+        require!(read_u32(data, VERSION_OFFSET) == PYTH_VERSION, ErrorCode::InvalidOracleConfig);
+        require!(
+            read_u32(data, ACCOUNT_TYPE_OFFSET) == PYTH_ACCOUNT_TYPE_PRICE,
+            ErrorCode::InvalidOracleConfig
+        );
+
+        Ok(Self {
+            price: read_u64(data, AGG_PRICE_OFFSET) as i64,
+            confidence: read_u64(data, AGG_CONF_OFFSET),
+            exponent: read_u32(data, EXPONENT_OFFSET) as i32,
+            publish_time: read_u64(data, TIMESTAMP_OFFSET) as i64,
+            status: read_u32(data, AGG_STATUS_OFFSET),
+        })
+    }
+
+    pub fn to_price_data(&self) -> Result<PriceData> {
+        require!(self.price > 0, ErrorCode::InvalidOracleConfig);
+
+        Ok(PriceData {
+            price: normalize_price(self.price as u128, self.exponent)?,
+            confidence: normalize_price(self.confidence as u128, self.exponent)?,
+            timestamp: self.publish_time,
+            is_valid: self.status == PYTH_STATUS_TRADING,
+        })
+    }
+}
+
+/// Reads a Pyth price account. Callers must check the account key against
+/// the configured feed before trusting the result.
+pub fn load_pyth_price_data(oracle_account: &AccountInfo) -> Result<PriceData> {
+    let data = oracle_account.try_borrow_data()?;
+    PythPrice::parse(&data)?.to_price_data()
+}
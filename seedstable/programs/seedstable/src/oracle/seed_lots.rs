@@ -0,0 +1,9 @@
+use anchor_lang::prelude::*;
+use crate::state::{load_program_account, PriceData, SeedLotRegistry};
+
+/// Reads the last price published by a seed lot registry. Callers must check
+/// the account key against the configured feed before trusting the result.
+pub fn load_seed_lot_registry_price_data(oracle_account: &AccountInfo) -> Result<PriceData> {
+    let registry: SeedLotRegistry = load_program_account(oracle_account)?;
+    Ok(registry.price_data())
+}
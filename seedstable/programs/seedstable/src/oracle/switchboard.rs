@@ -0,0 +1,83 @@
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use crate::state::PriceData;
+use super::{normalize_price, read_u32, read_u64};
+
+// Switchboard v2 `AggregatorAccountData` layout (packed, after the Anchor discriminator)
+pub const SWITCHBOARD_AGGREGATOR_DISCRIMINATOR: [u8; 8] = [217, 230, 65, 101, 201, 162, 27, 125];
+
+const MIN_ORACLE_RESULTS_OFFSET: usize = 236;
+const LATEST_ROUND_OFFSET: usize = 341;
+const ROUND_NUM_SUCCESS_OFFSET: usize = LATEST_ROUND_OFFSET;
+const ROUND_OPEN_TIMESTAMP_OFFSET: usize = LATEST_ROUND_OFFSET + 17;
+const ROUND_RESULT_OFFSET: usize = LATEST_ROUND_OFFSET + 25;
+const ROUND_STD_DEVIATION_OFFSET: usize = LATEST_ROUND_OFFSET + 45;
+pub const SWITCHBOARD_AGGREGATOR_MIN_LEN: usize = ROUND_STD_DEVIATION_OFFSET + 20;
+
+/// `SwitchboardDecimal`: `mantissa / 10^scale`
+pub struct SwitchboardDecimal {
+    pub mantissa: i128,
+    pub scale: u32,
+}
+
+impl SwitchboardDecimal {
+    fn read(data: &[u8], offset: usize) -> Self {
+        let mut bytes = [0u8; 16];
+        bytes.copy_from_slice(&data[offset..offset + 16]);
+        Self {
+            mantissa: i128::from_le_bytes(bytes),
+            scale: read_u32(data, offset + 16),
+        }
+    }
+
+    pub fn to_usd(&self) -> Result<u64> {
+        require!(self.mantissa >= 0, ErrorCode::InvalidOracleConfig);
+        let exponent = i32::try_from(self.scale).map_err(|_| ErrorCode::MathOverflow)?;
+        normalize_price(self.mantissa as u128, -exponent)
+    }
+}
+
+pub struct SwitchboardRound {
+    pub result: SwitchboardDecimal,
+    pub std_deviation: SwitchboardDecimal,
+    pub round_open_timestamp: i64,
+    pub num_success: u32,
+    pub min_oracle_results: u32,
+}
+
+impl SwitchboardRound {
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        require!(data.len() >= SWITCHBOARD_AGGREGATOR_MIN_LEN, ErrorCode::InvalidOracleConfig);
+        require!(
+            data[..8] == SWITCHBOARD_AGGREGATOR_DISCRIMINATOR,
+            ErrorCode::InvalidOracleConfig
+        );
+
+        Ok(Self {
+            result: SwitchboardDecimal::read(data, ROUND_RESULT_OFFSET),
+            std_deviation: SwitchboardDecimal::read(data, ROUND_STD_DEVIATION_OFFSET),
+            round_open_timestamp: read_u64(data, ROUND_OPEN_TIMESTAMP_OFFSET) as i64,
+            num_success: read_u32(data, ROUND_NUM_SUCCESS_OFFSET),
+            min_oracle_results: read_u32(data, MIN_ORACLE_RESULTS_OFFSET),
+        })
+    }
+
+    pub fn to_price_data(&self) -> Result<PriceData> {
+        let price = self.result.to_usd()?;
+        require!(price > 0, ErrorCode::InvalidOracleConfig);
+
+        Ok(PriceData {
+            price,
+            confidence: self.std_deviation.to_usd()?,
+            timestamp: self.round_open_timestamp,
+            is_valid: self.num_success > 0 && self.num_success >= self.min_oracle_results,
+        })
+    }
+}
+
+/// Reads the latest confirmed round of a Switchboard aggregator. Callers must
+/// check the account key against the configured feed before trusting the result.
+pub fn load_switchboard_price_data(oracle_account: &AccountInfo) -> Result<PriceData> {
+    let data = oracle_account.try_borrow_data()?;
+    SwitchboardRound::parse(&data)?.to_price_data()
+}
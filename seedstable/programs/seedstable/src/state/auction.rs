@@ -0,0 +1,61 @@
+use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+
+/// Descending-price sale of one pool's collateral taken from an unhealthy
+/// position. The debt moved into the auction no longer accrues fees.
+#[account]
+pub struct LiquidationAuction {
+    pub position_owner: Pubkey,
+    pub user_position: Pubkey,
+    pub collateral_pool: Pubkey,
+    pub initiator: Pubkey, // Receives the account rent on close
+    pub collateral_remaining: u64,
+    pub debt_remaining: u64, // SEEDS still to be raised
+    pub start_price: u64, // USD_DECIMALS
+    pub floor_price: u64, // USD_DECIMALS
+    pub start_timestamp: i64,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+impl LiquidationAuction {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // position_owner
+        32 + // user_position
+        32 + // collateral_pool
+        32 + // initiator
+        8 + // collateral_remaining
+        8 + // debt_remaining
+        8 + // start_price
+        8 + // floor_price
+        8 + // start_timestamp
+        8 + // duration
+        1; // bump
+
+    /// Price decays linearly from `start_price` to `floor_price` over
+    /// `duration`, then holds at the floor.
+    pub fn current_price(&self, now: i64) -> u64 {
+        let elapsed = (now - self.start_timestamp).clamp(0, self.duration);
+        let decay = (self.start_price - self.floor_price) as u128 * elapsed as u128
+            / self.duration as u128;
+        self.start_price - decay as u64
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.start_timestamp + self.duration
+    }
+
+    pub fn is_settled(&self) -> bool {
+        self.debt_remaining == 0 || self.collateral_remaining == 0
+    }
+
+    /// Prices an auction's start and floor from the oracle price.
+    pub fn price_bounds(oracle_price: u64, start_premium: u64, floor: u64) -> Result<(u64, u64)> {
+        let scale = |bps: u64| -> Result<u64> {
+            u64::try_from(oracle_price as u128 * bps as u128 / BASIS_POINTS as u128)
+                .map_err(|_| ErrorCode::MathOverflow.into())
+        };
+        Ok((scale(start_premium)?, scale(floor)?))
+    }
+}
@@ -1,12 +1,335 @@
 use anchor_lang::prelude::*;
+use anchor_spl::token::spl_token;
+use anchor_spl::token_2022::spl_token_2022;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use super::{load_program_account, save_program_account, PriceHistory, ProtocolState};
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum CollateralType {
+    USDC,
+    SOL,
+    TokenizedSeeds,
+}
+
+impl CollateralType {
+    /// Default (max LTV, liquidation threshold, liquidation bonus) in basis points.
+    pub fn default_risk_params(&self) -> (u64, u64, u64) {
+        match self {
+            CollateralType::USDC => (9000, 9500, 200),
+            CollateralType::SOL => (6666, 8333, 500),
+            CollateralType::TokenizedSeeds => (5000, 6500, 1000),
+        }
+    }
+
+    /// Largest deposit cap a pool of this collateral may be given.
+    pub fn max_deposit_cap(&self) -> u64 {
+        match self {
+            CollateralType::USDC => MAX_USDC_DEPOSIT,
+            CollateralType::SOL => MAX_SOL_DEPOSIT,
+            CollateralType::TokenizedSeeds => MAX_SEEDS_DEPOSIT,
+        }
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LiquidationMode {
+    /// `liquidate` sells collateral at the pool's fixed bonus.
+    FixedDiscount,
+    /// `start_auction` sells collateral through a descending-price auction.
+    DutchAuction,
+}
+
+#[account]
+pub struct CollateralPool {
+    pub protocol_state: Pubkey,
+    pub collateral_type: CollateralType,
+    pub mint: Pubkey,
+    pub decimals: u8,
+    pub vault: Pubkey,
+    pub oracle_configs: [Pubkey; MAX_ORACLE_SOURCES],
+    pub num_oracle_sources: u8,
+    pub fallback_oracle_config: Pubkey,
+    pub min_oracle_quorum: u8,
+    pub last_price: u64, // Aggregated oracle price, USD_DECIMALS
+    pub last_price_timestamp: i64,
+    pub total_deposited: u64,
+    pub total_value_usd: u64,
+    pub max_ltv: u64, // Max debt per collateral value in basis points (66.66% = 6666)
+    pub liquidation_threshold: u64, // Debt per collateral value that allows liquidation (83.33% = 8333)
+    pub liquidation_bonus: u64, // Basis points paid to liquidators on seized collateral
+    pub debt_ceiling: u64, // Max SEEDS debt attributed to this pool
+    pub total_normalized_debt: u64, // Attributed debt divided by the protocol rate index
+    pub liquidation_mode: LiquidationMode,
+    pub auction_start_premium: u64, // Auction start price over oracle price, basis points (120% = 12000)
+    pub auction_floor: u64, // Lowest auction price over oracle price, basis points (80% = 8000)
+    pub auction_duration: i64, // Seconds for the price to decay from start to floor
+    pub sunset_start: i64, // Start of the liquidation threshold wind-down, 0 when not sunsetting
+    pub sunset_duration: i64, // Seconds for the threshold to fall to `sunset_threshold`
+    pub sunset_threshold: u64, // Liquidation threshold at the end of the sunset, basis points
+    pub settlement_price: u64, // Final price frozen at global settlement, 0 until then
+    pub settlement_collateral: u64, // Collateral left to SEEDS holders after settlement
+    pub max_deposit_amount: u64,
+    pub is_active: bool,
+    pub bump: u8,
+}
+
+impl CollateralPool {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // protocol_state
+        1 + // collateral_type
+        32 + // mint
+        1 + // decimals
+        32 + // vault
+        32 * MAX_ORACLE_SOURCES + // oracle_configs
+        1 + // num_oracle_sources
+        32 + // fallback_oracle_config
+        1 + // min_oracle_quorum
+        8 + // last_price
+        8 + // last_price_timestamp
+        8 + // total_deposited
+        8 + // total_value_usd
+        8 + // max_ltv
+        8 + // liquidation_threshold
+        8 + // liquidation_bonus
+        8 + // debt_ceiling
+        8 + // total_normalized_debt
+        1 + // liquidation_mode
+        8 + // auction_start_premium
+        8 + // auction_floor
+        8 + // auction_duration
+        8 + // sunset_start
+        8 + // sunset_duration
+        8 + // sunset_threshold
+        8 + // settlement_price
+        8 + // settlement_collateral
+        8 + // max_deposit_amount
+        1 + // is_active
+        1; // bump
+
+    pub fn can_accept_deposit(&self, amount: u64) -> bool {
+        self.total_deposited
+            .checked_add(amount)
+            .map(|total| total <= self.max_deposit_amount)
+            .unwrap_or(false)
+    }
+
+    pub fn validate_risk_params(max_ltv: u64, liquidation_threshold: u64, liquidation_bonus: u64) -> Result<()> {
+        require!(max_ltv > 0 && max_ltv < liquidation_threshold, ErrorCode::InvalidRiskParameters);
+        require!(liquidation_threshold <= MAX_LIQUIDATION_THRESHOLD, ErrorCode::InvalidRiskParameters);
+        require!(liquidation_bonus <= MAX_LIQUIDATION_BONUS, ErrorCode::InvalidRiskParameters);
+        // Seizing collateral plus bonus at the threshold must not exceed the collateral itself
+        require!(
+            liquidation_threshold as u128 * (BASIS_POINTS + liquidation_bonus) as u128
+                <= (BASIS_POINTS * BASIS_POINTS) as u128,
+            ErrorCode::InvalidRiskParameters
+        );
+        Ok(())
+    }
+
+    pub fn validate_caps(collateral_type: CollateralType, max_deposit_amount: u64, debt_ceiling: u64) -> Result<()> {
+        require!(
+            max_deposit_amount > 0 && max_deposit_amount <= collateral_type.max_deposit_cap(),
+            ErrorCode::InvalidPoolCaps
+        );
+        require!(debt_ceiling > 0, ErrorCode::InvalidPoolCaps);
+        Ok(())
+    }
+
+    pub fn validate_auction_params(start_premium: u64, floor: u64, duration: i64) -> Result<()> {
+        require!(
+            start_premium > BASIS_POINTS && start_premium <= MAX_AUCTION_START_PREMIUM,
+            ErrorCode::InvalidAuctionParameters
+        );
+        require!(
+            (MIN_AUCTION_FLOOR..BASIS_POINTS).contains(&floor),
+            ErrorCode::InvalidAuctionParameters
+        );
+        require!(
+            (MIN_AUCTION_DURATION..=MAX_AUCTION_DURATION).contains(&duration),
+            ErrorCode::InvalidAuctionParameters
+        );
+        Ok(())
+    }
+
+    pub fn can_take_debt(&self, normalized_amount: u64, protocol_state: &ProtocolState) -> bool {
+        self.total_normalized_debt
+            .checked_add(normalized_amount)
+            .and_then(|total| protocol_state.debt_for(total).ok())
+            .map(|debt| debt <= self.debt_ceiling)
+            .unwrap_or(false)
+    }
+
+    /// Whether the pool holds wrapped SOL under either token program.
+    pub fn is_native_sol(&self) -> bool {
+        self.mint == spl_token::native_mint::ID || self.mint == spl_token_2022::native_mint::ID
+    }
+
+    pub fn is_sunsetting(&self) -> bool {
+        self.sunset_start > 0
+    }
+
+    /// Liquidation threshold at `now`. During a sunset it falls linearly from
+    /// `liquidation_threshold` to `sunset_threshold` and then stays there.
+    pub fn liquidation_threshold_at(&self, now: i64) -> u64 {
+        if !self.is_sunsetting() {
+            return self.liquidation_threshold;
+        }
+        let elapsed = (now - self.sunset_start).clamp(0, self.sunset_duration);
+        let decline = self.liquidation_threshold.saturating_sub(self.sunset_threshold);
+        let lowered = decline as u128 * elapsed as u128 / self.sunset_duration as u128;
+        self.liquidation_threshold - lowered as u64
+    }
+
+    /// Borrowing and liquidation limits contributed by `value` of this collateral.
+    pub fn valuation_of(&self, value: u64, now: i64) -> CollateralValuation {
+        let liquidation_threshold = self.liquidation_threshold_at(now);
+        CollateralValuation {
+            total_value: value,
+            // A sunset must not leave borrowing room above the liquidation limit
+            borrow_limit: apply_bps(value, self.max_ltv.min(liquidation_threshold)),
+            liquidation_limit: apply_bps(value, liquidation_threshold),
+        }
+    }
+
+    pub fn active_oracle_configs(&self) -> &[Pubkey] {
+        &self.oracle_configs[..self.num_oracle_sources as usize]
+    }
+
+    /// Last aggregated price, rejected once older than `MAX_PRICE_STALENESS`.
+    pub fn current_price(&self) -> Result<u64> {
+        require!(self.last_price > 0, ErrorCode::StalePriceData);
+        let age = Clock::get()?.unix_timestamp - self.last_price_timestamp;
+        require!(age <= MAX_PRICE_STALENESS, ErrorCode::StalePriceData);
+        Ok(self.last_price)
+    }
+
+    pub fn valuation_price(&self, price_history: &PriceHistory, valuation: Valuation, now: i64) -> Result<u64> {
+        let spot_price = self.current_price()?;
+        match valuation {
+            Valuation::Spot => Ok(spot_price),
+            Valuation::Conservative => price_history.conservative_price(spot_price, now),
+        }
+    }
+
+    pub fn update_value(&mut self, price: u64, decimals: u8) {
+        self.total_value_usd = calculate_usd_value(self.total_deposited, price, decimals)
+            .unwrap_or(u64::MAX);
+    }
+
+    /// Re-marks the pool at its last price after `total_deposited` changed and
+    /// carries the difference into the protocol total. Pool and protocol
+    /// totals are always valued at market, as `update_oracle_price` does;
+    /// only sub-positions keep deposit-time book values.
+    pub fn sync_value(&mut self, protocol_state: &mut ProtocolState) -> Result<()> {
+        let previous_value = self.total_value_usd;
+        self.update_value(self.last_price, self.decimals);
+        protocol_state.total_collateral_value = protocol_state.total_collateral_value
+            .saturating_sub(previous_value)
+            .checked_add(self.total_value_usd)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct CollateralPoolPosition {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub value_usd: u64,
+    pub normalized_debt: u64, // Debt attributed to this pool, divided by the rate index
+}
+
+impl CollateralPoolPosition {
+    pub const LEN: usize = 32 + // pool
+        8 + // amount
+        8 + // value_usd
+        8; // normalized_debt
+}
+
+/// Aggregate value of a position's collateral and the debt it can support.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct CollateralValuation {
+    pub total_value: u64,
+    pub borrow_limit: u64,
+    pub liquidation_limit: u64,
+}
+
+impl CollateralValuation {
+    pub fn checked_add(&self, other: &CollateralValuation) -> Option<CollateralValuation> {
+        Some(CollateralValuation {
+            total_value: self.total_value.checked_add(other.total_value)?,
+            borrow_limit: self.borrow_limit.checked_add(other.borrow_limit)?,
+            liquidation_limit: self.liquidation_limit.checked_add(other.liquidation_limit)?,
+        })
+    }
+
+    pub fn saturating_sub(&self, other: &CollateralValuation) -> CollateralValuation {
+        CollateralValuation {
+            total_value: self.total_value.saturating_sub(other.total_value),
+            borrow_limit: self.borrow_limit.saturating_sub(other.borrow_limit),
+            liquidation_limit: self.liquidation_limit.saturating_sub(other.liquidation_limit),
+        }
+    }
+
+    pub fn can_borrow(&self, debt: u64) -> bool {
+        debt <= self.borrow_limit
+    }
+
+    pub fn is_liquidatable(&self, debt: u64) -> bool {
+        debt > self.liquidation_limit
+    }
+
+    /// Collateral value over debt in basis points, `u64::MAX` when debt-free.
+    pub fn collateral_ratio(&self, debt: u64) -> u64 {
+        if debt == 0 {
+            return u64::MAX;
+        }
+        let ratio = (self.total_value as u128 * BASIS_POINTS as u128) / debt as u128;
+        u64::try_from(ratio).unwrap_or(u64::MAX)
+    }
+
+    /// Liquidation limit over debt in basis points; below `BASIS_POINTS` the
+    /// position can be liquidated. `u64::MAX` when debt-free.
+    pub fn health_factor(&self, debt: u64) -> u64 {
+        if debt == 0 {
+            return u64::MAX;
+        }
+        let ratio = (self.liquidation_limit as u128 * BASIS_POINTS as u128) / debt as u128;
+        u64::try_from(ratio).unwrap_or(u64::MAX)
+    }
+}
+
+/// One sub-position valued at a given price.
+#[derive(Clone, Copy, Debug)]
+pub struct PoolValuation {
+    pub pool: Pubkey,
+    pub amount: u64,
+    pub price: u64,
+    pub valuation: CollateralValuation,
+}
+
+/// How collateral is priced when valuing a position.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Valuation {
+    /// Latest aggregated oracle price; used to decide liquidations.
+    Spot,
+    /// Lower of spot and TWAP; used before minting or withdrawing.
+    Conservative,
+}
 
 #[account]
 pub struct UserPosition {
     pub owner: Pubkey,
     pub protocol_state: Pubkey,
-    pub usdc_deposited: u64,
-    pub stablecoin_minted: u64,
+    pub collateral_deposited: u64,
+    pub normalized_debt: u64, // SEEDS owed divided by ProtocolState.rate_index
+    pub collateral_positions: Vec<CollateralPoolPosition>, // Up to MAX_COLLATERAL_POSITIONS
     pub last_update_timestamp: i64,
+    pub sort_key: u64, // Nominal ratio when last placed in the position index
+    pub prev_position: Pubkey, // Riskier neighbour in the position index
+    pub next_position: Pubkey, // Safer neighbour in the position index
+    pub in_index: bool,
     pub bump: u8,
 }
 
@@ -14,8 +337,203 @@ impl UserPosition {
     pub const LEN: usize = 8 + // discriminator
         32 + // owner
         32 + // protocol_state
-        8 + // usdc_deposited
-        8 + // stablecoin_minted
+        8 + // collateral_deposited
+        8 + // normalized_debt
+        4 + CollateralPoolPosition::LEN * MAX_COLLATERAL_POSITIONS + // collateral_positions
         8 + // last_update_timestamp
+        8 + // sort_key
+        32 + // prev_position
+        32 + // next_position
+        1 + // in_index
         1; // bump
+
+    /// Book collateral value over normalized debt in basis points. It only
+    /// changes when the position does, so it can key the position index.
+    pub fn nominal_ratio(&self) -> u64 {
+        if self.normalized_debt == 0 {
+            return u64::MAX;
+        }
+        let ratio = (self.total_collateral_value() as u128 * BASIS_POINTS as u128)
+            / self.normalized_debt as u128;
+        u64::try_from(ratio).unwrap_or(u64::MAX)
+    }
+
+    /// Book collateral value over `debt` in basis points, `u64::MAX` without debt.
+    pub fn book_ratio(&self, debt: u64) -> u64 {
+        if debt == 0 {
+            return u64::MAX;
+        }
+        let ratio = (self.total_collateral_value() as u128 * BASIS_POINTS as u128) / debt as u128;
+        u64::try_from(ratio).unwrap_or(u64::MAX)
+    }
+
+    pub fn find_position(&self, pool: &Pubkey) -> Option<&CollateralPoolPosition> {
+        self.collateral_positions.iter().find(|pos| pos.pool == *pool)
+    }
+
+    pub fn find_position_mut(&mut self, pool: &Pubkey) -> Option<&mut CollateralPoolPosition> {
+        self.collateral_positions.iter_mut().find(|pos| pos.pool == *pool)
+    }
+
+    /// Attributes newly minted normalized debt to the sub-position of `pool`.
+    pub fn add_debt(&mut self, pool: &Pubkey, normalized_amount: u64) -> Result<()> {
+        let pool_position = self.find_position_mut(pool).ok_or(ErrorCode::InvalidCollateralType)?;
+        pool_position.normalized_debt = pool_position.normalized_debt
+            .checked_add(normalized_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.normalized_debt = self.normalized_debt
+            .checked_add(normalized_amount)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Repays normalized debt starting with the sub-position of `pool`, then
+    /// the others in order. Returns how much was taken off each pool.
+    pub fn repay_debt(&mut self, pool: &Pubkey, normalized_amount: u64) -> Result<Vec<(Pubkey, u64)>> {
+        require!(normalized_amount <= self.normalized_debt, ErrorCode::InsufficientFunds);
+
+        let mut remaining = normalized_amount;
+        let mut repaid = Vec::new();
+        let first = self.collateral_positions.iter().position(|pos| pos.pool == *pool);
+        let order = first.into_iter().chain(
+            (0..self.collateral_positions.len()).filter(|index| Some(*index) != first),
+        );
+        for index in order.collect::<Vec<_>>() {
+            if remaining == 0 {
+                break;
+            }
+            let pool_position = &mut self.collateral_positions[index];
+            let portion = remaining.min(pool_position.normalized_debt);
+            if portion > 0 {
+                pool_position.normalized_debt -= portion;
+                remaining -= portion;
+                repaid.push((pool_position.pool, portion));
+            }
+        }
+
+        self.normalized_debt -= normalized_amount;
+        Ok(repaid)
+    }
+
+    pub fn add_collateral(&mut self, pool: Pubkey, amount: u64, value_usd: u64) -> Result<()> {
+        if let Some(existing_pos) = self.find_position_mut(&pool) {
+            existing_pos.amount = existing_pos.amount
+                .checked_add(amount)
+                .ok_or(ErrorCode::MathOverflow)?;
+            existing_pos.value_usd = existing_pos.value_usd
+                .checked_add(value_usd)
+                .ok_or(ErrorCode::MathOverflow)?;
+            return Ok(());
+        }
+
+        require!(
+            self.collateral_positions.len() < MAX_COLLATERAL_POSITIONS,
+            ErrorCode::TooManyCollateralPositions
+        );
+        self.collateral_positions.push(CollateralPoolPosition { pool, amount, value_usd, normalized_debt: 0 });
+        Ok(())
+    }
+
+    /// Drops sub-positions that hold neither collateral nor debt, freeing their slot.
+    pub fn remove_empty_positions(&mut self) {
+        self.collateral_positions.retain(|pos| pos.amount > 0 || pos.normalized_debt > 0);
+    }
+
+    /// True once no sub-position holds any collateral.
+    pub fn has_no_collateral(&self) -> bool {
+        self.collateral_positions.iter().all(|pos| pos.amount == 0)
+    }
+
+    /// Sum of the book values recorded at deposit time.
+    pub fn total_collateral_value(&self) -> u64 {
+        self.collateral_positions
+            .iter()
+            .fold(0u64, |total, pos| total.saturating_add(pos.value_usd))
+    }
+
+    /// Values every sub-position at current prices. `pool_accounts` holds a
+    /// `[collateral_pool, price_history]` pair per sub-position, in order.
+    pub fn collateral_value(&self, pool_accounts: &[AccountInfo], valuation: Valuation) -> Result<CollateralValuation> {
+        self.pool_valuations(pool_accounts, valuation)?
+            .iter()
+            .try_fold(CollateralValuation::default(), |total, pool| total.checked_add(&pool.valuation))
+            .ok_or(ErrorCode::MathOverflow.into())
+    }
+
+    /// Per-pool breakdown of `collateral_value`, in sub-position order.
+    pub fn pool_valuations(&self, pool_accounts: &[AccountInfo], valuation: Valuation) -> Result<Vec<PoolValuation>> {
+        require!(
+            pool_accounts.len() >= self.collateral_positions.len() * 2,
+            ErrorCode::MissingCollateralAccounts
+        );
+
+        let now = Clock::get()?.unix_timestamp;
+        let mut pools = Vec::with_capacity(self.collateral_positions.len());
+        for (position, accounts) in self.collateral_positions.iter().zip(pool_accounts.chunks(2)) {
+            require_keys_eq!(accounts[0].key(), position.pool, ErrorCode::MissingCollateralAccounts);
+            let pool: CollateralPool = load_program_account(&accounts[0])?;
+            let price_history: Box<PriceHistory> = Box::new(load_program_account(&accounts[1])?);
+            require_keys_eq!(price_history.collateral_pool, position.pool, ErrorCode::MissingCollateralAccounts);
+
+            let price = pool.valuation_price(&price_history, valuation, now)?;
+            let value = calculate_usd_value(position.amount, price, pool.decimals)?;
+            pools.push(PoolValuation {
+                pool: position.pool,
+                amount: position.amount,
+                price,
+                valuation: pool.valuation_of(value, now),
+            });
+        }
+        Ok(pools)
+    }
+}
+
+/// Releases repaid debt from each pool's debt ceiling. `pool` is the pool
+/// named by the instruction; any other pool must be among the writable
+/// `[collateral_pool, price_history]` pairs in `pool_accounts`.
+pub fn release_pool_debt(
+    pool: &mut Account<CollateralPool>,
+    repaid: &[(Pubkey, u64)],
+    pool_accounts: &[AccountInfo],
+) -> Result<()> {
+    for (pool_key, portion) in repaid {
+        if *pool_key == pool.key() {
+            pool.total_normalized_debt = pool.total_normalized_debt.saturating_sub(*portion);
+            continue;
+        }
+        let info = pool_accounts
+            .iter()
+            .step_by(2)
+            .find(|info| info.key() == *pool_key)
+            .ok_or(ErrorCode::MissingCollateralAccounts)?;
+        let mut other_pool: CollateralPool = load_program_account(info)?;
+        other_pool.total_normalized_debt = other_pool.total_normalized_debt.saturating_sub(*portion);
+        save_program_account(info, &other_pool)?;
+    }
+    Ok(())
+}
+
+pub fn apply_bps(value: u64, bps: u64) -> u64 {
+    ((value as u128 * bps as u128) / BASIS_POINTS as u128) as u64
+}
+
+/// Token amount in base units worth `usd_value` at `price`, rounded down.
+pub fn calculate_token_amount(usd_value: u64, price: u64, decimals: u8) -> Result<u64> {
+    require!(price > 0, ErrorCode::StalePriceData);
+    let amount = (usd_value as u128)
+        .checked_mul(10_u128.pow(decimals as u32))
+        .map(|x| x / price as u128)
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    u64::try_from(amount).map_err(|_| ErrorCode::MathOverflow.into())
+}
+
+/// Converts a token amount in base units into USD with `USD_DECIMALS` precision.
+pub fn calculate_usd_value(amount: u64, price: u64, decimals: u8) -> Result<u64> {
+    let value = (amount as u128)
+        .checked_mul(price as u128)
+        .and_then(|x| x.checked_div(10_u128.pow(decimals as u32)))
+        .ok_or(ErrorCode::MathOverflow)?;
+
+    u64::try_from(value).map_err(|_| ErrorCode::MathOverflow.into())
 }
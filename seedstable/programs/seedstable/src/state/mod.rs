@@ -1,7 +1,37 @@
 pub mod protocol;
 pub mod collateral;
 pub mod oracle;
+pub mod price_history;
+pub mod auction;
+pub mod stability_pool;
+pub mod position_index;
+pub mod multisig;
+pub mod seed_lot;
 
 pub use protocol::*;
 pub use collateral::*;
-pub use oracle::*;
\ No newline at end of file
+pub use oracle::*;
+pub use price_history::*;
+pub use auction::*;
+pub use stability_pool::*;
+pub use position_index::*;
+pub use multisig::*;
+pub use seed_lot::*;
+
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+
+/// Deserializes an account passed through `remaining_accounts`, checking that
+/// it is owned by this program and carries the expected discriminator.
+pub fn load_program_account<T: AccountDeserialize>(info: &AccountInfo) -> Result<T> {
+    require_keys_eq!(*info.owner, crate::ID, ErrorCode::InvalidAccountOwner);
+    T::try_deserialize(&mut &info.try_borrow_data()?[..])
+}
+
+/// Writes back an account passed through `remaining_accounts`.
+pub fn save_program_account<T: AccountSerialize>(info: &AccountInfo, account: &T) -> Result<()> {
+    require_keys_eq!(*info.owner, crate::ID, ErrorCode::InvalidAccountOwner);
+    require!(info.is_writable, ErrorCode::MissingCollateralAccounts);
+    let mut data = info.try_borrow_mut_data()?;
+    account.try_serialize(&mut &mut data[..])
+}
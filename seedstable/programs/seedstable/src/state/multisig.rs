@@ -0,0 +1,175 @@
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::sysvar::instructions::{
+    load_current_index_checked, load_instruction_at_checked,
+};
+use crate::constants::*;
+use crate::error::ErrorCode;
+
+/// m-of-n signer set that can stand in for an authority key. Admin actions
+/// pass the multisig and have at least `threshold` of its owners sign.
+#[account]
+pub struct Multisig {
+    pub owners: [Pubkey; MAX_MULTISIG_OWNERS],
+    pub owner_count: u8,
+    pub threshold: u8,
+}
+
+impl Multisig {
+    pub const LEN: usize = 8 + // discriminator
+        32 * MAX_MULTISIG_OWNERS + // owners
+        1 + // owner_count
+        1; // threshold
+
+    pub fn set_owners(&mut self, owners: &[Pubkey], threshold: u8) -> Result<()> {
+        require!(
+            !owners.is_empty() && owners.len() <= MAX_MULTISIG_OWNERS,
+            ErrorCode::InvalidMultisig
+        );
+        require!(
+            threshold > 0 && threshold as usize <= owners.len(),
+            ErrorCode::InvalidMultisig
+        );
+        for (i, owner) in owners.iter().enumerate() {
+            require!(!owners[..i].contains(owner), ErrorCode::InvalidMultisig);
+        }
+
+        self.owners = [Pubkey::default(); MAX_MULTISIG_OWNERS];
+        self.owners[..owners.len()].copy_from_slice(owners);
+        self.owner_count = owners.len() as u8;
+        self.threshold = threshold;
+        Ok(())
+    }
+
+    pub fn active_owners(&self) -> &[Pubkey] {
+        &self.owners[..self.owner_count as usize]
+    }
+
+    /// Whether at least `threshold` owners signed the instruction being
+    /// executed, read from the instructions sysvar. The instruction must be
+    /// a top-level call into this program, and no other instruction in the
+    /// transaction may use the multisig at `multisig_key`, so one set of
+    /// signatures approves exactly one admin action.
+    pub fn approves(&self, multisig_key: &Pubkey, instructions: &AccountInfo) -> Result<bool> {
+        let current_index = load_current_index_checked(instructions)? as usize;
+        let current = load_instruction_at_checked(current_index, instructions)?;
+        // Reached through CPI, the top-level instruction belongs to another program
+        if current.program_id != crate::ID {
+            return Ok(false);
+        }
+
+        let mut index = 0;
+        while let Ok(other) = load_instruction_at_checked(index, instructions) {
+            if index != current_index
+                && other.program_id == crate::ID
+                && other.accounts.iter().any(|meta| meta.pubkey == *multisig_key)
+            {
+                return Ok(false);
+            }
+            index += 1;
+        }
+
+        let mut signed: Vec<Pubkey> = Vec::with_capacity(self.owner_count as usize);
+        for meta in current.accounts.iter().filter(|meta| meta.is_signer) {
+            if self.active_owners().contains(&meta.pubkey) && !signed.contains(&meta.pubkey) {
+                signed.push(meta.pubkey);
+            }
+        }
+        Ok(signed.len() >= self.threshold as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::sysvar::instructions::{
+        construct_instructions_data, store_current_index, BorrowedAccountMeta, BorrowedInstruction,
+    };
+
+    struct Call {
+        program_id: Pubkey,
+        signers: Vec<Pubkey>,
+        accounts: Vec<Pubkey>,
+    }
+
+    fn sysvar_data(calls: &[Call], current: u16) -> Vec<u8> {
+        let instructions: Vec<BorrowedInstruction> = calls
+            .iter()
+            .map(|call| BorrowedInstruction {
+                program_id: &call.program_id,
+                accounts: call
+                    .signers
+                    .iter()
+                    .map(|pubkey| BorrowedAccountMeta { pubkey, is_signer: true, is_writable: false })
+                    .chain(call.accounts.iter().map(|pubkey| BorrowedAccountMeta {
+                        pubkey,
+                        is_signer: false,
+                        is_writable: false,
+                    }))
+                    .collect(),
+                data: &[],
+            })
+            .collect();
+        let mut data = construct_instructions_data(&instructions);
+        store_current_index(&mut data, current);
+        data
+    }
+
+    fn approves(multisig: &Multisig, multisig_key: &Pubkey, calls: &[Call], current: u16) -> bool {
+        let key = anchor_lang::solana_program::sysvar::instructions::ID;
+        let owner = Pubkey::default();
+        let mut lamports = 1;
+        let mut data = sysvar_data(calls, current);
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        multisig.approves(multisig_key, &info).unwrap()
+    }
+
+    fn two_of_three() -> (Multisig, Vec<Pubkey>) {
+        let owners: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        let mut multisig = Multisig { owners: [Pubkey::default(); MAX_MULTISIG_OWNERS], owner_count: 0, threshold: 0 };
+        multisig.set_owners(&owners, 2).unwrap();
+        (multisig, owners)
+    }
+
+    #[test]
+    fn approves_once_threshold_owners_sign() {
+        let (multisig, owners) = two_of_three();
+        let key = Pubkey::new_unique();
+
+        let one = Call { program_id: crate::ID, signers: vec![owners[0]], accounts: vec![key] };
+        assert!(!approves(&multisig, &key, &[one], 0));
+
+        // A repeated or foreign signer does not count
+        let stranger = Call {
+            program_id: crate::ID,
+            signers: vec![owners[0], owners[0], Pubkey::new_unique()],
+            accounts: vec![key],
+        };
+        assert!(!approves(&multisig, &key, &[stranger], 0));
+
+        let two = Call { program_id: crate::ID, signers: vec![owners[0], owners[2]], accounts: vec![key] };
+        assert!(approves(&multisig, &key, &[two], 0));
+    }
+
+    #[test]
+    fn rejects_calls_reached_through_another_program() {
+        let (multisig, owners) = two_of_three();
+        let key = Pubkey::new_unique();
+
+        let outer = Call { program_id: Pubkey::new_unique(), signers: vec![owners[0], owners[1]], accounts: vec![key] };
+        assert!(!approves(&multisig, &key, &[outer], 0));
+    }
+
+    #[test]
+    fn approves_one_multisig_instruction_per_transaction() {
+        let (multisig, owners) = two_of_three();
+        let key = Pubkey::new_unique();
+        let admin = || Call { program_id: crate::ID, signers: vec![owners[0], owners[1]], accounts: vec![key] };
+
+        assert!(!approves(&multisig, &key, &[admin(), admin()], 1));
+
+        // Other instructions, including this program's, are fine without the multisig
+        let keeper = Call { program_id: crate::ID, signers: vec![owners[2]], accounts: vec![Pubkey::new_unique()] };
+        let other = Call { program_id: Pubkey::new_unique(), signers: vec![], accounts: vec![key] };
+        assert!(approves(&multisig, &key, &[keeper, admin(), other], 1));
+    }
+}
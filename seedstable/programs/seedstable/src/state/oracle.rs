@@ -1,13 +1,18 @@
 use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use crate::oracle::load_price_data;
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, PartialEq, Eq, Debug)]
 pub enum OracleType {
     Pyth,
     Switchboard,
+    /// Published price of a `SeedLotRegistry`
+    SeedLotRegistry,
 }
 
 #[account]
 pub struct OracleConfig {
+    pub collateral_pool: Pubkey,
     pub oracle_type: OracleType,
     pub price_feed: Pubkey,
     pub max_staleness: i64, // Maximum age of price data in seconds
@@ -19,6 +24,7 @@ pub struct OracleConfig {
 
 impl OracleConfig {
     pub const LEN: usize = 8 + // discriminator
+        32 + // collateral_pool
         1 + // oracle_type
         32 + // price_feed
         8 + // max_staleness
@@ -26,6 +32,12 @@ impl OracleConfig {
         1 + // decimals
         1 + // is_active
         1; // bump
+
+    pub fn load_price_data(&self, oracle_account: &AccountInfo) -> Result<PriceData> {
+        require!(self.is_active, ErrorCode::InvalidOracleConfig);
+        require_keys_eq!(oracle_account.key(), self.price_feed, ErrorCode::InvalidOracleConfig);
+        load_price_data(self.oracle_type, oracle_account)
+    }
 }
 
 #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
@@ -46,7 +58,14 @@ impl PriceData {
         if self.price == 0 {
             return false;
         }
-        let deviation_bps = (self.confidence * 10000) / self.price;
-        deviation_bps <= max_deviation
+        let deviation_bps = (self.confidence as u128 * 10000) / self.price as u128;
+        deviation_bps <= max_deviation as u128
+    }
+
+    pub fn validate(&self, max_staleness: i64, max_deviation: u64) -> Result<()> {
+        require!(self.is_valid, ErrorCode::InvalidOracleConfig);
+        require!(!self.is_stale(max_staleness), ErrorCode::StalePriceData);
+        require!(self.has_high_confidence(max_deviation), ErrorCode::LowConfidencePriceData);
+        Ok(())
     }
 }
@@ -0,0 +1,335 @@
+use anchor_lang::prelude::*;
+use crate::error::ErrorCode;
+use super::{load_program_account, save_program_account, UserPosition};
+
+/// Doubly linked list of `UserPosition`s with debt, sorted by nominal
+/// collateral ratio from the riskiest (`head`) up. Links live on the
+/// positions themselves; see `UserPosition::nominal_ratio`. The key does not
+/// depend on oracle prices, so only instructions that change a position's
+/// collateral or debt move it.
+#[account]
+pub struct PositionIndex {
+    pub protocol_state: Pubkey,
+    pub head: Pubkey, // Lowest nominal ratio
+    pub tail: Pubkey, // Highest nominal ratio
+    pub size: u64,
+    pub bump: u8,
+}
+
+impl PositionIndex {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // protocol_state
+        32 + // head
+        32 + // tail
+        8 + // size
+        1; // bump
+
+    /// Reads linked positions from `accounts` starting at the head, checking
+    /// each one follows the last. Returns `(position, nominal_ratio)` pairs,
+    /// riskiest first.
+    pub fn walk(&self, accounts: &[AccountInfo]) -> Result<Vec<(Pubkey, u64)>> {
+        let mut expected = self.head;
+        let mut positions = Vec::with_capacity(accounts.len());
+        for info in accounts {
+            require_keys_eq!(info.key(), expected, ErrorCode::PositionIndexMismatch);
+            let position: UserPosition = load_program_account(info)?;
+            positions.push((info.key(), position.sort_key));
+            expected = position.next_position;
+        }
+        Ok(positions)
+    }
+
+    /// Unlinks `position` and re-inserts it at its current nominal ratio
+    /// between `insert_prev` and `insert_next`, where `Pubkey::default()`
+    /// marks an end of the list. Its current neighbours and the insertion
+    /// neighbours are looked up by key in `accounts`. Positions without debt
+    /// are left out of the index.
+    pub fn reposition(
+        &mut self,
+        accounts: &[AccountInfo],
+        position_key: Pubkey,
+        position: &mut UserPosition,
+        insert_prev: Pubkey,
+        insert_next: Pubkey,
+    ) -> Result<()> {
+        if position.in_index {
+            self.unlink(accounts, position)?;
+        }
+
+        position.sort_key = position.nominal_ratio();
+        if position.normalized_debt == 0 {
+            return Ok(());
+        }
+        self.insert(accounts, position_key, position, insert_prev, insert_next)
+    }
+
+    fn unlink(&mut self, accounts: &[AccountInfo], position: &mut UserPosition) -> Result<()> {
+        let prev_key = position.prev_position;
+        let next_key = position.next_position;
+
+        if prev_key == Pubkey::default() {
+            self.head = next_key;
+        } else {
+            let info = find_account(accounts, prev_key)?;
+            let mut prev: UserPosition = load_program_account(info)?;
+            prev.next_position = next_key;
+            save_program_account(info, &prev)?;
+        }
+
+        if next_key == Pubkey::default() {
+            self.tail = prev_key;
+        } else {
+            let info = find_account(accounts, next_key)?;
+            let mut next: UserPosition = load_program_account(info)?;
+            next.prev_position = prev_key;
+            save_program_account(info, &next)?;
+        }
+
+        self.size = self.size.saturating_sub(1);
+        position.prev_position = Pubkey::default();
+        position.next_position = Pubkey::default();
+        position.in_index = false;
+        Ok(())
+    }
+
+    fn insert(
+        &mut self,
+        accounts: &[AccountInfo],
+        position_key: Pubkey,
+        position: &mut UserPosition,
+        prev_key: Pubkey,
+        next_key: Pubkey,
+    ) -> Result<()> {
+        require!(
+            prev_key != position_key && next_key != position_key,
+            ErrorCode::InvalidPositionIndexHint
+        );
+
+        if prev_key == Pubkey::default() {
+            require_keys_eq!(self.head, next_key, ErrorCode::PositionIndexMismatch);
+            self.head = position_key;
+        } else {
+            let info = find_account(accounts, prev_key)?;
+            let mut prev: UserPosition = load_program_account(info)?;
+            require!(
+                prev.in_index && prev.next_position == next_key,
+                ErrorCode::PositionIndexMismatch
+            );
+            require!(prev.sort_key <= position.sort_key, ErrorCode::InvalidPositionIndexHint);
+            prev.next_position = position_key;
+            save_program_account(info, &prev)?;
+        }
+
+        if next_key == Pubkey::default() {
+            require_keys_eq!(self.tail, prev_key, ErrorCode::PositionIndexMismatch);
+            self.tail = position_key;
+        } else {
+            let info = find_account(accounts, next_key)?;
+            let mut next: UserPosition = load_program_account(info)?;
+            require!(
+                next.in_index && next.prev_position == prev_key,
+                ErrorCode::PositionIndexMismatch
+            );
+            require!(position.sort_key <= next.sort_key, ErrorCode::InvalidPositionIndexHint);
+            next.prev_position = position_key;
+            save_program_account(info, &next)?;
+        }
+
+        self.size = self.size
+            .checked_add(1)
+            .ok_or(ErrorCode::MathOverflow)?;
+        position.prev_position = prev_key;
+        position.next_position = next_key;
+        position.in_index = true;
+        Ok(())
+    }
+}
+
+fn find_account<'a, 'info>(accounts: &'a [AccountInfo<'info>], key: Pubkey) -> Result<&'a AccountInfo<'info>> {
+    accounts
+        .iter()
+        .find(|info| info.key() == key)
+        .ok_or(ErrorCode::PositionIndexMismatch.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::state::{calculate_token_amount, calculate_usd_value, CollateralPoolPosition};
+
+    const DECIMALS: u8 = 6;
+
+    struct Slot {
+        key: Pubkey,
+        lamports: u64,
+        data: Vec<u8>,
+    }
+
+    fn slot(amount: u64, price: u64, normalized_debt: u64) -> Slot {
+        let pool = Pubkey::new_unique();
+        let position = UserPosition {
+            owner: Pubkey::new_unique(),
+            protocol_state: Pubkey::default(),
+            collateral_deposited: amount,
+            normalized_debt,
+            collateral_positions: vec![CollateralPoolPosition {
+                pool,
+                amount,
+                value_usd: calculate_usd_value(amount, price, DECIMALS).unwrap(),
+                normalized_debt,
+            }],
+            last_update_timestamp: 0,
+            sort_key: 0,
+            prev_position: Pubkey::default(),
+            next_position: Pubkey::default(),
+            in_index: false,
+            bump: 0,
+        };
+        let mut data = vec![0; UserPosition::LEN];
+        position.try_serialize(&mut &mut data[..]).unwrap();
+        Slot { key: Pubkey::new_unique(), lamports: 1, data }
+    }
+
+    fn infos(slots: &mut [Slot]) -> Vec<AccountInfo<'_>> {
+        slots
+            .iter_mut()
+            .map(|slot| {
+                AccountInfo::new(&slot.key, false, true, &mut slot.lamports, &mut slot.data, &crate::ID, false, 0)
+            })
+            .collect()
+    }
+
+    fn empty_index() -> PositionIndex {
+        PositionIndex {
+            protocol_state: Pubkey::default(),
+            head: Pubkey::default(),
+            tail: Pubkey::default(),
+            size: 0,
+            bump: 0,
+        }
+    }
+
+    /// Neighbours `key` should sit between at `sort_key`, found by walking
+    /// the list as an off-chain client would.
+    fn hints(index: &PositionIndex, accounts: &[AccountInfo], key: Pubkey, sort_key: u64) -> (Pubkey, Pubkey) {
+        let mut prev = Pubkey::default();
+        let mut cursor = index.head;
+        while cursor != Pubkey::default() {
+            let position: UserPosition = load_program_account(find_account(accounts, cursor).unwrap()).unwrap();
+            if cursor != key {
+                if position.sort_key > sort_key {
+                    break;
+                }
+                prev = cursor;
+            }
+            cursor = position.next_position;
+        }
+        (prev, cursor)
+    }
+
+    /// Applies `change` to the position at `at` and moves it to its new key.
+    fn update(index: &mut PositionIndex, accounts: &[AccountInfo], at: usize, change: impl FnOnce(&mut UserPosition)) {
+        let info = &accounts[at];
+        let mut position: UserPosition = load_program_account(info).unwrap();
+        change(&mut position);
+        let (prev, next) = hints(index, accounts, info.key(), position.nominal_ratio());
+        index.reposition(accounts, info.key(), &mut position, prev, next).unwrap();
+        save_program_account(info, &position).unwrap();
+    }
+
+    fn walk_keys(index: &PositionIndex, accounts: &[AccountInfo]) -> Vec<Pubkey> {
+        let mut ordered = Vec::new();
+        let mut cursor = index.head;
+        while cursor != Pubkey::default() {
+            let info = find_account(accounts, cursor).unwrap();
+            let position: UserPosition = load_program_account(info).unwrap();
+            ordered.push(info.clone());
+            cursor = position.next_position;
+        }
+        let walked = index.walk(&ordered).unwrap();
+        for (info, (key, sort_key)) in ordered.iter().zip(&walked) {
+            let position: UserPosition = load_program_account(info).unwrap();
+            assert_eq!(*sort_key, position.nominal_ratio(), "stale key for {}", key);
+        }
+        assert!(walked.windows(2).all(|pair| pair[0].1 <= pair[1].1));
+        assert_eq!(walked.len() as u64, index.size);
+        walked.into_iter().map(|(key, _)| key).collect()
+    }
+
+    #[test]
+    fn inserts_in_nominal_ratio_order() {
+        // 2000, 1500 and 1800 of collateral at $1 against 1000 debt each
+        let mut slots = vec![
+            slot(2_000_000_000, 1_000_000, 1_000_000_000),
+            slot(1_500_000_000, 1_000_000, 1_000_000_000),
+            slot(1_800_000_000, 1_000_000, 1_000_000_000),
+        ];
+        let keys: Vec<Pubkey> = slots.iter().map(|slot| slot.key).collect();
+        let accounts = infos(&mut slots);
+        let mut index = empty_index();
+        for at in 0..accounts.len() {
+            update(&mut index, &accounts, at, |_| {});
+        }
+
+        assert_eq!(walk_keys(&index, &accounts), vec![keys[1], keys[2], keys[0]]);
+        assert_eq!(index.head, keys[1]);
+        assert_eq!(index.tail, keys[0]);
+    }
+
+    #[test]
+    fn price_change_keeps_order_until_positions_change() {
+        let mut slots = vec![
+            slot(2_000_000_000, 1_000_000, 1_000_000_000),
+            slot(1_500_000_000, 1_000_000, 1_000_000_000),
+            slot(1_800_000_000, 1_000_000, 1_000_000_000),
+        ];
+        let keys: Vec<Pubkey> = slots.iter().map(|slot| slot.key).collect();
+        let accounts = infos(&mut slots);
+        let mut index = empty_index();
+        for at in 0..accounts.len() {
+            update(&mut index, &accounts, at, |_| {});
+        }
+
+        // A price drop does not touch book values, so keys stay valid
+        let price = 800_000;
+        assert_eq!(walk_keys(&index, &accounts), vec![keys[1], keys[2], keys[0]]);
+
+        // Liquidating the riskiest at the new price: 750 of debt repaid for
+        // 825 of collateral value with a 10% bonus
+        update(&mut index, &accounts, 1, |position| {
+            let seized = calculate_token_amount(825_000_000, price, DECIMALS).unwrap();
+            let pool_position = &mut position.collateral_positions[0];
+            let book_value = ((pool_position.value_usd as u128 * seized as u128)
+                / pool_position.amount as u128) as u64;
+            pool_position.amount -= seized;
+            pool_position.value_usd -= book_value;
+            pool_position.normalized_debt -= 750_000_000;
+            position.normalized_debt -= 750_000_000;
+        });
+        assert_eq!(walk_keys(&index, &accounts), vec![keys[2], keys[1], keys[0]]);
+
+        // Repaying all of a position's debt takes it out of the index
+        update(&mut index, &accounts, 2, |position| {
+            position.collateral_positions[0].normalized_debt = 0;
+            position.normalized_debt = 0;
+        });
+        assert_eq!(walk_keys(&index, &accounts), vec![keys[1], keys[0]]);
+    }
+
+    #[test]
+    fn rejects_out_of_order_hints() {
+        let mut slots = vec![
+            slot(2_000_000_000, 1_000_000, 1_000_000_000),
+            slot(1_500_000_000, 1_000_000, 1_000_000_000),
+        ];
+        let keys: Vec<Pubkey> = slots.iter().map(|slot| slot.key).collect();
+        let accounts = infos(&mut slots);
+        let mut index = empty_index();
+        update(&mut index, &accounts, 0, |_| {});
+
+        // The riskier position may not be placed after the safer one
+        let mut position: UserPosition = load_program_account(&accounts[1]).unwrap();
+        let result = index.reposition(&accounts, keys[1], &mut position, keys[0], Pubkey::default());
+        assert_eq!(result.unwrap_err(), ErrorCode::InvalidPositionIndexHint.into());
+    }
+}
@@ -0,0 +1,226 @@
+use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct PriceObservation {
+    pub price: u64,
+    pub timestamp: i64,
+}
+
+impl PriceObservation {
+    pub const LEN: usize = 8 + // price
+        8; // timestamp
+}
+
+#[account]
+pub struct PriceHistory {
+    pub collateral_pool: Pubkey,
+    pub observations: [PriceObservation; PRICE_HISTORY_CAPACITY],
+    pub head: u16, // Index of the next write
+    pub count: u16,
+    pub twap_window: i64, // Seconds, also the EMA period
+    pub ema_price: u64,
+    pub bump: u8,
+}
+
+impl PriceHistory {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // collateral_pool
+        PriceObservation::LEN * PRICE_HISTORY_CAPACITY + // observations
+        2 + // head
+        2 + // count
+        8 + // twap_window
+        8 + // ema_price
+        1; // bump
+
+    pub fn latest(&self) -> Option<&PriceObservation> {
+        if self.count == 0 {
+            return None;
+        }
+        let index = (self.head as usize + PRICE_HISTORY_CAPACITY - 1) % PRICE_HISTORY_CAPACITY;
+        Some(&self.observations[index])
+    }
+
+    /// Shortest gap between recorded observations, so a full buffer always
+    /// spans at least the TWAP window.
+    pub fn min_observation_spacing(&self) -> i64 {
+        (self.twap_window / PRICE_HISTORY_CAPACITY as i64).max(1)
+    }
+
+    /// Appends an observation and folds it into the EMA. Observations less
+    /// than `min_observation_spacing` after the latest one are ignored, so
+    /// rapid updates cannot flush the buffer and pull the TWAP to spot.
+    pub fn record(&mut self, price: u64, timestamp: i64) -> Result<()> {
+        if let Some(latest) = self.latest().copied() {
+            if timestamp - latest.timestamp < self.min_observation_spacing() {
+                return Ok(());
+            }
+            let elapsed = (timestamp - latest.timestamp).min(self.twap_window) as i128;
+            let delta = price as i128 - self.ema_price as i128;
+            let ema = self.ema_price as i128 + delta * elapsed / self.twap_window as i128;
+            self.ema_price = u64::try_from(ema).map_err(|_| ErrorCode::MathOverflow)?;
+        } else {
+            self.ema_price = price;
+        }
+
+        self.observations[self.head as usize] = PriceObservation { price, timestamp };
+        self.head = ((self.head as usize + 1) % PRICE_HISTORY_CAPACITY) as u16;
+        self.count = (self.count as usize + 1).min(PRICE_HISTORY_CAPACITY) as u16;
+        Ok(())
+    }
+
+    /// Time-weighted average over `[now - twap_window, now]`. Each observation
+    /// holds until the next one; history older than the buffer is not counted.
+    pub fn twap(&self, now: i64) -> Result<u64> {
+        require!(self.count > 0, ErrorCode::StalePriceData);
+
+        let window_start = now - self.twap_window;
+        let mut weighted_sum: u128 = 0;
+        let mut total_time: u128 = 0;
+        let mut period_end = now;
+
+        for offset in 1..=self.count as usize {
+            let index = (self.head as usize + PRICE_HISTORY_CAPACITY - offset) % PRICE_HISTORY_CAPACITY;
+            let observation = &self.observations[index];
+            let period_start = observation.timestamp.max(window_start);
+            if period_end > period_start {
+                let duration = (period_end - period_start) as u128;
+                weighted_sum += observation.price as u128 * duration;
+                total_time += duration;
+            }
+            if observation.timestamp <= window_start {
+                break;
+            }
+            period_end = observation.timestamp;
+        }
+
+        if total_time == 0 {
+            return self.latest().map(|obs| obs.price).ok_or(ErrorCode::StalePriceData.into());
+        }
+        u64::try_from(weighted_sum / total_time).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    pub fn ema(&self) -> Result<u64> {
+        require!(self.count > 0, ErrorCode::StalePriceData);
+        Ok(self.ema_price)
+    }
+
+    /// The lower of spot and TWAP, for valuing collateral conservatively.
+    pub fn conservative_price(&self, spot_price: u64, now: i64) -> Result<u64> {
+        Ok(spot_price.min(self.twap(now)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn price_history(twap_window: i64) -> PriceHistory {
+        PriceHistory {
+            collateral_pool: Pubkey::default(),
+            observations: [PriceObservation::default(); PRICE_HISTORY_CAPACITY],
+            head: 0,
+            count: 0,
+            twap_window,
+            ema_price: 0,
+            bump: 0,
+        }
+    }
+
+    #[test]
+    fn empty_history_has_no_price() {
+        let history = price_history(100);
+        assert!(history.latest().is_none());
+        assert_eq!(history.twap(0).unwrap_err(), ErrorCode::StalePriceData.into());
+        assert_eq!(history.ema().unwrap_err(), ErrorCode::StalePriceData.into());
+    }
+
+    #[test]
+    fn ignores_observations_that_are_not_newer() {
+        let mut history = price_history(100);
+        history.record(100, 10).unwrap();
+        history.record(500, 10).unwrap();
+        history.record(500, 5).unwrap();
+
+        assert_eq!(history.count, 1);
+        assert_eq!(history.latest().unwrap().price, 100);
+        assert_eq!(history.ema().unwrap(), 100);
+    }
+
+    #[test]
+    fn twap_weights_prices_by_how_long_they_held() {
+        let mut history = price_history(100);
+        history.record(100, 0).unwrap();
+        history.record(200, 50).unwrap();
+
+        assert_eq!(history.twap(100).unwrap(), 150);
+        // The first observation has fallen out of the window
+        assert_eq!(history.twap(150).unwrap(), 200);
+        // Only the first observation had been seen a quarter into the window
+        assert_eq!(history.twap(25).unwrap(), 100);
+    }
+
+    #[test]
+    fn ema_moves_towards_new_prices_over_the_window() {
+        let mut history = price_history(100);
+        history.record(100, 0).unwrap();
+        assert_eq!(history.ema().unwrap(), 100);
+
+        history.record(200, 50).unwrap();
+        assert_eq!(history.ema().unwrap(), 150);
+
+        // Gaps longer than the window snap to the new price
+        history.record(50, 1_000).unwrap();
+        assert_eq!(history.ema().unwrap(), 50);
+    }
+
+    #[test]
+    fn ring_buffer_keeps_the_latest_observations() {
+        let mut history = price_history(10 * PRICE_HISTORY_CAPACITY as i64);
+        let total = PRICE_HISTORY_CAPACITY as i64 + 8;
+        for step in 0..total {
+            history.record(1_000 + step as u64, step * 10).unwrap();
+        }
+
+        assert_eq!(history.count as usize, PRICE_HISTORY_CAPACITY);
+        assert_eq!(history.latest().unwrap().price, 1_000 + total as u64 - 1);
+
+        // Overwritten observations no longer count towards the TWAP
+        let now = (total - 1) * 10 + 10;
+        let oldest = 1_000 + (total - PRICE_HISTORY_CAPACITY as i64) as u64;
+        let newest = 1_000 + total as u64 - 1;
+        assert_eq!(history.twap(now).unwrap(), (oldest + newest) / 2);
+    }
+
+    #[test]
+    fn rapid_updates_leave_the_twap_unchanged() {
+        let mut history = price_history(10 * PRICE_HISTORY_CAPACITY as i64);
+        assert_eq!(history.min_observation_spacing(), 10);
+        for step in 0..4 {
+            history.record(100, step * 10).unwrap();
+        }
+        let twap = history.twap(39).unwrap();
+
+        // A burst of manipulated prices inside one spacing is not recorded
+        for timestamp in 31..40 {
+            history.record(1_000, timestamp).unwrap();
+        }
+        assert_eq!(history.count, 4);
+        assert_eq!(history.twap(39).unwrap(), twap);
+        assert_eq!(history.ema().unwrap(), 100);
+
+        // The next spaced observation is
+        history.record(1_000, 40).unwrap();
+        assert_eq!(history.count, 5);
+    }
+
+    #[test]
+    fn conservative_price_is_the_lower_of_spot_and_twap() {
+        let mut history = price_history(100);
+        history.record(100, 0).unwrap();
+
+        assert_eq!(history.conservative_price(150, 50).unwrap(), 100);
+        assert_eq!(history.conservative_price(80, 50).unwrap(), 80);
+    }
+}
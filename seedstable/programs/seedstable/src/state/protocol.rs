@@ -1,4 +1,8 @@
 use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use crate::events::RecoveryModeChanged;
+use super::CollateralValuation;
 
 #[account]
 pub struct ProtocolState {
@@ -10,8 +14,33 @@ pub struct ProtocolState {
     pub collateral_ratio: u64, // Basis points (150% = 15000)
     pub min_collateral_ratio: u64, // Minimum 150% = 15000 basis points
     pub peg_deviation_threshold: u64, // 2% = 200 basis points
-    pub is_paused: bool,
+    pub paused_operations: u16, // PAUSE_* flags
     pub last_rebalance_timestamp: i64,
+    pub stability_fee: u64, // Annualized basis points charged on debt
+    pub rate_index: u128, // Cumulative debt multiplier, RATE_INDEX_PRECISION = 1.0
+    pub last_fee_accrual_timestamp: i64,
+    pub total_normalized_debt: u64, // Sum of position debt divided by rate_index
+    pub accrued_fees: u64, // Fees accrued but not yet minted to the treasury, already in total_stablecoin_supply
+    pub close_factor: u64, // Max share of a position's debt repaid per liquidation, basis points
+    pub total_bad_debt: u64, // Debt written off from positions left without collateral
+    pub total_auction_debt: u64, // Debt moved out of positions into running auctions
+    pub redemption_base_rate: u64, // Basis points, raised by redemptions and decaying over time
+    pub last_redemption_timestamp: i64,
+    pub recovery_mode: bool, // Set while collateral_ratio is below min_collateral_ratio
+    pub recovery_mode_since: i64,
+    pub collateral_pool_count: u64,
+    pub global_settlement: bool, // One-way; set when the protocol is shut down
+    pub settlement_timestamp: i64,
+    pub settlement_debt: u64, // Position debt outstanding when settlement was triggered
+    pub settled_debt: u64, // Position debt settled against frozen prices so far
+    pub settlement_shortfall: u64, // Settled debt not covered by collateral
+    pub settlement_pools_frozen: u64, // Collateral pools with a final price
+    pub settlement_redeemed: u64, // SEEDS burned for collateral baskets
+    pub flash_mint_cap: u64, // Max SEEDS per flash mint, 0 disables flash mints
+    pub flash_mint_fee: u64, // Basis points of the flash-minted amount
+    pub flash_mint_outstanding: u64, // Flash-minted SEEDS awaiting repayment in this transaction
+    pub pending_authority: Pubkey, // Proposed authority, default when no transfer is pending
+    pub guardian: Pubkey, // May pause operations but not unpause them
     pub bump: u8,
 }
 
@@ -25,21 +54,188 @@ impl ProtocolState {
         8 + // collateral_ratio
         8 + // min_collateral_ratio
         8 + // peg_deviation_threshold
-        1 + // is_paused
+        2 + // paused_operations
         8 + // last_rebalance_timestamp
+        8 + // stability_fee
+        16 + // rate_index
+        8 + // last_fee_accrual_timestamp
+        8 + // total_normalized_debt
+        8 + // accrued_fees
+        8 + // close_factor
+        8 + // total_bad_debt
+        8 + // total_auction_debt
+        8 + // redemption_base_rate
+        8 + // last_redemption_timestamp
+        1 + // recovery_mode
+        8 + // recovery_mode_since
+        8 + // collateral_pool_count
+        1 + // global_settlement
+        8 + // settlement_timestamp
+        8 + // settlement_debt
+        8 + // settled_debt
+        8 + // settlement_shortfall
+        8 + // settlement_pools_frozen
+        8 + // settlement_redeemed
+        8 + // flash_mint_cap
+        8 + // flash_mint_fee
+        8 + // flash_mint_outstanding
+        32 + // pending_authority
+        32 + // guardian
         1; // bump
 
+    /// Total collateral value over supply in basis points, `u64::MAX` without supply.
     pub fn calculate_collateral_ratio(&self) -> u64 {
         if self.total_stablecoin_supply == 0 {
             return u64::MAX;
         }
-        (self.total_collateral_value * 10000) / self.total_stablecoin_supply
+        let ratio = (self.total_collateral_value as u128 * BASIS_POINTS as u128)
+            / self.total_stablecoin_supply as u128;
+        u64::try_from(ratio).unwrap_or(u64::MAX)
     }
 
     pub fn is_undercollateralized(&self) -> bool {
         self.calculate_collateral_ratio() < self.min_collateral_ratio
     }
 
+    /// Whether any of the `PAUSE_*` flags in `operations` is set.
+    pub fn is_paused(&self, operations: u16) -> bool {
+        self.paused_operations & operations != 0
+    }
+
+    /// Recomputes the system collateral ratio, entering or leaving recovery
+    /// mode when it crosses `min_collateral_ratio`.
+    pub fn refresh_collateral_ratio(&mut self, now: i64) {
+        self.collateral_ratio = self.calculate_collateral_ratio();
+        let undercollateralized = self.is_undercollateralized();
+        if undercollateralized == self.recovery_mode {
+            return;
+        }
+
+        self.recovery_mode = undercollateralized;
+        self.recovery_mode_since = if undercollateralized { now } else { 0 };
+        emit!(RecoveryModeChanged {
+            recovery_mode: undercollateralized,
+            collateral_ratio: self.collateral_ratio,
+            min_collateral_ratio: self.min_collateral_ratio,
+            timestamp: now,
+        });
+        msg!(
+            "Recovery mode {} at system collateral ratio {}",
+            if undercollateralized { "entered" } else { "left" },
+            self.collateral_ratio
+        );
+    }
+
+    /// Whether a position can be liquidated: past its liquidation thresholds
+    /// or, in recovery mode, below the system collateral ratio.
+    pub fn is_liquidatable(&self, valuation: &CollateralValuation, debt: u64) -> bool {
+        valuation.is_liquidatable(debt)
+            || (self.recovery_mode && valuation.collateral_ratio(debt) < self.collateral_ratio)
+    }
+
+    /// Grows the rate index by the stability fee for the time since the last
+    /// accrual and books the resulting fees on the outstanding debt. The fees
+    /// count towards the supply as they accrue, so repaying principal plus
+    /// fees never burns more than the supply tracks.
+    pub fn accrue_stability_fee(&mut self, now: i64) -> Result<()> {
+        // Debt stops growing once settlement is triggered
+        if self.global_settlement {
+            return Ok(());
+        }
+        let elapsed = now.saturating_sub(self.last_fee_accrual_timestamp);
+        if elapsed <= 0 {
+            return Ok(());
+        }
+        self.last_fee_accrual_timestamp = now;
+        if self.stability_fee == 0 {
+            return Ok(());
+        }
+
+        let growth = self.rate_index
+            .checked_mul(self.stability_fee as u128 * elapsed as u128)
+            .ok_or(ErrorCode::MathOverflow)?
+            / (BASIS_POINTS as u128 * SECONDS_PER_YEAR as u128);
+        let old_debt = self.debt_for(self.total_normalized_debt)?;
+        self.rate_index = self.rate_index
+            .checked_add(growth)
+            .ok_or(ErrorCode::MathOverflow)?;
+        let new_debt = self.debt_for(self.total_normalized_debt)?;
+
+        let fees = new_debt.saturating_sub(old_debt);
+        self.accrued_fees = self.accrued_fees
+            .checked_add(fees)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.total_stablecoin_supply = self.total_stablecoin_supply
+            .checked_add(fees)
+            .ok_or(ErrorCode::MathOverflow)?;
+        Ok(())
+    }
+
+    /// Debt owed for a normalized amount at the current index, rounded up.
+    pub fn debt_for(&self, normalized_debt: u64) -> Result<u64> {
+        let debt = (normalized_debt as u128 * self.rate_index)
+            .div_ceil(RATE_INDEX_PRECISION);
+        u64::try_from(debt).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Normalized amount for `debt` at the current index. Rounds up when
+    /// borrowing and down when repaying so rounding never favours the borrower.
+    pub fn normalize_debt(&self, debt: u64, round_up: bool) -> Result<u64> {
+        let scaled = debt as u128 * RATE_INDEX_PRECISION;
+        let normalized = if round_up {
+            scaled.div_ceil(self.rate_index)
+        } else {
+            scaled / self.rate_index
+        };
+        u64::try_from(normalized).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+
+    /// Normalized debt cleared by repaying `amount` against `normalized_debt`;
+    /// repaying the full amount owed clears it exactly.
+    pub fn normalize_repayment(&self, normalized_debt: u64, amount: u64) -> Result<u64> {
+        if amount >= self.debt_for(normalized_debt)? {
+            return Ok(normalized_debt);
+        }
+        self.normalize_debt(amount, false)
+    }
+
+    /// Most debt a single liquidation may repay. Positions with a health
+    /// factor below `FULL_LIQUIDATION_HEALTH_FACTOR` can be closed in one call.
+    pub fn max_liquidation_repay(&self, debt: u64, health_factor: u64) -> u64 {
+        if health_factor < FULL_LIQUIDATION_HEALTH_FACTOR {
+            return debt;
+        }
+        ((debt as u128 * self.close_factor as u128) / BASIS_POINTS as u128) as u64
+    }
+
+    /// Base rate after halving every `REDEMPTION_HALF_LIFE` since the last redemption.
+    pub fn decayed_redemption_base_rate(&self, now: i64) -> u64 {
+        let elapsed = now.saturating_sub(self.last_redemption_timestamp).max(0);
+        let halvings = elapsed / REDEMPTION_HALF_LIFE;
+        if halvings >= 64 {
+            return 0;
+        }
+        let rate = self.redemption_base_rate >> halvings;
+        // Linear between halvings
+        let partial = elapsed % REDEMPTION_HALF_LIFE;
+        rate - (rate as u128 * partial as u128 / (2 * REDEMPTION_HALF_LIFE) as u128) as u64
+    }
+
+    /// Raises the base rate in proportion to the share of supply redeemed and
+    /// returns the fee charged on this redemption.
+    pub fn update_redemption_rate(&mut self, redeemed: u64, now: i64) -> u64 {
+        let supply = self.total_stablecoin_supply.max(1) as u128;
+        let increase = (redeemed as u128 * BASIS_POINTS as u128 / (REDEMPTION_BETA as u128 * supply)) as u64;
+        self.redemption_base_rate = self.decayed_redemption_base_rate(now)
+            .saturating_add(increase)
+            .min(BASIS_POINTS);
+        self.last_redemption_timestamp = now;
+
+        self.redemption_base_rate
+            .saturating_add(REDEMPTION_FEE_FLOOR)
+            .min(MAX_REDEMPTION_FEE)
+    }
+
     pub fn needs_rebalancing(&self, current_price: u64, target_price: u64) -> bool {
         let deviation = if current_price > target_price {
             ((current_price - target_price) * 10000) / target_price
@@ -49,3 +245,146 @@ impl ProtocolState {
         deviation > self.peg_deviation_threshold
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn protocol_state() -> ProtocolState {
+        let mut state = ProtocolState::deserialize(&mut &vec![0; ProtocolState::LEN - 8][..]).unwrap();
+        state.rate_index = RATE_INDEX_PRECISION;
+        state.min_collateral_ratio = 15000;
+        state
+    }
+
+    #[test]
+    fn collateral_ratio_saturates_instead_of_overflowing() {
+        let mut state = protocol_state();
+        assert_eq!(state.calculate_collateral_ratio(), u64::MAX);
+
+        state.total_collateral_value = 3_000_000;
+        state.total_stablecoin_supply = 2_000_000;
+        assert_eq!(state.calculate_collateral_ratio(), 15000);
+
+        // Overflowed u64 before the multiplication moved to u128
+        state.total_collateral_value = u64::MAX / 2;
+        state.total_stablecoin_supply = 1_000;
+        assert_eq!(state.calculate_collateral_ratio(), u64::MAX);
+    }
+
+    #[test]
+    fn accrued_fees_join_the_supply() {
+        let mut state = protocol_state();
+        state.stability_fee = 200;
+        state.total_normalized_debt = 1_000_000_000;
+        state.total_stablecoin_supply = 1_000_000_000;
+
+        state.accrue_stability_fee(SECONDS_PER_YEAR).unwrap();
+        assert_eq!(state.accrued_fees, 20_000_000);
+        assert_eq!(state.total_stablecoin_supply, 1_020_000_000);
+
+        // Repaying principal plus fees burns exactly the tracked supply
+        let debt = state.debt_for(state.total_normalized_debt).unwrap();
+        assert_eq!(debt, state.total_stablecoin_supply);
+    }
+
+    #[test]
+    fn accrual_grows_the_rate_index_once_per_interval() {
+        let mut state = protocol_state();
+        state.stability_fee = 200;
+        state.total_normalized_debt = 1_000_000_000;
+        state.last_fee_accrual_timestamp = 100;
+
+        // No time passed, or the clock went backwards
+        state.accrue_stability_fee(100).unwrap();
+        state.accrue_stability_fee(50).unwrap();
+        assert_eq!(state.rate_index, RATE_INDEX_PRECISION);
+        assert_eq!(state.last_fee_accrual_timestamp, 100);
+
+        state.accrue_stability_fee(100 + SECONDS_PER_YEAR / 2).unwrap();
+        assert_eq!(state.rate_index, RATE_INDEX_PRECISION * 101 / 100);
+        assert_eq!(state.accrued_fees, 10_000_000);
+    }
+
+    #[test]
+    fn accrual_without_debt_or_fee_books_nothing() {
+        let mut state = protocol_state();
+        state.stability_fee = 200;
+
+        // Zero supply and debt: the index still grows for future borrowers
+        state.accrue_stability_fee(SECONDS_PER_YEAR).unwrap();
+        assert_eq!(state.rate_index, RATE_INDEX_PRECISION * 102 / 100);
+        assert_eq!(state.accrued_fees, 0);
+        assert_eq!(state.total_stablecoin_supply, 0);
+
+        // A zero fee only moves the accrual timestamp
+        state.stability_fee = 0;
+        state.total_normalized_debt = 1_000_000;
+        let rate_index = state.rate_index;
+        state.accrue_stability_fee(2 * SECONDS_PER_YEAR).unwrap();
+        assert_eq!(state.rate_index, rate_index);
+        assert_eq!(state.last_fee_accrual_timestamp, 2 * SECONDS_PER_YEAR);
+    }
+
+    #[test]
+    fn accrual_stops_at_global_settlement() {
+        let mut state = protocol_state();
+        state.stability_fee = 200;
+        state.total_normalized_debt = 1_000_000_000;
+        state.global_settlement = true;
+
+        state.accrue_stability_fee(SECONDS_PER_YEAR).unwrap();
+        assert_eq!(state.rate_index, RATE_INDEX_PRECISION);
+        assert_eq!(state.accrued_fees, 0);
+    }
+
+    #[test]
+    fn debt_rounds_against_the_borrower() {
+        let mut state = protocol_state();
+        state.rate_index = RATE_INDEX_PRECISION * 3 / 2;
+
+        assert_eq!(state.debt_for(3).unwrap(), 5);
+        assert_eq!(state.normalize_debt(5, true).unwrap(), 4);
+        assert_eq!(state.normalize_debt(5, false).unwrap(), 3);
+
+        // Repaying what is owed clears the normalized debt exactly
+        assert_eq!(state.normalize_repayment(3, 5).unwrap(), 3);
+        assert_eq!(state.normalize_repayment(3, 4).unwrap(), 2);
+    }
+
+    #[test]
+    fn redemption_fee_follows_the_redeemed_share() {
+        let mut state = protocol_state();
+        state.total_stablecoin_supply = 1_000_000;
+
+        // 1% of supply raises the base rate by 1% / REDEMPTION_BETA
+        assert_eq!(state.update_redemption_rate(10_000, 0), 50 + REDEMPTION_FEE_FLOOR);
+        assert_eq!(state.redemption_base_rate, 50);
+
+        // A large redemption is capped at the maximum fee
+        assert_eq!(state.update_redemption_rate(500_000, 0), MAX_REDEMPTION_FEE);
+        assert_eq!(state.redemption_base_rate, 2_550);
+    }
+
+    #[test]
+    fn redemption_fee_with_zero_supply_is_capped() {
+        let mut state = protocol_state();
+        assert_eq!(state.update_redemption_rate(100, 0), MAX_REDEMPTION_FEE);
+        assert_eq!(state.redemption_base_rate, BASIS_POINTS);
+    }
+
+    #[test]
+    fn redemption_base_rate_decays() {
+        let mut state = protocol_state();
+        state.redemption_base_rate = 50;
+        state.last_redemption_timestamp = 1_000;
+
+        assert_eq!(state.decayed_redemption_base_rate(1_000), 50);
+        assert_eq!(state.decayed_redemption_base_rate(1_000 + REDEMPTION_HALF_LIFE / 2), 38);
+        assert_eq!(state.decayed_redemption_base_rate(1_000 + REDEMPTION_HALF_LIFE), 25);
+        assert_eq!(state.decayed_redemption_base_rate(1_000 + 64 * REDEMPTION_HALF_LIFE), 0);
+
+        // Fully decayed, only the floor is charged
+        assert_eq!(state.update_redemption_rate(0, 1_000 + 64 * REDEMPTION_HALF_LIFE), REDEMPTION_FEE_FLOOR);
+    }
+}
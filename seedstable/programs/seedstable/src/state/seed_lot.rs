@@ -0,0 +1,199 @@
+use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+use super::PriceData;
+
+/// Registry of the seed lots backing one tokenized seeds mint. Attestors
+/// register lots and mint their tokens; the registry publishes a
+/// quantity-weighted, haircut price that a `SeedLotRegistry` oracle source
+/// feeds into the collateral pool.
+///
+/// Pricing runs in rounds so any number of lots can be folded in over several
+/// `refresh_seed_lot_registry` calls: each call adds the next lots to the
+/// pending sums, and the round's price is published once every lot is in.
+#[account]
+pub struct SeedLotRegistry {
+    pub mint: Pubkey, // Tokenized seeds mint, minted by this registry's PDA
+    pub attestors: [Pubkey; MAX_SEED_LOT_ATTESTORS],
+    pub attestor_count: u8,
+    pub lot_count: u64,
+    pub total_quantity: u64, // Tokens minted across all lots
+    pub price: u64, // Last published price per whole token, USD_DECIMALS
+    pub price_timestamp: i64, // Start of the round that produced `price`
+    pub next_lot: u64, // Next lot id to fold into the pending round
+    pub pending_value: u128, // Sum of quantity * haircut price this round
+    pub pending_quantity: u64,
+    pub round_started: i64,
+    pub bump: u8,
+}
+
+impl SeedLotRegistry {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // mint
+        32 * MAX_SEED_LOT_ATTESTORS + // attestors
+        1 + // attestor_count
+        8 + // lot_count
+        8 + // total_quantity
+        8 + // price
+        8 + // price_timestamp
+        8 + // next_lot
+        16 + // pending_value
+        8 + // pending_quantity
+        8 + // round_started
+        1; // bump
+
+    pub fn active_attestors(&self) -> &[Pubkey] {
+        &self.attestors[..self.attestor_count as usize]
+    }
+
+    pub fn is_attestor(&self, key: &Pubkey) -> bool {
+        self.active_attestors().contains(key)
+    }
+
+    pub fn add_attestor(&mut self, attestor: Pubkey) -> Result<()> {
+        require!(!self.is_attestor(&attestor), ErrorCode::InvalidAttestor);
+        let index = self.attestor_count as usize;
+        require!(index < MAX_SEED_LOT_ATTESTORS, ErrorCode::InvalidAttestor);
+        self.attestors[index] = attestor;
+        self.attestor_count += 1;
+        Ok(())
+    }
+
+    pub fn remove_attestor(&mut self, attestor: &Pubkey) -> Result<()> {
+        let index = self
+            .active_attestors()
+            .iter()
+            .position(|key| key == attestor)
+            .ok_or(ErrorCode::InvalidAttestor)?;
+        let last = self.attestor_count as usize - 1;
+        self.attestors[index] = self.attestors[last];
+        self.attestors[last] = Pubkey::default();
+        self.attestor_count -= 1;
+        Ok(())
+    }
+
+    /// Starts a new pricing round unless one is in progress and still fresh.
+    pub fn begin_round(&mut self, now: i64) {
+        if self.next_lot == 0 || now - self.round_started > MAX_PRICE_STALENESS {
+            self.next_lot = 0;
+            self.pending_value = 0;
+            self.pending_quantity = 0;
+            self.round_started = now;
+        }
+    }
+
+    /// Adds the next lot in id order to the pending round.
+    pub fn fold_lot(&mut self, registry: &Pubkey, lot: &SeedLot, now: i64) -> Result<()> {
+        require!(
+            lot.registry == *registry && lot.lot_id == self.next_lot,
+            ErrorCode::InvalidSeedLot
+        );
+        let value = lot.quantity as u128 * lot.haircut_price(now) as u128;
+        self.pending_value = self.pending_value
+            .checked_add(value)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.pending_quantity = self.pending_quantity
+            .checked_add(lot.quantity)
+            .ok_or(ErrorCode::MathOverflow)?;
+        self.next_lot += 1;
+        Ok(())
+    }
+
+    /// Publishes the round's price once every lot has been folded in.
+    pub fn finish_round(&mut self) -> bool {
+        if self.next_lot < self.lot_count {
+            return false;
+        }
+        self.price = if self.pending_quantity == 0 {
+            0
+        } else {
+            (self.pending_value / self.pending_quantity as u128) as u64
+        };
+        self.price_timestamp = self.round_started;
+        self.next_lot = 0;
+        self.pending_value = 0;
+        self.pending_quantity = 0;
+        true
+    }
+
+    /// Last published price in oracle form; invalid until a round completes.
+    pub fn price_data(&self) -> PriceData {
+        PriceData {
+            price: self.price,
+            confidence: 0,
+            timestamp: self.price_timestamp,
+            is_valid: self.price > 0,
+        }
+    }
+}
+
+/// One warehouse lot of seed, attested by an authorized attestor. Its tokens
+/// are minted to the depositor when the lot is registered.
+#[account]
+pub struct SeedLot {
+    pub registry: Pubkey,
+    pub lot_id: u64,
+    pub attestor: Pubkey, // Last attestor to price the lot
+    pub crop: String,
+    pub variety: String,
+    pub warehouse: String,
+    pub harvest_date: i64,
+    pub germination_rate: u64, // Basis points
+    pub quantity: u64, // Tokens minted for the lot
+    pub attested_price: u64, // Per whole token, USD_DECIMALS
+    pub attested_at: i64,
+    pub attestation_expiry: i64,
+    pub bump: u8,
+}
+
+impl SeedLot {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // registry
+        8 + // lot_id
+        32 + // attestor
+        4 + MAX_SEED_LOT_TEXT_LEN + // crop
+        4 + MAX_SEED_LOT_TEXT_LEN + // variety
+        4 + MAX_SEED_LOT_TEXT_LEN + // warehouse
+        8 + // harvest_date
+        8 + // germination_rate
+        8 + // quantity
+        8 + // attested_price
+        8 + // attested_at
+        8 + // attestation_expiry
+        1; // bump
+
+    pub fn validate_description(crop: &str, variety: &str, warehouse: &str) -> Result<()> {
+        for text in [crop, variety, warehouse] {
+            require!(
+                !text.is_empty() && text.len() <= MAX_SEED_LOT_TEXT_LEN,
+                ErrorCode::InvalidSeedLot
+            );
+        }
+        Ok(())
+    }
+
+    pub fn validate_attestation(price: u64, expiry: i64, now: i64) -> Result<()> {
+        require!(price > 0, ErrorCode::InvalidAttestation);
+        require!(
+            expiry > now && expiry - now <= MAX_ATTESTATION_PERIOD,
+            ErrorCode::InvalidAttestation
+        );
+        Ok(())
+    }
+
+    /// Haircut in basis points: `SEED_LOT_AGE_HAIRCUT` per year since harvest
+    /// plus `SEED_LOT_STALE_HAIRCUT` per day past the attestation expiry,
+    /// capped at the whole price.
+    pub fn haircut(&self, now: i64) -> u64 {
+        let age = (now - self.harvest_date).max(0) as u128;
+        let age_haircut = age * SEED_LOT_AGE_HAIRCUT as u128 / SECONDS_PER_YEAR as u128;
+        let stale = (now - self.attestation_expiry).max(0) as u128;
+        let stale_haircut = stale * SEED_LOT_STALE_HAIRCUT as u128 / SECONDS_PER_DAY as u128;
+        (age_haircut + stale_haircut).min(BASIS_POINTS as u128) as u64
+    }
+
+    pub fn haircut_price(&self, now: i64) -> u64 {
+        let remaining = BASIS_POINTS - self.haircut(now);
+        (self.attested_price as u128 * remaining as u128 / BASIS_POINTS as u128) as u64
+    }
+}
@@ -0,0 +1,328 @@
+use anchor_lang::prelude::*;
+use crate::constants::*;
+use crate::error::ErrorCode;
+
+/// Collateral a stability pool receives from liquidations of one pool.
+/// `sums` holds the reward sum S for the last `STABILITY_SUM_HISTORY` scales,
+/// indexed by `scale % STABILITY_SUM_HISTORY`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct StabilityPoolCollateral {
+    pub collateral_pool: Pubkey,
+    pub vault: Pubkey,
+    pub sums: [u128; STABILITY_SUM_HISTORY],
+}
+
+impl StabilityPoolCollateral {
+    pub const LEN: usize = 32 + // collateral_pool
+        32 + // vault
+        16 * STABILITY_SUM_HISTORY; // sums
+}
+
+/// SEEDS deposits that absorb liquidated debt in exchange for the seized
+/// collateral. Shares are tracked with Liquity's product-sum scheme: a
+/// deposit `d` made at product `P0` is worth `d * P / P0` now, and its
+/// collateral gain is `d * (S - S0) / P0`.
+#[account]
+pub struct StabilityPool {
+    pub protocol_state: Pubkey,
+    pub seeds_vault: Pubkey,
+    pub total_deposits: u64,
+    pub product: u128, // P, PRODUCT_PRECISION = 1.0
+    pub scale: u64, // Times P has been rescaled by SCALE_FACTOR
+    pub collaterals: Vec<StabilityPoolCollateral>, // Up to MAX_STABILITY_POOL_COLLATERALS
+    pub bump: u8,
+}
+
+impl StabilityPool {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // protocol_state
+        32 + // seeds_vault
+        8 + // total_deposits
+        16 + // product
+        8 + // scale
+        4 + StabilityPoolCollateral::LEN * MAX_STABILITY_POOL_COLLATERALS + // collaterals
+        1; // bump
+
+    pub fn find_collateral(&self, collateral_pool: &Pubkey) -> Option<usize> {
+        self.collaterals.iter().position(|entry| entry.collateral_pool == *collateral_pool)
+    }
+
+    fn sum_at(&self, index: usize, scale: u64) -> u128 {
+        self.collaterals[index].sums[(scale % STABILITY_SUM_HISTORY as u64) as usize]
+    }
+
+    /// Most debt one liquidation may offset. P never shrinks by more than
+    /// `SCALE_FACTOR` at once, so the pool is never emptied outright.
+    pub fn max_offset(&self) -> u64 {
+        let reserve = self.total_deposits.div_ceil(SCALE_FACTOR as u64);
+        self.total_deposits.saturating_sub(reserve)
+    }
+
+    /// Burns `debt_loss` of pooled SEEDS against `collateral_gain` of the
+    /// collateral at `index`, updating S and P.
+    pub fn offset(&mut self, index: usize, debt_loss: u64, collateral_gain: u64) -> Result<()> {
+        require!(debt_loss <= self.max_offset(), ErrorCode::StabilityPoolDepleted);
+        if debt_loss == 0 {
+            return Ok(());
+        }
+        let total = self.total_deposits as u128;
+
+        let sum_delta = (collateral_gain as u128)
+            .checked_mul(self.product)
+            .ok_or(ErrorCode::MathOverflow)?
+            / total;
+        let slot = (self.scale % STABILITY_SUM_HISTORY as u64) as usize;
+        let sum = &mut self.collaterals[index].sums[slot];
+        *sum = sum.checked_add(sum_delta).ok_or(ErrorCode::MathOverflow)?;
+
+        let product_factor = PRODUCT_PRECISION - (debt_loss as u128 * PRODUCT_PRECISION) / total;
+        let product = self.product * product_factor / PRODUCT_PRECISION;
+        if product < SCALE_FACTOR {
+            self.product = self.product * product_factor / (PRODUCT_PRECISION / SCALE_FACTOR);
+            self.scale += 1;
+            let slot = (self.scale % STABILITY_SUM_HISTORY as u64) as usize;
+            for entry in self.collaterals.iter_mut() {
+                entry.sums[slot] = 0;
+            }
+        } else {
+            self.product = product;
+        }
+
+        self.total_deposits -= debt_loss;
+        Ok(())
+    }
+
+    /// Current value of a deposit after the losses absorbed since its snapshot.
+    pub fn compounded_deposit(&self, deposit: &StabilityDeposit) -> u64 {
+        if deposit.deposit == 0 {
+            return 0;
+        }
+        let value = match self.scale - deposit.scale_snapshot {
+            0 => deposit.deposit as u128 * self.product / deposit.product_snapshot,
+            1 => deposit.deposit as u128 * self.product / deposit.product_snapshot / SCALE_FACTOR,
+            _ => 0,
+        };
+        value as u64
+    }
+
+    /// Collateral earned by a deposit since its snapshot. Gains from scales
+    /// that have dropped out of the sum history are forfeited.
+    pub fn collateral_gain(&self, deposit: &StabilityDeposit, index: usize) -> Result<u64> {
+        let scales_behind = self.scale - deposit.scale_snapshot;
+        if deposit.deposit == 0 || scales_behind >= STABILITY_SUM_HISTORY as u64 {
+            return Ok(0);
+        }
+
+        let sum_snapshot = deposit.gains.get(index).map(|gain| gain.sum_snapshot).unwrap_or(0);
+        let first = self.sum_at(index, deposit.scale_snapshot).saturating_sub(sum_snapshot);
+        let second = if scales_behind > 0 {
+            self.sum_at(index, deposit.scale_snapshot + 1) / SCALE_FACTOR
+        } else {
+            0
+        };
+
+        let gain = (deposit.deposit as u128)
+            .checked_mul(first + second)
+            .ok_or(ErrorCode::MathOverflow)?
+            / deposit.product_snapshot;
+        u64::try_from(gain).map_err(|_| ErrorCode::MathOverflow.into())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct DepositorGain {
+    pub sum_snapshot: u128,
+    pub pending: u64, // Collateral realized but not yet claimed
+}
+
+impl DepositorGain {
+    pub const LEN: usize = 16 + // sum_snapshot
+        8; // pending
+}
+
+/// A depositor's share of the stability pool. `gains` lines up with
+/// `StabilityPool::collaterals` by index.
+#[account]
+pub struct StabilityDeposit {
+    pub owner: Pubkey,
+    pub deposit: u64, // Value at the snapshot
+    pub product_snapshot: u128,
+    pub scale_snapshot: u64,
+    pub gains: Vec<DepositorGain>, // Up to MAX_STABILITY_POOL_COLLATERALS
+    pub bump: u8,
+}
+
+impl StabilityDeposit {
+    pub const LEN: usize = 8 + // discriminator
+        32 + // owner
+        8 + // deposit
+        16 + // product_snapshot
+        8 + // scale_snapshot
+        4 + DepositorGain::LEN * MAX_STABILITY_POOL_COLLATERALS + // gains
+        1; // bump
+
+    /// Moves gains earned since the snapshot into `pending`, compounds the
+    /// deposit and snapshots the pool's current P, scale and sums.
+    pub fn sync(&mut self, pool: &StabilityPool) -> Result<()> {
+        for index in 0..pool.collaterals.len() {
+            let gain = pool.collateral_gain(self, index)?;
+            if index >= self.gains.len() {
+                self.gains.push(DepositorGain::default());
+            }
+            let entry = &mut self.gains[index];
+            entry.pending = entry.pending.checked_add(gain).ok_or(ErrorCode::MathOverflow)?;
+            entry.sum_snapshot = pool.sum_at(index, pool.scale);
+        }
+
+        self.deposit = pool.compounded_deposit(self);
+        self.product_snapshot = pool.product;
+        self.scale_snapshot = pool.scale;
+        Ok(())
+    }
+
+    /// Takes up to `available` of the pending gain at `index`; whatever the
+    /// vault cannot cover stays pending for a later claim.
+    pub fn take_gain(&mut self, index: usize, available: u64) -> u64 {
+        let entry = &mut self.gains[index];
+        let gain = entry.pending.min(available);
+        entry.pending -= gain;
+        gain
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stability_pool() -> StabilityPool {
+        StabilityPool {
+            protocol_state: Pubkey::default(),
+            seeds_vault: Pubkey::default(),
+            total_deposits: 0,
+            product: PRODUCT_PRECISION,
+            scale: 0,
+            collaterals: vec![StabilityPoolCollateral {
+                collateral_pool: Pubkey::new_unique(),
+                vault: Pubkey::new_unique(),
+                sums: [0; STABILITY_SUM_HISTORY],
+            }],
+            bump: 0,
+        }
+    }
+
+    /// Opens a deposit of `amount` at the pool's current snapshot.
+    fn join(pool: &mut StabilityPool, amount: u64) -> StabilityDeposit {
+        let mut deposit = StabilityDeposit {
+            owner: Pubkey::new_unique(),
+            deposit: 0,
+            product_snapshot: pool.product,
+            scale_snapshot: pool.scale,
+            gains: Vec::new(),
+            bump: 0,
+        };
+        deposit.sync(pool).unwrap();
+        deposit.deposit = amount;
+        pool.total_deposits += amount;
+        deposit
+    }
+
+    #[test]
+    fn offsets_pro_rata() {
+        let mut pool = stability_pool();
+        let first = join(&mut pool, 600_000_000);
+        let second = join(&mut pool, 400_000_000);
+
+        pool.offset(0, 500_000_000, 550_000_000).unwrap();
+
+        assert_eq!(pool.total_deposits, 500_000_000);
+        assert_eq!(pool.compounded_deposit(&first), 300_000_000);
+        assert_eq!(pool.compounded_deposit(&second), 200_000_000);
+        assert_eq!(pool.collateral_gain(&first, 0).unwrap(), 330_000_000);
+        assert_eq!(pool.collateral_gain(&second, 0).unwrap(), 220_000_000);
+    }
+
+    #[test]
+    fn offsets_no_more_than_max_offset() {
+        let mut pool = stability_pool();
+        join(&mut pool, 1_000_000_000_000);
+
+        // A reserve of 1 / SCALE_FACTOR of the deposits always stays behind
+        assert_eq!(pool.max_offset(), 1_000_000_000_000 - 1_000);
+        let result = pool.offset(0, pool.max_offset() + 1, 0);
+        assert_eq!(result.unwrap_err(), ErrorCode::StabilityPoolDepleted.into());
+
+        let mut empty = stability_pool();
+        assert_eq!(empty.max_offset(), 0);
+        empty.offset(0, 0, 0).unwrap();
+        assert_eq!(empty.product, PRODUCT_PRECISION);
+    }
+
+    #[test]
+    fn depleting_the_pool_moves_to_the_next_scale() {
+        let mut pool = stability_pool();
+        let depositor = join(&mut pool, 1_000_000_000_000);
+
+        // Drain down to the reserve twice; the second pushes P below
+        // SCALE_FACTOR and rescales it
+        pool.offset(0, pool.max_offset(), 1_100_000_000_000).unwrap();
+        assert_eq!(pool.scale, 0);
+        assert_eq!(pool.total_deposits, 1_000);
+        pool.offset(0, pool.max_offset(), 1_100).unwrap();
+        assert_eq!(pool.scale, 1);
+        assert_eq!(pool.total_deposits, 1);
+        assert_eq!(pool.product, 1_000_000_000_000_000);
+
+        // The deposit compounds across the scale change and keeps every gain
+        assert_eq!(pool.compounded_deposit(&depositor), 1);
+        assert_eq!(pool.collateral_gain(&depositor, 0).unwrap(), 1_100_000_001_100);
+
+        // A deposit opened after the rescale starts from the new snapshot
+        let late = join(&mut pool, 1_000_000);
+        assert_eq!(pool.compounded_deposit(&late), 1_000_000);
+        assert_eq!(pool.collateral_gain(&late, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn gains_older_than_the_sum_history_are_forfeited() {
+        let mut pool = stability_pool();
+        let depositor = join(&mut pool, 1_000_000);
+        pool.offset(0, 500_000, 550_000).unwrap();
+        assert_eq!(pool.collateral_gain(&depositor, 0).unwrap(), 550_000);
+
+        pool.scale = STABILITY_SUM_HISTORY as u64;
+        assert_eq!(pool.collateral_gain(&depositor, 0).unwrap(), 0);
+        assert_eq!(pool.compounded_deposit(&depositor), 0);
+    }
+
+    #[test]
+    fn sync_realizes_gains_and_resnapshots() {
+        let mut pool = stability_pool();
+        let mut depositor = join(&mut pool, 1_000_000);
+        pool.offset(0, 250_000, 300_000).unwrap();
+
+        depositor.sync(&pool).unwrap();
+        assert_eq!(depositor.deposit, 750_000);
+        assert_eq!(depositor.gains[0].pending, 300_000);
+        assert_eq!(pool.collateral_gain(&depositor, 0).unwrap(), 0);
+
+        // Syncing again realizes nothing twice
+        depositor.sync(&pool).unwrap();
+        assert_eq!(depositor.gains[0].pending, 300_000);
+    }
+
+    #[test]
+    fn capped_claims_keep_the_rest_pending() {
+        let mut pool = stability_pool();
+        let mut depositor = join(&mut pool, 1_000_000);
+        pool.offset(0, 500_000, 100_000).unwrap();
+        depositor.sync(&pool).unwrap();
+
+        // The vault only holds 60% of the gain
+        assert_eq!(depositor.take_gain(0, 60_000), 60_000);
+        assert_eq!(depositor.gains[0].pending, 40_000);
+
+        assert_eq!(depositor.take_gain(0, 1_000_000), 40_000);
+        assert_eq!(depositor.gains[0].pending, 0);
+        assert_eq!(depositor.take_gain(0, 1_000_000), 0);
+    }
+}
@@ -14,8 +14,12 @@ import {
   createAssociatedTokenAccount,
   mintTo,
   getAssociatedTokenAddress,
+  NATIVE_MINT,
 } from "@solana/spl-token";
 import { expect } from "chai";
+import { positionIndexHints, walkPositionIndex } from "./utils/positionIndex";
+import { cosignerAccounts, multisigSigners, singleKeySigners } from "./utils/authority";
+import { eventsOf } from "./utils/events";
 
 describe("SeedStable Integration Tests", () => {
   const provider = anchor.AnchorProvider.env();
@@ -27,15 +31,27 @@ describe("SeedStable Integration Tests", () => {
   let protocolState: PublicKey;
   let protocolStateBump: number;
   let stablecoinMint: PublicKey;
+  let treasury: PublicKey;
   let collateralMint: PublicKey;
   let collateralPool: PublicKey;
   let collateralPoolBump: number;
   let userPosition: PublicKey;
   let userPositionBump: number;
+  let positionIndex: PublicKey;
+  const stablecoinMintKeypair = Keypair.generate();
 
   const PROTOCOL_SEED = "protocol";
   const COLLATERAL_POOL_SEED = "collateral_pool";
   const USER_POSITION_SEED = "user_position";
+  const TREASURY_SEED = "treasury";
+  const SOL_UNWRAP_SEED = "sol_unwrap";
+
+  // Pause flags, see programs/seedstable/src/constants.rs
+  const PAUSE_WITHDRAW = 1 << 1;
+  const PAUSE_MINT = 1 << 2;
+  const PAUSE_LIQUIDATE = 1 << 4;
+  const PAUSE_REBALANCE = 1 << 5;
+  const POSITION_INDEX_SEED = "position_index";
 
   before(async () => {
     // Find PDAs
@@ -44,15 +60,16 @@ describe("SeedStable Integration Tests", () => {
       program.programId
     );
 
-    // Create test mints
-    stablecoinMint = await createMint(
-      provider.connection,
-      payer.payer,
-      protocolState,
-      null,
-      6
+    [treasury] = PublicKey.findProgramAddressSync(
+      [Buffer.from(TREASURY_SEED)],
+      program.programId
     );
 
+    // The stablecoin mint is created by `initialize`
+    stablecoinMint = stablecoinMintKeypair.publicKey;
+
+    // Create test mints
+
     collateralMint = await createMint(
       provider.connection,
       payer.payer,
@@ -70,32 +87,56 @@ describe("SeedStable Integration Tests", () => {
       [Buffer.from(USER_POSITION_SEED), payer.publicKey.toBuffer()],
       program.programId
     );
+
+    [positionIndex] = PublicKey.findProgramAddressSync(
+      [Buffer.from(POSITION_INDEX_SEED)],
+      program.programId
+    );
   });
 
   it("Initializes the protocol", async () => {
     try {
       await program.methods
-        .initialize(protocolStateBump)
+        .initialize(protocolStateBump, null)
         .accounts({
           protocolState,
           stablecoinMint,
+          treasury,
           authority: payer.publicKey,
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
           rent: SYSVAR_RENT_PUBKEY,
         })
+        .signers([stablecoinMintKeypair])
         .rpc();
 
       const protocolStateAccount = await program.account.protocolState.fetch(protocolState);
       expect(protocolStateAccount.authority.toString()).to.equal(payer.publicKey.toString());
       expect(protocolStateAccount.stablecoinMint.toString()).to.equal(stablecoinMint.toString());
-      expect(protocolStateAccount.isPaused).to.be.false;
+      expect(protocolStateAccount.pausedOperations).to.equal(0);
+      expect(protocolStateAccount.recoveryMode).to.be.false;
     } catch (error) {
       console.log("Initialize error:", error);
       throw error;
     }
   });
 
+  it("Initializes the position index", async () => {
+    await program.methods
+      .initializePositionIndex()
+      .accounts({
+        authority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
+        positionIndex,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const index = await program.account.positionIndex.fetch(positionIndex);
+    expect(index.size.toNumber()).to.equal(0);
+  });
+
   it("Can pause and unpause the protocol", async () => {
     // Pause
     await program.methods
@@ -103,11 +144,12 @@ describe("SeedStable Integration Tests", () => {
       .accounts({
         protocolState,
         authority: payer.publicKey,
+        signers: singleKeySigners(),
       })
       .rpc();
 
     let protocolStateAccount = await program.account.protocolState.fetch(protocolState);
-    expect(protocolStateAccount.isPaused).to.be.true;
+    expect(protocolStateAccount.pausedOperations).to.not.equal(0);
 
     // Unpause
     await program.methods
@@ -115,11 +157,91 @@ describe("SeedStable Integration Tests", () => {
       .accounts({
         protocolState,
         authority: payer.publicKey,
+        signers: singleKeySigners(),
+      })
+      .rpc();
+
+    protocolStateAccount = await program.account.protocolState.fetch(protocolState);
+    expect(protocolStateAccount.pausedOperations).to.equal(0);
+  });
+
+  it("Hands the authority to a 2-of-3 multisig and back", async () => {
+    const owners = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const multisig = Keypair.generate();
+    await program.methods
+      .createMultisig(owners.map((owner) => owner.publicKey), 2)
+      .accounts({
+        payer: payer.publicKey,
+        multisig: multisig.publicKey,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([multisig])
+      .rpc();
+
+    await program.methods
+      .proposeAuthority(multisig.publicKey)
+      .accounts({
+        authority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
+      })
+      .rpc();
+
+    // Accepting on behalf of the multisig takes two owners
+    await program.methods
+      .acceptAuthority()
+      .accounts({
+        newAuthority: owners[0].publicKey,
+        signers: multisigSigners(multisig.publicKey),
+        protocolState,
+      })
+      .remainingAccounts(cosignerAccounts([owners[1]]))
+      .signers([owners[0], owners[1]])
+      .rpc();
+
+    let protocolStateAccount = await program.account.protocolState.fetch(protocolState);
+    expect(protocolStateAccount.authority.toString()).to.equal(multisig.publicKey.toString());
+
+    // One owner alone cannot pause
+    try {
+      await program.methods
+        .emergencyPause(true)
+        .accounts({
+          protocolState,
+          authority: owners[0].publicKey,
+          signers: multisigSigners(multisig.publicKey),
+        })
+        .signers([owners[0]])
+        .rpc();
+      expect.fail("Pause should need two owners");
+    } catch (error) {
+      expect(error.toString()).to.include("Unauthorized");
+    }
+
+    // Hand the authority back
+    await program.methods
+      .proposeAuthority(payer.publicKey)
+      .accounts({
+        authority: owners[2].publicKey,
+        signers: multisigSigners(multisig.publicKey),
+        protocolState,
+      })
+      .remainingAccounts(cosignerAccounts([owners[0]]))
+      .signers([owners[2], owners[0]])
+      .rpc();
+
+    await program.methods
+      .acceptAuthority()
+      .accounts({
+        newAuthority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
       })
       .rpc();
 
     protocolStateAccount = await program.account.protocolState.fetch(protocolState);
-    expect(protocolStateAccount.isPaused).to.be.false;
+    expect(protocolStateAccount.authority.toString()).to.equal(payer.publicKey.toString());
+    expect(protocolStateAccount.pendingAuthority.toString()).to.equal(PublicKey.default.toString());
   });
 
   it("Creates collateral pool and deposits collateral", async () => {
@@ -150,9 +272,6 @@ describe("SeedStable Integration Tests", () => {
       true
     );
 
-    // Create oracle account (mock)
-    const oracleAccount = Keypair.generate();
-
     try {
       await program.methods
         .depositCollateral({ sol: {} }, new anchor.BN(100 * 10**9)) // 100 tokens
@@ -164,7 +283,7 @@ describe("SeedStable Integration Tests", () => {
           collateralMint,
           collateralVault,
           userCollateralAccount,
-          oracleAccount: oracleAccount.publicKey,
+          index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
           systemProgram: SystemProgram.programId,
           tokenProgram: TOKEN_PROGRAM_ID,
           rent: SYSVAR_RENT_PUBKEY,
@@ -190,26 +309,212 @@ describe("SeedStable Integration Tests", () => {
     );
 
     try {
-      await program.methods
+      const signature = await program.methods
         .mintStable(new anchor.BN(50 * 10**6)) // 50 stablecoins with 6 decimals
         .accounts({
           user: payer.publicKey,
           protocolState,
           userPosition,
+          collateralPool,
           stablecoinMint,
           userStablecoinAccount,
+          index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
           tokenProgram: TOKEN_PROGRAM_ID,
         })
-        .rpc();
+        .rpc({ commitment: "confirmed" });
 
       const userPositionAccount = await program.account.userPosition.fetch(userPosition);
-      expect(userPositionAccount.stablecoinMinted.toNumber()).to.be.greaterThan(0);
+      expect(userPositionAccount.normalizedDebt.toNumber()).to.be.greaterThan(0);
+      expect(userPositionAccount.inIndex).to.be.true;
+
+      const minted = (await eventsOf(program, signature)).find((event) => event.name === "StablecoinMinted");
+      expect(minted.data.position.toString()).to.equal(userPosition.toString());
+      expect(minted.data.amount.toNumber()).to.equal(50 * 10**6);
+
+      const [head] = await walkPositionIndex(program, positionIndex, 1);
+      expect(head.publicKey.toString()).to.equal(userPosition.toString());
     } catch (error) {
       console.log("Mint stable error:", error);
       throw error;
     }
   });
 
+  it("Guardian pause leaves repayments open", async () => {
+    const guardian = Keypair.generate();
+    const userStablecoinAccount = await getAssociatedTokenAddress(
+      stablecoinMint,
+      payer.publicKey
+    );
+
+    await program.methods
+      .setGuardian(guardian.publicKey)
+      .accounts({
+        authority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
+      })
+      .rpc();
+
+    await program.methods
+      .guardianPause(PAUSE_WITHDRAW | PAUSE_MINT | PAUSE_LIQUIDATE | PAUSE_REBALANCE)
+      .accounts({
+        guardian: guardian.publicKey,
+        protocolState,
+      })
+      .signers([guardian])
+      .rpc();
+
+    const stableAccounts = {
+      user: payer.publicKey,
+      protocolState,
+      userPosition,
+      collateralPool,
+      stablecoinMint,
+      userStablecoinAccount,
+      index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
+      tokenProgram: TOKEN_PROGRAM_ID,
+    };
+
+    try {
+      await program.methods
+        .mintStable(new anchor.BN(1 * 10**6))
+        .accounts(stableAccounts)
+        .rpc();
+      expect.fail("Minting should be paused");
+    } catch (error) {
+      expect(error.toString()).to.include("ProtocolPaused");
+    }
+
+    await program.methods
+      .burnStable(new anchor.BN(1 * 10**6))
+      .accounts(stableAccounts)
+      .rpc();
+
+    // Only the authority can lift the pause
+    await program.methods
+      .setPausedOperations(0)
+      .accounts({
+        authority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
+      })
+      .rpc();
+
+    const protocolStateAccount = await program.account.protocolState.fetch(protocolState);
+    expect(protocolStateAccount.pausedOperations).to.equal(0);
+  });
+
+  it("Freezes, resizes and sunsets a collateral pool", async () => {
+    const userStablecoinAccount = await getAssociatedTokenAddress(
+      stablecoinMint,
+      payer.publicKey
+    );
+    const poolAdmin = {
+      authority: payer.publicKey,
+      signers: singleKeySigners(),
+      protocolState,
+      collateralPool,
+    };
+
+    await program.methods
+      .setPoolFrozen(true)
+      .accounts(poolAdmin)
+      .rpc();
+
+    try {
+      await program.methods
+        .mintStable(new anchor.BN(1 * 10**6))
+        .accounts({
+          user: payer.publicKey,
+          protocolState,
+          userPosition,
+          collateralPool,
+          stablecoinMint,
+          userStablecoinAccount,
+          index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
+          tokenProgram: TOKEN_PROGRAM_ID,
+        })
+        .rpc();
+      expect.fail("Minting against a frozen pool should fail");
+    } catch (error) {
+      expect(error.toString()).to.include("CollateralPoolInactive");
+    }
+
+    await program.methods
+      .setPoolFrozen(false)
+      .accounts(poolAdmin)
+      .rpc();
+
+    const capsSignature = await program.methods
+      .setPoolCaps(new anchor.BN(5_000 * 10**9), new anchor.BN(1_000_000 * 10**6))
+      .accounts(poolAdmin)
+      .rpc({ commitment: "confirmed" });
+    const changed = (await eventsOf(program, capsSignature)).filter((event) => event.name === "ParameterChanged");
+    expect(changed.map((event) => Object.keys(event.data.parameter)[0])).to.deep.equal(["maxDeposit", "debtCeiling"]);
+
+    // Above the SOL deposit cap
+    try {
+      await program.methods
+        .setPoolCaps(new anchor.BN("20000000000000"), new anchor.BN(1_000_000 * 10**6))
+        .accounts(poolAdmin)
+        .rpc();
+      expect.fail("Deposit cap should be range-checked");
+    } catch (error) {
+      expect(error.toString()).to.include("InvalidPoolCaps");
+    }
+
+    const week = 7 * 24 * 60 * 60;
+    await program.methods
+      .startPoolSunset(new anchor.BN(7000), new anchor.BN(4 * week))
+      .accounts(poolAdmin)
+      .rpc();
+
+    let pool = await program.account.collateralPool.fetch(collateralPool);
+    expect(pool.isActive).to.be.false;
+    expect(pool.sunsetStart.toNumber()).to.be.greaterThan(0);
+    expect(pool.sunsetThreshold.toNumber()).to.equal(7000);
+
+    await program.methods
+      .cancelPoolSunset()
+      .accounts(poolAdmin)
+      .rpc();
+    await program.methods
+      .setPoolFrozen(false)
+      .accounts(poolAdmin)
+      .rpc();
+
+    pool = await program.account.collateralPool.fetch(collateralPool);
+    expect(pool.isActive).to.be.true;
+    expect(pool.sunsetStart.toNumber()).to.equal(0);
+  });
+
+  it("Reports position health and protocol stats as return data", async () => {
+    const [priceHistory] = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_history"), collateralPool.toBuffer()],
+      program.programId
+    );
+
+    const health = await program.methods
+      .getPositionHealth()
+      .accounts({ protocolState, userPosition })
+      .remainingAccounts([
+        { pubkey: collateralPool, isSigner: false, isWritable: false },
+        { pubkey: priceHistory, isSigner: false, isWritable: false },
+      ])
+      .view();
+    expect(health.debt.toNumber()).to.be.greaterThan(0);
+    expect(health.pools).to.have.length(1);
+    expect(health.pools[0].pool.toString()).to.equal(collateralPool.toString());
+    expect(health.liquidatable).to.be.false;
+
+    const stats = await program.methods
+      .getProtocolStats()
+      .accounts({ protocolState })
+      .view();
+    expect(stats.totalStablecoinSupply.toNumber()).to.be.greaterThan(0);
+    expect(stats.recoveryMode).to.equal(false);
+  });
+
   it("Burns stablecoins", async () => {
     const userStablecoinAccount = await getAssociatedTokenAddress(
       stablecoinMint,
@@ -223,14 +528,16 @@ describe("SeedStable Integration Tests", () => {
           user: payer.publicKey,
           protocolState,
           userPosition,
+          collateralPool,
           stablecoinMint,
           userStablecoinAccount,
+          index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
 
       const userPositionAccount = await program.account.userPosition.fetch(userPosition);
-      expect(userPositionAccount.stablecoinMinted.toNumber()).to.be.lessThan(50 * 10**6);
+      expect(userPositionAccount.normalizedDebt.toNumber()).to.be.lessThan(50 * 10**6);
     } catch (error) {
       console.log("Burn stable error:", error);
       throw error;
@@ -258,7 +565,9 @@ describe("SeedStable Integration Tests", () => {
           userPosition,
           collateralPool,
           collateralVault,
+          collateralMint,
           userCollateralAccount,
+          index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
           tokenProgram: TOKEN_PROGRAM_ID,
         })
         .rpc();
@@ -270,4 +579,143 @@ describe("SeedStable Integration Tests", () => {
       throw error;
     }
   });
+
+  it("Deposits and withdraws native SOL without a wSOL account", async () => {
+    const [solPool] = PublicKey.findProgramAddressSync(
+      [Buffer.from(COLLATERAL_POOL_SEED), NATIVE_MINT.toBuffer()],
+      program.programId
+    );
+    const [solPriceHistory] = PublicKey.findProgramAddressSync(
+      [Buffer.from("price_history"), solPool.toBuffer()],
+      program.programId
+    );
+    const [unwrapAccount] = PublicKey.findProgramAddressSync(
+      [Buffer.from(SOL_UNWRAP_SEED), payer.publicKey.toBuffer()],
+      program.programId
+    );
+    const pool = await program.account.collateralPool.fetch(solPool);
+    const amount = new anchor.BN(2 * 10**9);
+
+    const vaultBefore = await provider.connection.getTokenAccountBalance(pool.vault);
+    await program.methods
+      .depositSol(amount)
+      .accounts({
+        user: payer.publicKey,
+        protocolState,
+        userPosition,
+        collateralPool: solPool,
+        collateralVault: pool.vault,
+        index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    const vaultAfter = await provider.connection.getTokenAccountBalance(pool.vault);
+    expect(Number(vaultAfter.value.amount) - Number(vaultBefore.value.amount)).to.equal(amount.toNumber());
+
+    const lamportsBefore = await provider.connection.getBalance(payer.publicKey);
+    await program.methods
+      .withdrawSol(amount)
+      .accounts({
+        user: payer.publicKey,
+        protocolState,
+        userPosition,
+        collateralPool: solPool,
+        priceHistory: solPriceHistory,
+        collateralVault: pool.vault,
+        collateralMint: NATIVE_MINT,
+        unwrapAccount,
+        index: await positionIndexHints(program, positionIndex, userPosition, new anchor.BN(0)),
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    // The withdrawn SOL arrives as lamports, less the transaction fee
+    const lamportsAfter = await provider.connection.getBalance(payer.publicKey);
+    expect(lamportsAfter - lamportsBefore).to.be.greaterThan(amount.toNumber() - 10_000);
+    expect(await provider.connection.getAccountInfo(unwrapAccount)).to.be.null;
+  });
+
+  it("Registers an attested seed lot and prices the registry", async () => {
+    const attestor = Keypair.generate();
+    await provider.connection.confirmTransaction(
+      await provider.connection.requestAirdrop(attestor.publicKey, 10**9),
+      "confirmed"
+    );
+
+    // The registry PDA must be the mint authority before the registry exists
+    const mintKeypair = Keypair.generate();
+    const [registry] = PublicKey.findProgramAddressSync(
+      [Buffer.from("seed_lot_registry"), mintKeypair.publicKey.toBuffer()],
+      program.programId
+    );
+    const seedsMint = await createMint(provider.connection, payer.payer, registry, null, 6, mintKeypair);
+    const recipient = await createAssociatedTokenAccount(provider.connection, payer.payer, seedsMint, payer.publicKey);
+
+    await program.methods
+      .createSeedLotRegistry()
+      .accounts({
+        authority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
+        seedLotRegistry: registry,
+        mint: seedsMint,
+        systemProgram: SystemProgram.programId,
+      })
+      .rpc();
+
+    await program.methods
+      .setSeedLotAttestor(attestor.publicKey, true)
+      .accounts({
+        authority: payer.publicKey,
+        signers: singleKeySigners(),
+        protocolState,
+        seedLotRegistry: registry,
+      })
+      .rpc();
+
+    const now = Math.floor(Date.now() / 1000);
+    const [seedLot] = PublicKey.findProgramAddressSync(
+      [Buffer.from("seed_lot"), registry.toBuffer(), new anchor.BN(0).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    );
+    await program.methods
+      .registerSeedLot({
+        crop: "maize",
+        variety: "DK-777",
+        warehouse: "Nakuru-3",
+        harvestDate: new anchor.BN(now - 60),
+        germinationRate: new anchor.BN(9200),
+        quantity: new anchor.BN(1_000 * 10**6),
+        attestedPrice: new anchor.BN(50 * 10**6),
+        attestationExpiry: new anchor.BN(now + 30 * 24 * 60 * 60),
+      })
+      .accounts({
+        attestor: attestor.publicKey,
+        seedLotRegistry: registry,
+        seedLot,
+        mint: seedsMint,
+        recipientTokenAccount: recipient,
+        tokenProgram: TOKEN_PROGRAM_ID,
+        systemProgram: SystemProgram.programId,
+      })
+      .signers([attestor])
+      .rpc();
+
+    const balance = await provider.connection.getTokenAccountBalance(recipient);
+    expect(balance.value.amount).to.equal((1_000 * 10**6).toString());
+
+    await program.methods
+      .refreshSeedLotRegistry()
+      .accounts({ seedLotRegistry: registry })
+      .remainingAccounts([{ pubkey: seedLot, isSigner: false, isWritable: false }])
+      .rpc();
+
+    // A freshly harvested, freshly attested lot is priced with almost no haircut
+    const registryAccount = await program.account.seedLotRegistry.fetch(registry);
+    expect(registryAccount.lotCount.toNumber()).to.equal(1);
+    expect(registryAccount.price.toNumber()).to.be.within(49_900_000, 50 * 10**6);
+  });
 });
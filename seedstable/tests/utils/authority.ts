@@ -0,0 +1,23 @@
+import { PublicKey, Keypair, SYSVAR_INSTRUCTIONS_PUBKEY } from "@solana/web3.js";
+
+// Builds the `signers` accounts of authority-gated instructions, see
+// programs/seedstable/src/instructions/authority.rs.
+
+/// A single-key authority signs as `authority` and needs no extra accounts.
+export function singleKeySigners() {
+  return { multisig: null, instructions: null };
+}
+
+/// A multisig authority: `authority` is one owner, the other signing owners
+/// go in as remaining accounts.
+export function multisigSigners(multisig: PublicKey) {
+  return { multisig, instructions: SYSVAR_INSTRUCTIONS_PUBKEY };
+}
+
+export function cosignerAccounts(owners: Keypair[]) {
+  return owners.map((owner) => ({
+    pubkey: owner.publicKey,
+    isSigner: true,
+    isWritable: false,
+  }));
+}
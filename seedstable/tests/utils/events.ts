@@ -0,0 +1,13 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { Seedstable } from "../../target/types/seedstable";
+
+/// Decodes the events a confirmed transaction emitted, in order.
+export async function eventsOf(program: Program<Seedstable>, signature: string) {
+  const tx = await program.provider.connection.getTransaction(signature, {
+    commitment: "confirmed",
+    maxSupportedTransactionVersion: 0,
+  });
+  const parser = new anchor.EventParser(program.programId, new anchor.BorshCoder(program.idl));
+  return Array.from(parser.parseLogs(tx?.meta?.logMessages ?? []));
+}
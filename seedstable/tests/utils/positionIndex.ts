@@ -0,0 +1,72 @@
+import * as anchor from "@coral-xyz/anchor";
+import { Program } from "@coral-xyz/anchor";
+import { PublicKey } from "@solana/web3.js";
+import { Seedstable } from "../../target/types/seedstable";
+
+// Mirrors the on-chain list in programs/seedstable/src/state/position_index.rs:
+// positions with debt, linked from the lowest nominal ratio (head) up.
+
+function orNull(key: PublicKey): PublicKey | null {
+  return key.equals(PublicKey.default) ? null : key;
+}
+
+/// Walks the index from the riskiest position, returning up to `limit` entries.
+export async function walkPositionIndex(
+  program: Program<Seedstable>,
+  positionIndex: PublicKey,
+  limit = Infinity
+) {
+  const index = await program.account.positionIndex.fetch(positionIndex);
+  const positions = [];
+  let cursor = index.head;
+  while (!cursor.equals(PublicKey.default) && positions.length < limit) {
+    const account = await program.account.userPosition.fetch(cursor);
+    positions.push({ publicKey: cursor, account });
+    cursor = account.nextPosition;
+  }
+  return positions;
+}
+
+/// Builds the `index` accounts for an instruction that leaves `userPosition`
+/// at nominal ratio `sortKey`: its current neighbours and the neighbours it
+/// will sit between once re-inserted.
+export async function positionIndexHints(
+  program: Program<Seedstable>,
+  positionIndex: PublicKey,
+  userPosition: PublicKey,
+  sortKey: anchor.BN
+) {
+  const index = await program.account.positionIndex.fetch(positionIndex);
+  const current = await program.account.userPosition.fetchNullable(userPosition);
+
+  let prevPosition: PublicKey | null = null;
+  let nextPosition: PublicKey | null = null;
+  if (current?.inIndex) {
+    prevPosition = orNull(current.prevPosition);
+    nextPosition = orNull(current.nextPosition);
+  }
+
+  // Find the insertion point as it will be once the position is unlinked
+  let insertPrev: PublicKey | null = null;
+  let cursor = index.head;
+  while (!cursor.equals(PublicKey.default)) {
+    if (cursor.equals(userPosition)) {
+      cursor = current.nextPosition;
+      continue;
+    }
+    const account = await program.account.userPosition.fetch(cursor);
+    if (account.sortKey.gt(sortKey)) {
+      break;
+    }
+    insertPrev = cursor;
+    cursor = account.nextPosition;
+  }
+
+  return {
+    positionIndex,
+    prevPosition,
+    nextPosition,
+    insertPrev,
+    insertNext: orNull(cursor),
+  };
+}
@@ -0,0 +1,50 @@
+import { PublicKey } from "@solana/web3.js";
+
+// Pyth v2 price account layout, mirrored from programs/seedstable/src/oracle/pyth.rs
+const PYTH_MAGIC = 0xa1b2c3d4;
+const PYTH_VERSION = 2;
+const PYTH_ACCOUNT_TYPE_PRICE = 3;
+const PYTH_PRICE_ACCOUNT_LEN = 3312;
+
+export const PYTH_STATUS_TRADING = 1;
+
+export interface PythPriceFixture {
+  price: bigint;
+  confidence: bigint;
+  exponent: number;
+  publishTime: number;
+  status?: number;
+}
+
+export function encodePythPriceAccount(fixture: PythPriceFixture): Buffer {
+  const data = Buffer.alloc(PYTH_PRICE_ACCOUNT_LEN);
+  data.writeUInt32LE(PYTH_MAGIC, 0);
+  data.writeUInt32LE(PYTH_VERSION, 4);
+  data.writeUInt32LE(PYTH_ACCOUNT_TYPE_PRICE, 8);
+  data.writeUInt32LE(PYTH_PRICE_ACCOUNT_LEN, 12);
+  data.writeUInt32LE(1, 16); // price type
+  data.writeInt32LE(fixture.exponent, 20);
+  data.writeBigInt64LE(BigInt(fixture.publishTime), 96);
+  data.writeBigInt64LE(fixture.price, 208);
+  data.writeBigUInt64LE(fixture.confidence, 216);
+  data.writeUInt32LE(fixture.status ?? PYTH_STATUS_TRADING, 224);
+  return data;
+}
+
+// Account JSON in the format accepted by `[[test.validator.account]]` in Anchor.toml
+export function pythFixtureAccountJson(
+  address: PublicKey,
+  owner: PublicKey,
+  fixture: PythPriceFixture
+) {
+  return {
+    pubkey: address.toBase58(),
+    account: {
+      lamports: 23_942_400,
+      data: [encodePythPriceAccount(fixture).toString("base64"), "base64"],
+      owner: owner.toBase58(),
+      executable: false,
+      rentEpoch: 0,
+    },
+  };
+}